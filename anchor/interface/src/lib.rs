@@ -0,0 +1,613 @@
+//! Instruction builders for composing with zkcash, either from clients or through CPI.
+//!
+//! A program can deposit from a PDA vault by building a `transact` instruction with the
+//! vault as `signer` and invoking it with `invoke_transact` and the vault's seeds. The vault
+//! must be owned by the system program, because the deposit is a system transfer.
+//!
+//! A withdraw-and-call commits a `WithdrawCall` in `ExtData.call`. After the withdrawn
+//! lamports reach `recipient`, zkcash invokes the target program with the committed data
+//! and accounts, which `withdraw_and_call` appends.
+//!
+//! A split withdrawal commits its recipients and amounts in `ExtData.payouts`, and
+//! `withdraw_to_many` passes the recipients as remaining accounts.
+//!
+//! A pool can own several tree shards that share the vault created by `initialize`, and a
+//! full shard is rolled over to a new tree. Outputs go to the shard in `tree_index`, which
+//! must be active, and a proof against another tree's root names that tree in
+//! `root_tree_index`. Both inputs must be notes of that one tree. The builders pass the tree
+//! set for every tree but tree 0.
+//!
+//! An `ExtData.association` proves that both inputs are in an association set, a set of
+//! approved notes whose roots a provider publishes with `update_association_root`. The
+//! builders pass the set it names and the pool's association verifying key.
+//!
+//! A tree with a commitment queue takes outputs into the queue, and `process_queue` inserts
+//! them later. Transactions into such a tree set `use_commitment_queue`.
+//!
+//! A batch deposit funds up to eight notes from one transfer. The depositor collects the
+//! outputs with `add_batch_outputs`, a few per transaction, and deposits them with
+//! `batch_deposit` and a proof that their hidden amounts add up to the deposit. The outputs
+//! are published in a `BatchDepositEvent` instead of commitment accounts.
+//!
+//! `transact` returns a `TransactReturnData`, which can be read with
+//! `get_transact_return_data` after a CPI, or with `decode_transact_return_data` from the
+//! program id and bytes of a simulation result. `check_transact` returns a `TransactVerdict`,
+//! decoded with `decode_transact_verdict`.
+//!
+//! The runtime drops trailing zero bytes from return data, so the decoders pad the data back
+//! with zeros. A `Valid` verdict is all zeros, so it comes back as no return data at all.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::{get_return_data, invoke_signed};
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+
+pub use zkcash::preflight::TransactVerdict;
+pub use zkcash::{
+    AssociationProof, AssociationVerifyingKey, AuditorOutputs, BatchDepositEvent, BatchDepositProof, BatchDepositVerifyingKey,
+    BatchOutput, ExtData, Payout, Proof, TransactReturnData, WithdrawCall, WithdrawCallAccount, ID,
+    TRANSACT_RETURN_DATA_VERSION,
+};
+
+/// The accounts of `transact` that are not derived from the proof.
+pub struct TransactAccounts {
+    pub authority: Pubkey,
+    /// The shard the outputs are appended to.
+    pub tree_index: u32,
+    /// The tree holding `proof.root`, if it's not the active tree.
+    pub root_tree_index: Option<u32>,
+    /// Whether the tree in `tree_index` has a commitment queue.
+    pub use_commitment_queue: bool,
+    pub recipient: Pubkey,
+    pub fee_recipient_account: Pubkey,
+    pub signer: Pubkey,
+}
+
+/// Tree 0 is the tree created by `initialize`, later trees are created by `rollover_tree`.
+pub fn tree_account_address(authority: &Pubkey, tree_index: u32) -> Pubkey {
+    if tree_index == 0 {
+        Pubkey::find_program_address(&[b"merkle_tree", authority.as_ref()], &ID).0
+    } else {
+        Pubkey::find_program_address(&[b"merkle_tree", authority.as_ref(), &tree_index.to_le_bytes()], &ID).0
+    }
+}
+
+pub fn tree_set_address(authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"tree_set", authority.as_ref()], &ID).0
+}
+
+pub fn commitment_queue_address(tree_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"commitment_queue", tree_account.as_ref()], &ID).0
+}
+
+pub fn tree_token_account_address(authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"tree_token", authority.as_ref()], &ID).0
+}
+
+/// The policy shared by every tree of the pool.
+pub fn pool_config_address(authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"pool_config", authority.as_ref()], &ID).0
+}
+
+pub fn association_set_address(provider: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"association_set", provider.as_ref()], &ID).0
+}
+
+/// The verifying key association proofs of the pool are checked against.
+pub fn association_verifying_key_address(authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"association_verifying_key", authority.as_ref()], &ID).0
+}
+
+/// The verifying key batch deposit proofs of the pool are checked against.
+pub fn batch_deposit_verifying_key_address(authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"batch_deposit_verifying_key", authority.as_ref()], &ID).0
+}
+
+/// The pending batch deposit of `signer` into the pool.
+pub fn batch_deposit_address(authority: &Pubkey, signer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"batch_deposit", authority.as_ref(), signer.as_ref()], &ID).0
+}
+
+pub fn nullifier_addresses(proof: &Proof) -> [Pubkey; 2] {
+    [
+        Pubkey::find_program_address(&[b"nullifier0", proof.input_nullifiers[0].as_ref()], &ID).0,
+        Pubkey::find_program_address(&[b"nullifier1", proof.input_nullifiers[1].as_ref()], &ID).0,
+    ]
+}
+
+/// The PDAs each nullifier of the proof would have in the other input slot. `transact`
+/// checks them too, so a note can't be spent again through the other slot.
+pub fn cross_slot_nullifier_addresses(proof: &Proof) -> [Pubkey; 2] {
+    [
+        Pubkey::find_program_address(&[b"nullifier0", proof.input_nullifiers[1].as_ref()], &ID).0,
+        Pubkey::find_program_address(&[b"nullifier1", proof.input_nullifiers[0].as_ref()], &ID).0,
+    ]
+}
+
+/// The two PDAs a nullifier can be recorded at, as input 0 or as input 1 of a `transact`.
+/// The note is spent if either exists.
+pub fn nullifier_account_addresses(nullifier: &[u8; 32]) -> [Pubkey; 2] {
+    [
+        Pubkey::find_program_address(&[b"nullifier0", nullifier.as_ref()], &ID).0,
+        Pubkey::find_program_address(&[b"nullifier1", nullifier.as_ref()], &ID).0,
+    ]
+}
+
+pub fn commitment_addresses(proof: &Proof) -> [Pubkey; 2] {
+    [
+        Pubkey::find_program_address(&[b"commitment0", proof.output_commitments[0].as_ref()], &ID).0,
+        Pubkey::find_program_address(&[b"commitment1", proof.output_commitments[1].as_ref()], &ID).0,
+    ]
+}
+
+/// The PDAs each output commitment of the proof would have in the other output slot.
+/// `transact` reads them to reject a commitment that already exists as the other output.
+pub fn cross_slot_commitment_addresses(proof: &Proof) -> [Pubkey; 2] {
+    [
+        Pubkey::find_program_address(&[b"commitment1", proof.output_commitments[0].as_ref()], &ID).0,
+        Pubkey::find_program_address(&[b"commitment0", proof.output_commitments[1].as_ref()], &ID).0,
+    ]
+}
+
+/// Builds a `transact` instruction for a deposit or a plain withdrawal.
+pub fn transact(accounts: &TransactAccounts, proof: Proof, ext_data: ExtData) -> Instruction {
+    let [nullifier0, nullifier1] = nullifier_addresses(&proof);
+    let [nullifier2, nullifier3] = cross_slot_nullifier_addresses(&proof);
+    let [commitment0, commitment1] = commitment_addresses(&proof);
+    let [commitment2, commitment3] = cross_slot_commitment_addresses(&proof);
+
+    let tree_account = tree_account_address(&accounts.authority, accounts.tree_index);
+
+    let accounts = zkcash::accounts::Transact {
+        tree_account,
+        root_tree_account: accounts
+            .root_tree_index
+            .map(|root_tree_index| tree_account_address(&accounts.authority, root_tree_index)),
+        commitment_queue: accounts
+            .use_commitment_queue
+            .then(|| commitment_queue_address(&tree_account)),
+        tree_set: (accounts.tree_index != 0).then(|| tree_set_address(&accounts.authority)),
+        pool_config: pool_config_address(&accounts.authority),
+        nullifier0,
+        nullifier1,
+        nullifier2,
+        nullifier3,
+        commitment0,
+        commitment1,
+        commitment2,
+        commitment3,
+        tree_token_account: tree_token_account_address(&accounts.authority),
+        recipient: accounts.recipient,
+        fee_recipient_account: accounts.fee_recipient_account,
+        authority: accounts.authority,
+        signer: accounts.signer,
+        system_program: system_program::ID,
+        association_set: ext_data.association.as_ref().map(|association| association.association_set),
+        association_verifying_key: ext_data
+            .association
+            .as_ref()
+            .map(|_| association_verifying_key_address(&accounts.authority)),
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::Transact { proof, ext_data }.data(),
+    }
+}
+
+/// Builds a read-only `check_transact` instruction to simulate before sending `transact`,
+/// with the same accounts as the `transact` it checks, including the payout recipients or
+/// withdraw-call accounts committed in `ext_data`.
+pub fn check_transact(accounts: &TransactAccounts, proof: Proof, ext_data: ExtData) -> Instruction {
+    let [nullifier0, nullifier1] = nullifier_addresses(&proof);
+    let [nullifier2, nullifier3] = cross_slot_nullifier_addresses(&proof);
+    let [commitment0, commitment1] = commitment_addresses(&proof);
+    let [commitment2, commitment3] = cross_slot_commitment_addresses(&proof);
+
+    let tree_account = tree_account_address(&accounts.authority, accounts.tree_index);
+    let remaining_accounts = committed_remaining_accounts(&ext_data);
+
+    let accounts = zkcash::accounts::CheckTransact {
+        tree_account,
+        root_tree_account: accounts
+            .root_tree_index
+            .map(|root_tree_index| tree_account_address(&accounts.authority, root_tree_index)),
+        commitment_queue: accounts
+            .use_commitment_queue
+            .then(|| commitment_queue_address(&tree_account)),
+        tree_set: (accounts.tree_index != 0).then(|| tree_set_address(&accounts.authority)),
+        pool_config: pool_config_address(&accounts.authority),
+        nullifier0,
+        nullifier1,
+        nullifier2,
+        nullifier3,
+        commitment0,
+        commitment1,
+        commitment2,
+        commitment3,
+        tree_token_account: tree_token_account_address(&accounts.authority),
+        recipient: accounts.recipient,
+        fee_recipient_account: accounts.fee_recipient_account,
+        authority: accounts.authority,
+        association_set: ext_data.association.as_ref().map(|association| association.association_set),
+        association_verifying_key: ext_data
+            .association
+            .as_ref()
+            .map(|_| association_verifying_key_address(&accounts.authority)),
+    };
+
+    let mut account_metas = accounts.to_account_metas(None);
+    account_metas.extend(remaining_accounts);
+    Instruction {
+        program_id: ID,
+        accounts: account_metas,
+        data: zkcash::instruction::CheckTransact { proof, ext_data }.data(),
+    }
+}
+
+/// Builds a `rollover_tree` instruction that replaces the full shard `full_tree_index`.
+/// `new_tree_index` is the tree set's current `tree_count`.
+pub fn rollover_tree(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    full_tree_index: u32,
+    new_tree_index: u32,
+) -> Instruction {
+    let accounts = zkcash::accounts::RolloverTree {
+        tree_set: tree_set_address(authority),
+        full_tree_account: tree_account_address(authority, full_tree_index),
+        new_tree_account: tree_account_address(authority, new_tree_index),
+        authority: *authority,
+        payer: *payer,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::RolloverTree { tree_index: new_tree_index }.data(),
+    }
+}
+
+/// Builds an `add_tree_shard` instruction. The new shard copies the deposit limit of
+/// `tree_index`, and `new_tree_index` is the tree set's current `tree_count`.
+pub fn add_tree_shard(authority: &Pubkey, tree_index: u32, new_tree_index: u32) -> Instruction {
+    let accounts = zkcash::accounts::AddTreeShard {
+        tree_set: tree_set_address(authority),
+        tree_account: tree_account_address(authority, tree_index),
+        new_tree_account: tree_account_address(authority, new_tree_index),
+        authority: *authority,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::AddTreeShard { tree_index: new_tree_index }.data(),
+    }
+}
+
+/// Builds a `set_association_verifying_key` instruction. The key can only be set once.
+pub fn set_association_verifying_key(authority: &Pubkey, verifying_key: AssociationVerifyingKey) -> Instruction {
+    let accounts = zkcash::accounts::SetAssociationVerifyingKey {
+        association_verifying_key: association_verifying_key_address(authority),
+        pool_config: pool_config_address(authority),
+        authority: *authority,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::SetAssociationVerifyingKey { verifying_key }.data(),
+    }
+}
+
+/// Builds a `create_association_set` instruction for `provider`.
+pub fn create_association_set(provider: &Pubkey) -> Instruction {
+    let accounts = zkcash::accounts::CreateAssociationSet {
+        association_set: association_set_address(provider),
+        provider: *provider,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::CreateAssociationSet {}.data(),
+    }
+}
+
+/// Builds an `update_association_root` instruction that publishes `root` for `provider`.
+pub fn update_association_root(provider: &Pubkey, root: [u8; 32]) -> Instruction {
+    let accounts = zkcash::accounts::UpdateAssociationRoot {
+        association_set: association_set_address(provider),
+        provider: *provider,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::UpdateAssociationRoot { root }.data(),
+    }
+}
+
+/// Builds a `set_batch_deposit_verifying_key` instruction. The key can only be set once.
+pub fn set_batch_deposit_verifying_key(authority: &Pubkey, verifying_key: BatchDepositVerifyingKey) -> Instruction {
+    let accounts = zkcash::accounts::SetBatchDepositVerifyingKey {
+        batch_deposit_verifying_key: batch_deposit_verifying_key_address(authority),
+        pool_config: pool_config_address(authority),
+        authority: *authority,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::SetBatchDepositVerifyingKey { verifying_key }.data(),
+    }
+}
+
+/// Builds an `add_batch_outputs` instruction that adds `outputs` to the pending batch
+/// deposit of `signer`.
+pub fn add_batch_outputs(authority: &Pubkey, signer: &Pubkey, outputs: Vec<BatchOutput>) -> Instruction {
+    let accounts = zkcash::accounts::AddBatchOutputs {
+        batch_deposit: batch_deposit_address(authority, signer),
+        authority: *authority,
+        signer: *signer,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::AddBatchOutputs { outputs }.data(),
+    }
+}
+
+/// Builds a `cancel_batch_deposit` instruction that drops the pending batch deposit of `signer`.
+pub fn cancel_batch_deposit(authority: &Pubkey, signer: &Pubkey) -> Instruction {
+    let accounts = zkcash::accounts::CancelBatchDeposit {
+        batch_deposit: batch_deposit_address(authority, signer),
+        authority: *authority,
+        signer: *signer,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::CancelBatchDeposit {}.data(),
+    }
+}
+
+/// The accounts of `batch_deposit`.
+pub struct BatchDepositAccounts {
+    pub authority: Pubkey,
+    /// The shard the outputs are appended to.
+    pub tree_index: u32,
+    /// Whether the tree in `tree_index` has a commitment queue.
+    pub use_commitment_queue: bool,
+    pub signer: Pubkey,
+}
+
+/// Builds a `batch_deposit` instruction that deposits `amount` into the pending batch of
+/// `accounts.signer`.
+pub fn batch_deposit(accounts: &BatchDepositAccounts, amount: u64, proof: BatchDepositProof) -> Instruction {
+    let tree_account = tree_account_address(&accounts.authority, accounts.tree_index);
+
+    let accounts = zkcash::accounts::BatchDeposit {
+        tree_account,
+        commitment_queue: accounts
+            .use_commitment_queue
+            .then(|| commitment_queue_address(&tree_account)),
+        tree_set: (accounts.tree_index != 0).then(|| tree_set_address(&accounts.authority)),
+        pool_config: pool_config_address(&accounts.authority),
+        batch_deposit: batch_deposit_address(&accounts.authority, &accounts.signer),
+        batch_deposit_verifying_key: batch_deposit_verifying_key_address(&accounts.authority),
+        tree_token_account: tree_token_account_address(&accounts.authority),
+        authority: accounts.authority,
+        signer: accounts.signer,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::BatchDeposit { amount, proof }.data(),
+    }
+}
+
+/// Builds an `initialize_pool_config` instruction for a pool created before pool configs.
+pub fn initialize_pool_config(authority: &Pubkey) -> Instruction {
+    let accounts = zkcash::accounts::InitializePoolConfig {
+        tree_account: tree_account_address(authority, 0),
+        pool_config: pool_config_address(authority),
+        authority: *authority,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::InitializePoolConfig {}.data(),
+    }
+}
+
+/// Builds a `migrate_tree_account` instruction for a pool whose tree predates the layout
+/// version. Send it before `initialize_pool_config` and any other instruction on the pool.
+pub fn migrate_tree_account(authority: &Pubkey) -> Instruction {
+    let accounts = zkcash::accounts::MigrateTreeAccount {
+        tree_account: tree_account_address(authority, 0),
+        authority: *authority,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::MigrateTreeAccount {}.data(),
+    }
+}
+
+/// Builds an `initialize_commitment_queue` instruction for the tree `tree_index`.
+pub fn initialize_commitment_queue(authority: &Pubkey, tree_index: u32) -> Instruction {
+    let tree_account = tree_account_address(authority, tree_index);
+
+    let accounts = zkcash::accounts::InitializeCommitmentQueue {
+        tree_account,
+        commitment_queue: commitment_queue_address(&tree_account),
+        authority: *authority,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::InitializeCommitmentQueue {}.data(),
+    }
+}
+
+/// Builds a `process_queue` instruction that inserts up to `max_leaves` queued commitments.
+pub fn process_queue(authority: &Pubkey, tree_index: u32, max_leaves: u32) -> Instruction {
+    let tree_account = tree_account_address(authority, tree_index);
+
+    let accounts = zkcash::accounts::ProcessQueue {
+        tree_account,
+        commitment_queue: commitment_queue_address(&tree_account),
+        pool_config: pool_config_address(authority),
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::ProcessQueue { max_leaves }.data(),
+    }
+}
+
+pub fn shielded_account_address(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"shielded_account", owner.as_ref()], &ID).0
+}
+
+/// Builds a `register_account` instruction. It also rotates the keys of a registered wallet.
+pub fn register_account(owner: &Pubkey, shielded_pubkey: [u8; 32], encryption_key: [u8; 32]) -> Instruction {
+    let accounts = zkcash::accounts::RegisterAccount {
+        shielded_account: shielded_account_address(owner),
+        owner: *owner,
+        system_program: system_program::ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::RegisterAccount { shielded_pubkey, encryption_key }.data(),
+    }
+}
+
+/// Builds a `delete_account` instruction that removes `owner` from the registry.
+pub fn delete_account(owner: &Pubkey) -> Instruction {
+    let accounts = zkcash::accounts::DeleteAccount {
+        shielded_account: shielded_account_address(owner),
+        owner: *owner,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: zkcash::instruction::DeleteAccount {}.data(),
+    }
+}
+
+/// Builds a withdraw-and-call `transact` instruction. `ext_data.call` must be set, and its
+/// program and committed accounts are appended in order.
+pub fn withdraw_and_call(accounts: &TransactAccounts, proof: Proof, ext_data: ExtData) -> Result<Instruction> {
+    require!(ext_data.call.is_some(), zkcash::ErrorCode::InvalidWithdrawCall);
+    let remaining_accounts = committed_remaining_accounts(&ext_data);
+
+    let mut instruction = transact(accounts, proof, ext_data);
+    instruction.accounts.extend(remaining_accounts);
+    Ok(instruction)
+}
+
+/// Builds a `transact` instruction that splits a withdrawal across `ext_data.payouts`,
+/// which must be set. The payout recipients are appended as writable accounts, in order.
+pub fn withdraw_to_many(accounts: &TransactAccounts, proof: Proof, ext_data: ExtData) -> Result<Instruction> {
+    require!(ext_data.payouts.is_some(), zkcash::ErrorCode::InvalidPayouts);
+    let remaining_accounts = committed_remaining_accounts(&ext_data);
+
+    let mut instruction = transact(accounts, proof, ext_data);
+    instruction.accounts.extend(remaining_accounts);
+    Ok(instruction)
+}
+
+/// The remaining accounts `transact` expects for `ext_data`: the payout recipients, writable
+/// and in order, or the withdraw-call program followed by the committed accounts.
+fn committed_remaining_accounts(ext_data: &ExtData) -> Vec<AccountMeta> {
+    if let Some(payouts) = &ext_data.payouts {
+        return payouts.iter().map(|payout| AccountMeta::new(payout.recipient, false)).collect();
+    }
+    let Some(call) = &ext_data.call else {
+        return Vec::new();
+    };
+
+    std::iter::once(AccountMeta::new_readonly(call.program_id, false))
+        .chain(call.accounts.iter().map(|account| AccountMeta {
+            pubkey: account.pubkey,
+            is_signer: false,
+            is_writable: account.is_writable,
+        }))
+        .collect()
+}
+
+/// Invokes a `transact` instruction, signing for the caller's PDAs with `signer_seeds`.
+/// `account_infos` must contain every account of `instruction` and the zkcash program.
+pub fn invoke_transact(
+    instruction: &Instruction,
+    account_infos: &[AccountInfo],
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    invoke_signed(instruction, account_infos, signer_seeds)?;
+    Ok(())
+}
+
+/// Borsh length of `TransactReturnData`, which only has fixed-size fields.
+const TRANSACT_RETURN_DATA_LEN: usize = 1 + 2 * 8 + 2 * 32 + 32 + 8 + 4;
+
+/// Borsh length of the longest `TransactVerdict`, a variant with an input or output index.
+const TRANSACT_VERDICT_MAX_LEN: usize = 2;
+
+/// Decodes the return data of `transact`. Returns `None` if the data was set by another
+/// program or has an unknown version.
+///
+/// The runtime strips trailing zeros from return data, and `tree_index` is 0 in a pool
+/// without shards, so `data` is padded back to the full length first.
+pub fn decode_transact_return_data(program_id: &Pubkey, data: &[u8]) -> Option<TransactReturnData> {
+    if *program_id != ID || data.len() > TRANSACT_RETURN_DATA_LEN {
+        return None;
+    }
+
+    let mut padded = data.to_vec();
+    padded.resize(TRANSACT_RETURN_DATA_LEN, 0);
+    let return_data = TransactReturnData::try_from_slice(&padded).ok()?;
+    (return_data.version == TRANSACT_RETURN_DATA_VERSION).then_some(return_data)
+}
+
+/// Decodes the return data of a simulated `check_transact`. The runtime strips trailing
+/// zeros, so a `Valid` verdict has no return data and `data` may be empty: pass the return
+/// data of the simulation, or an empty slice with the zkcash program id if it had none.
+/// Returns `None` if the data was set by another program or isn't a known verdict.
+pub fn decode_transact_verdict(program_id: &Pubkey, data: &[u8]) -> Option<TransactVerdict> {
+    if *program_id != ID || data.len() > TRANSACT_VERDICT_MAX_LEN {
+        return None;
+    }
+
+    let mut padded = data.to_vec();
+    padded.resize(TRANSACT_VERDICT_MAX_LEN, 0);
+    // A verdict without an index leaves padding behind
+    TransactVerdict::deserialize(&mut padded.as_slice()).ok()
+}
+
+/// Reads the return data of a `transact` CPI that just completed.
+pub fn get_transact_return_data() -> Option<TransactReturnData> {
+    let (program_id, data) = get_return_data()?;
+    decode_transact_return_data(&program_id, &data)
+}
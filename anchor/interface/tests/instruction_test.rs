@@ -0,0 +1,363 @@
+use anchor_lang::prelude::*;
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use zkcash_interface::{
+    add_batch_outputs, association_set_address, association_verifying_key_address, batch_deposit, batch_deposit_address,
+    batch_deposit_verifying_key_address, check_transact, commitment_addresses, cross_slot_commitment_addresses, cross_slot_nullifier_addresses, commitment_queue_address, decode_transact_return_data, decode_transact_verdict, nullifier_addresses, pool_config_address, process_queue, transact,
+    tree_account_address, tree_set_address, tree_token_account_address, withdraw_and_call, withdraw_to_many, AssociationProof, BatchDepositAccounts, BatchDepositProof,
+    BatchOutput, ExtData, Payout, Proof, TransactAccounts, TransactReturnData, TransactVerdict, WithdrawCall, WithdrawCallAccount, ID, TRANSACT_RETURN_DATA_VERSION,
+};
+
+fn test_proof() -> Proof {
+    Proof {
+        proof_a: [1u8; 64],
+        proof_b: [2u8; 128],
+        proof_c: [3u8; 64],
+        root: [4u8; 32],
+        public_amount: [5u8; 32],
+        ext_data_hash: [6u8; 32],
+        input_nullifiers: [[7u8; 32], [8u8; 32]],
+        output_commitments: [[9u8; 32], [10u8; 32]],
+    }
+}
+
+fn test_ext_data(recipient: Pubkey, call: Option<WithdrawCall>) -> ExtData {
+    ExtData {
+        recipient,
+        ext_amount: -1_000_000,
+        encrypted_output1: vec![1, 2, 3],
+        encrypted_output2: vec![4, 5, 6],
+        fee: 1_000,
+        call,
+        auditor_outputs: None,
+        payouts: None,
+        association: None,
+    }
+}
+
+fn test_accounts() -> TransactAccounts {
+    TransactAccounts {
+        authority: Pubkey::new_unique(),
+        tree_index: 0,
+        root_tree_index: None,
+        use_commitment_queue: false,
+        recipient: Pubkey::new_unique(),
+        fee_recipient_account: Pubkey::new_unique(),
+        signer: Pubkey::new_unique(),
+    }
+}
+
+#[test]
+fn test_transact_instruction_accounts() {
+    let accounts = test_accounts();
+    let proof = test_proof();
+    let instruction = transact(&accounts, proof.clone(), test_ext_data(accounts.recipient, None));
+
+    assert_eq!(instruction.program_id, ID);
+    let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+    let [nullifier0, nullifier1] = nullifier_addresses(&proof);
+    let [nullifier2, nullifier3] = cross_slot_nullifier_addresses(&proof);
+    let [commitment0, commitment1] = commitment_addresses(&proof);
+    let [commitment2, commitment3] = cross_slot_commitment_addresses(&proof);
+    assert_eq!(keys, vec![
+        tree_account_address(&accounts.authority, 0),
+        ID, // no root tree account
+        ID, // no commitment queue
+        ID, // no tree set for tree 0
+        pool_config_address(&accounts.authority),
+        nullifier0,
+        nullifier1,
+        nullifier2,
+        nullifier3,
+        commitment0,
+        commitment1,
+        commitment2,
+        commitment3,
+        tree_token_account_address(&accounts.authority),
+        accounts.recipient,
+        accounts.fee_recipient_account,
+        accounts.authority,
+        accounts.signer,
+        anchor_lang::system_program::ID,
+        ID, // no association set
+        ID, // no association verifying key
+    ]);
+
+    // Only the signer signs, so a PDA vault can sign through invoke_signed
+    let signers: Vec<Pubkey> = instruction.accounts.iter().filter(|meta| meta.is_signer).map(|meta| meta.pubkey).collect();
+    assert_eq!(signers, vec![accounts.signer]);
+}
+
+#[test]
+fn test_transact_instruction_with_association_proof() {
+    let accounts = test_accounts();
+    let provider = Pubkey::new_unique();
+    let mut ext_data = test_ext_data(accounts.recipient, None);
+    ext_data.association = Some(AssociationProof {
+        association_set: association_set_address(&provider),
+        root: [12u8; 32],
+        proof_a: [0u8; 64],
+        proof_b: [0u8; 128],
+        proof_c: [0u8; 64],
+    });
+
+    for instruction in [
+        transact(&accounts, test_proof(), ext_data.clone()),
+        check_transact(&accounts, test_proof(), ext_data.clone()),
+    ] {
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(
+            keys[keys.len() - 2..],
+            [association_set_address(&provider), association_verifying_key_address(&accounts.authority)]
+        );
+    }
+}
+
+#[test]
+fn test_transact_instruction_data_round_trip() {
+    let accounts = test_accounts();
+    let call = WithdrawCall { program_id: Pubkey::new_unique(), accounts: vec![], data: vec![42, 43] };
+    let instruction = transact(&accounts, test_proof(), test_ext_data(accounts.recipient, Some(call.clone())));
+
+    let discriminator = zkcash::instruction::Transact::DISCRIMINATOR;
+    assert_eq!(&instruction.data[..discriminator.len()], discriminator);
+
+    let decoded = zkcash::instruction::Transact::deserialize(&mut &instruction.data[discriminator.len()..]).unwrap();
+    assert_eq!(decoded.proof.root, [4u8; 32]);
+    assert_eq!(decoded.ext_data.recipient, accounts.recipient);
+    let decoded_call = decoded.ext_data.call.unwrap();
+    assert_eq!(decoded_call.program_id, call.program_id);
+    assert_eq!(decoded_call.data, call.data);
+}
+
+#[test]
+fn test_withdraw_and_call_appends_committed_accounts() {
+    let accounts = test_accounts();
+    let target_program = Pubkey::new_unique();
+    let target_account = Pubkey::new_unique();
+    let call = WithdrawCall {
+        program_id: target_program,
+        accounts: vec![
+            WithdrawCallAccount { pubkey: accounts.recipient, is_writable: true },
+            WithdrawCallAccount { pubkey: target_account, is_writable: false },
+        ],
+        data: vec![1],
+    };
+
+    let instruction = withdraw_and_call(&accounts, test_proof(), test_ext_data(accounts.recipient, Some(call))).unwrap();
+
+    let remaining = &instruction.accounts[21..];
+    assert_eq!(remaining.iter().map(|meta| meta.pubkey).collect::<Vec<_>>(), vec![target_program, accounts.recipient, target_account]);
+    assert_eq!(remaining.iter().map(|meta| meta.is_writable).collect::<Vec<_>>(), vec![false, true, false]);
+    assert!(remaining.iter().all(|meta| !meta.is_signer));
+}
+
+#[test]
+fn test_withdraw_and_call_requires_call() {
+    let accounts = test_accounts();
+    let result = withdraw_and_call(&accounts, test_proof(), test_ext_data(accounts.recipient, None));
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_withdraw_to_many_appends_payout_recipients() {
+    let accounts = test_accounts();
+    let payouts = vec![
+        Payout { recipient: Pubkey::new_unique(), amount: 400_000 },
+        Payout { recipient: Pubkey::new_unique(), amount: 600_000 },
+    ];
+    let mut ext_data = test_ext_data(accounts.recipient, None);
+    ext_data.payouts = Some(payouts.clone());
+
+    let instruction = withdraw_to_many(&accounts, test_proof(), ext_data).unwrap();
+    let remaining = &instruction.accounts[21..];
+    assert_eq!(remaining.iter().map(|meta| meta.pubkey).collect::<Vec<_>>(), vec![payouts[0].recipient, payouts[1].recipient]);
+    assert!(remaining.iter().all(|meta| meta.is_writable && !meta.is_signer));
+
+    let result = withdraw_to_many(&accounts, test_proof(), test_ext_data(accounts.recipient, None));
+    assert!(result.is_err(), "Should require payouts");
+}
+
+#[test]
+fn test_check_transact_instruction_matches_transact() {
+    let mut accounts = test_accounts();
+    accounts.root_tree_index = Some(2);
+    let mut ext_data = test_ext_data(accounts.recipient, None);
+    ext_data.payouts = Some(vec![Payout { recipient: Pubkey::new_unique(), amount: 1_000_000 }]);
+
+    let transact_instruction = withdraw_to_many(&accounts, test_proof(), ext_data.clone()).unwrap();
+    let check_instruction = check_transact(&accounts, test_proof(), ext_data);
+
+    // The same accounts without the signer and the system program, read-only where
+    // `transact` writes, and with the same remaining accounts
+    let keys = |instruction: &anchor_lang::solana_program::instruction::Instruction| -> Vec<Pubkey> {
+        instruction.accounts.iter().map(|meta| meta.pubkey).collect()
+    };
+    let mut expected = keys(&transact_instruction);
+    expected.retain(|key| *key != accounts.signer && *key != anchor_lang::system_program::ID);
+    assert_eq!(keys(&check_instruction), expected);
+    assert!(check_instruction.accounts[..19].iter().all(|meta| !meta.is_writable && !meta.is_signer));
+    assert!(check_instruction.accounts[19].is_writable, "Payout recipients are checked as writable");
+}
+
+#[test]
+fn test_decode_transact_return_data() {
+    let return_data = TransactReturnData {
+        version: TRANSACT_RETURN_DATA_VERSION,
+        leaf_indices: [4, 5],
+        commitments: [[9u8; 32], [10u8; 32]],
+        root: [11u8; 32],
+        root_index: 3,
+        tree_index: 1,
+    };
+    let mut data = Vec::new();
+    return_data.serialize(&mut data).unwrap();
+
+    assert_eq!(decode_transact_return_data(&ID, &data), Some(return_data.clone()));
+    assert_eq!(decode_transact_return_data(&Pubkey::new_unique(), &data), None,
+        "Should ignore return data set by another program");
+    assert_eq!(decode_transact_return_data(&ID, &[data.clone(), vec![0]].concat()), None,
+        "Should reject overlong return data");
+
+    let mut unknown_version = data.clone();
+    unknown_version[0] = TRANSACT_RETURN_DATA_VERSION + 1;
+    assert_eq!(decode_transact_return_data(&ID, &unknown_version), None,
+        "Should reject unknown versions");
+}
+
+#[test]
+fn test_decode_transact_return_data_without_trailing_zeros() {
+    // A pool without shards returns tree index 0, which the runtime strips with any other
+    // trailing zeros
+    let return_data = TransactReturnData {
+        version: TRANSACT_RETURN_DATA_VERSION,
+        leaf_indices: [6, 7],
+        commitments: [[9u8; 32], [10u8; 32]],
+        root: [11u8; 32],
+        root_index: 256,
+        tree_index: 0,
+    };
+    let mut data = Vec::new();
+    return_data.serialize(&mut data).unwrap();
+    let trimmed_len = data.iter().rposition(|byte| *byte != 0).unwrap() + 1;
+    assert!(trimmed_len < data.len() - 4);
+
+    assert_eq!(decode_transact_return_data(&ID, &data[..trimmed_len]), Some(return_data));
+}
+
+#[test]
+fn test_decode_transact_verdict() {
+    // A valid verdict is a single zero byte, so the runtime returns no data for it
+    assert_eq!(decode_transact_verdict(&ID, &[]), Some(TransactVerdict::Valid));
+    assert_eq!(
+        decode_transact_verdict(&ID, &[5]),
+        Some(TransactVerdict::NullifierAlreadySpent { index: 0 })
+    );
+    assert_eq!(
+        decode_transact_verdict(&ID, &[14, 1]),
+        Some(TransactVerdict::CommitmentAlreadyExists { index: 1 })
+    );
+    assert_eq!(decode_transact_verdict(&ID, &[4]), Some(TransactVerdict::InvalidProof));
+    assert_eq!(decode_transact_verdict(&Pubkey::new_unique(), &[]), None,
+        "Should ignore return data set by another program");
+    assert_eq!(decode_transact_verdict(&ID, &[200]), None);
+}
+
+#[test]
+fn test_transact_instruction_with_root_tree() {
+    let mut accounts = test_accounts();
+    accounts.tree_index = 2;
+    accounts.root_tree_index = Some(1);
+    let instruction = transact(&accounts, test_proof(), test_ext_data(accounts.recipient, None));
+
+    assert_eq!(instruction.accounts[0].pubkey, tree_account_address(&accounts.authority, 2));
+    assert!(instruction.accounts[0].is_writable);
+    assert_eq!(instruction.accounts[1].pubkey, tree_account_address(&accounts.authority, 1));
+    assert!(!instruction.accounts[1].is_writable);
+
+    // Every tree but tree 0 is checked against the tree set
+    assert_eq!(instruction.accounts[3].pubkey, tree_set_address(&accounts.authority));
+    assert!(!instruction.accounts[3].is_writable);
+}
+
+#[test]
+fn test_transact_instruction_with_commitment_queue() {
+    let mut accounts = test_accounts();
+    accounts.use_commitment_queue = true;
+    let instruction = transact(&accounts, test_proof(), test_ext_data(accounts.recipient, None));
+
+    let tree_account = tree_account_address(&accounts.authority, 0);
+    assert_eq!(instruction.accounts[2].pubkey, commitment_queue_address(&tree_account));
+    assert!(instruction.accounts[2].is_writable);
+}
+
+#[test]
+fn test_batch_deposit_instructions_share_the_pending_batch() {
+    let authority = Pubkey::new_unique();
+    let signer = Pubkey::new_unique();
+    let pending_batch = batch_deposit_address(&authority, &signer);
+    assert_ne!(pending_batch, batch_deposit_address(&Pubkey::new_unique(), &signer), "Batches are per pool");
+
+    let output = BatchOutput { commitment: [1u8; 32], encrypted_output: vec![2, 3], auditor_output: Vec::new() };
+    let add = add_batch_outputs(&authority, &signer, vec![output.clone()]);
+    assert_eq!(add.accounts[0].pubkey, pending_batch);
+    assert!(add.accounts[0].is_writable);
+    let decoded = zkcash::instruction::AddBatchOutputs::deserialize(
+        &mut &add.data[zkcash::instruction::AddBatchOutputs::DISCRIMINATOR.len()..],
+    )
+    .unwrap();
+    assert_eq!(decoded.outputs, vec![output]);
+
+    let proof = BatchDepositProof { proof_a: [1u8; 64], proof_b: [2u8; 128], proof_c: [3u8; 64] };
+    let accounts = BatchDepositAccounts { authority, tree_index: 0, use_commitment_queue: false, signer };
+    let deposit = batch_deposit(&accounts, 1_000, proof);
+    let keys: Vec<Pubkey> = deposit.accounts.iter().map(|meta| meta.pubkey).collect();
+    assert_eq!(
+        keys[..8],
+        [
+            tree_account_address(&authority, 0),
+            ID,
+            ID,
+            pool_config_address(&authority),
+            pending_batch,
+            batch_deposit_verifying_key_address(&authority),
+            tree_token_account_address(&authority),
+            authority,
+        ]
+    );
+    assert!(deposit.accounts[4].is_writable, "The pending batch is closed by the deposit");
+}
+
+#[test]
+fn test_process_queue_instruction_accounts() {
+    let authority = Pubkey::new_unique();
+    let instruction = process_queue(&authority, 1, 32);
+
+    let tree_account = tree_account_address(&authority, 1);
+    let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+    assert_eq!(keys, vec![tree_account, commitment_queue_address(&tree_account), pool_config_address(&authority)]);
+    assert!(!instruction.accounts[2].is_writable);
+}
+
+#[test]
+fn test_tree_account_addresses() {
+    let authority = Pubkey::new_unique();
+    let legacy = Pubkey::find_program_address(&[b"merkle_tree", authority.as_ref()], &ID).0;
+
+    assert_eq!(tree_account_address(&authority, 0), legacy);
+    assert_ne!(tree_account_address(&authority, 1), legacy);
+    assert_ne!(tree_account_address(&authority, 1), tree_account_address(&authority, 2));
+}
+
+#[test]
+fn test_tree_token_account_is_shared_by_every_tree() {
+    let mut accounts = test_accounts();
+    let (vault, _) = Pubkey::find_program_address(&[b"tree_token", accounts.authority.as_ref()], &ID);
+    assert_eq!(tree_token_account_address(&accounts.authority), vault);
+
+    // Outputs into one shard spending notes of another still move funds through the one vault
+    accounts.tree_index = 2;
+    accounts.root_tree_index = Some(1);
+    let instruction = transact(&accounts, test_proof(), test_ext_data(accounts.recipient, None));
+    assert_eq!(instruction.accounts[13].pubkey, vault);
+    assert!(instruction.accounts[13].is_writable);
+}
@@ -0,0 +1,58 @@
+use crate::{AssociationSetAccount, ErrorCode};
+use anchor_lang::prelude::*;
+
+pub const ASSOCIATION_ROOT_HISTORY_SIZE: usize = 30;
+
+/// Public inputs of the association circuit: the association root and the two input
+/// nullifiers of the transaction.
+pub const ASSOCIATION_PUBLIC_INPUTS: usize = 3;
+
+/// A set of approved note commitments, published by an association-set provider as a Merkle
+/// root. A `transact` can carry an association proof that each of its inputs is a leaf of a
+/// recent root, which shows the funds come from the approved set without revealing which
+/// notes were spent.
+///
+/// The last `ASSOCIATION_ROOT_HISTORY_SIZE` roots stay valid, so a proof made against a root
+/// survives a few updates of the set.
+pub struct AssociationSet;
+
+impl AssociationSet {
+    pub fn initialize(association_set: &mut AssociationSetAccount, provider: Pubkey, bump: u8) {
+        association_set.provider = provider;
+        association_set.root_history = [[0u8; 32]; ASSOCIATION_ROOT_HISTORY_SIZE];
+        association_set.root_index = 0;
+        association_set.root_count = 0;
+        association_set.bump = bump;
+    }
+
+    pub fn update_root(association_set: &mut AssociationSetAccount, root: [u8; 32]) -> Result<()> {
+        require!(root != [0u8; 32], ErrorCode::InvalidAssociationRoot);
+
+        if association_set.root_count > 0 {
+            association_set.root_index = (association_set.root_index + 1) % ASSOCIATION_ROOT_HISTORY_SIZE as u64;
+        }
+        association_set.root_history[association_set.root_index as usize] = root;
+        association_set.root_count = association_set.root_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        Ok(())
+    }
+
+    pub fn current_root(association_set: &AssociationSetAccount) -> Option<[u8; 32]> {
+        (association_set.root_count > 0).then(|| association_set.root_history[association_set.root_index as usize])
+    }
+
+    /// The PDA of `association_set`, derived from its provider and bump.
+    pub fn address(association_set: &AssociationSetAccount) -> Option<Pubkey> {
+        Pubkey::create_program_address(
+            &[b"association_set", association_set.provider.as_ref(), &[association_set.bump]],
+            &crate::ID,
+        )
+        .ok()
+    }
+
+    pub fn is_known_root(association_set: &AssociationSetAccount, root: [u8; 32]) -> bool {
+        root != [0u8; 32] && association_set.root_history.contains(&root)
+    }
+}
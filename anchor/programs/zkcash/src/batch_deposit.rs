@@ -0,0 +1,73 @@
+use crate::groth16::is_less_than_bn254_field_size_be;
+use crate::{BatchDepositAccount, BatchOutput, ErrorCode};
+use anchor_lang::prelude::*;
+use light_hasher::{Hasher, Poseidon};
+
+/// Outputs of one batch deposit, the `nOuts` of `circuits/batchDeposit.circom`.
+pub const BATCH_DEPOSIT_OUTPUTS: usize = 8;
+
+/// Public inputs of the batch deposit circuit: the deposited amount and the hash of the
+/// output commitments.
+pub const BATCH_DEPOSIT_PUBLIC_INPUTS: usize = 2;
+
+/// Longest ciphertext a batch output can carry, for the recipient or the auditor. Notes
+/// encrypted by the SDK fit with room to spare.
+pub const MAX_BATCH_CIPHERTEXT_LEN: usize = 256;
+
+/// Funds many notes from one transfer, for payroll and airdrops.
+///
+/// The encrypted outputs of eight notes don't fit in one transaction, so the depositor first
+/// collects them in a `BatchDepositAccount` with `add_batch_outputs`, a few per transaction.
+/// `batch_deposit` then checks one proof that the hidden amounts of the notes add up to the
+/// deposit, appends all commitments with a single root and publishes the outputs in a
+/// `BatchDepositEvent`, without commitment or nullifier accounts to pay rent for.
+///
+/// The proof takes the commitments through their Poseidon hash, padded with zeros to
+/// `BATCH_DEPOSIT_OUTPUTS`, so its verifying key stays small enough to upload in one
+/// transaction.
+pub struct PendingBatch;
+
+impl PendingBatch {
+    pub fn push_outputs(batch: &mut BatchDepositAccount, outputs: Vec<BatchOutput>) -> Result<()> {
+        let len = batch.outputs.len().checked_add(outputs.len()).ok_or(ErrorCode::ArithmeticOverflow)?;
+        require!(len <= BATCH_DEPOSIT_OUTPUTS, ErrorCode::BatchDepositFull);
+        require!(outputs.iter().all(Self::is_valid_output), ErrorCode::InvalidBatchOutput);
+
+        batch.outputs.extend(outputs);
+        Ok(())
+    }
+
+    /// A zero commitment is padding in the circuit, so it can't be a real output.
+    pub fn is_valid_output(output: &BatchOutput) -> bool {
+        output.commitment != [0u8; 32]
+            && is_less_than_bn254_field_size_be(&output.commitment)
+            && !output.encrypted_output.is_empty()
+            && output.encrypted_output.len() <= MAX_BATCH_CIPHERTEXT_LEN
+            && output.auditor_output.len() <= MAX_BATCH_CIPHERTEXT_LEN
+    }
+
+    /// Whether every output carries a ciphertext for the auditor, when the pool has one.
+    /// Advisory like `utils::has_auditor_outputs`.
+    pub fn has_auditor_outputs(batch: &BatchDepositAccount, auditor_key: [u8; 32]) -> bool {
+        auditor_key == [0u8; 32] || batch.outputs.iter().all(|output| !output.auditor_output.is_empty())
+    }
+
+    /// The Poseidon hash of the commitments, padded with zeros to `BATCH_DEPOSIT_OUTPUTS`.
+    pub fn outputs_hash(commitments: &[[u8; 32]]) -> Option<[u8; 32]> {
+        if commitments.len() > BATCH_DEPOSIT_OUTPUTS {
+            return None;
+        }
+        let mut padded = [[0u8; 32]; BATCH_DEPOSIT_OUTPUTS];
+        padded[..commitments.len()].copy_from_slice(commitments);
+        let inputs: Vec<&[u8]> = padded.iter().map(|commitment| commitment.as_slice()).collect();
+        Poseidon::hashv(&inputs).ok()
+    }
+
+    /// The public inputs of the proof for depositing `amount` into the outputs of `batch`.
+    pub fn public_inputs(batch: &BatchDepositAccount, amount: u64) -> Option<[[u8; 32]; BATCH_DEPOSIT_PUBLIC_INPUTS]> {
+        let commitments: Vec<[u8; 32]> = batch.outputs.iter().map(|output| output.commitment).collect();
+        let mut public_amount = [0u8; 32];
+        public_amount[24..].copy_from_slice(&amount.to_be_bytes());
+        Some([public_amount, Self::outputs_hash(&commitments)?])
+    }
+}
@@ -0,0 +1,62 @@
+use crate::merkle_tree::{MerkleTree, MAX_LEAVES};
+use crate::{CommitmentQueueAccount, ErrorCode, MerkleTreeAccount};
+use anchor_lang::prelude::*;
+use light_hasher::Hasher;
+
+pub const COMMITMENT_QUEUE_SIZE: usize = 256;
+
+/// Commitments waiting to be inserted into a tree. With a queue, `transact` only pushes its
+/// outputs and `process_queue` does the hashing later, in batches, in order.
+///
+/// Leaves keep the order in which they were queued, so a queued leaf already knows its leaf
+/// index: the tree's `next_index` plus its position in the queue. A note becomes spendable
+/// once it has been inserted and its root is in the root history.
+pub struct CommitmentQueue;
+
+impl CommitmentQueue {
+    pub fn initialize(queue: &mut CommitmentQueueAccount, tree_account: Pubkey, bump: u8) {
+        queue.tree_account = tree_account;
+        queue.head = 0;
+        queue.len = 0;
+        queue.bump = bump;
+    }
+
+    /// Queues a leaf and returns the leaf index it will be inserted at.
+    pub fn push(
+        queue: &mut CommitmentQueueAccount,
+        tree_account: &MerkleTreeAccount,
+        leaf: [u8; 32],
+    ) -> Result<u64> {
+        require!((queue.len as usize) < COMMITMENT_QUEUE_SIZE, ErrorCode::CommitmentQueueFull);
+
+        let leaf_index = tree_account.next_index
+            .checked_add(queue.len)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        require!(leaf_index < MAX_LEAVES, ErrorCode::MerkleTreeFull);
+
+        let position = (queue.head + queue.len) as usize % COMMITMENT_QUEUE_SIZE;
+        queue.leaves[position] = leaf;
+        queue.len += 1;
+
+        Ok(leaf_index)
+    }
+
+    /// Inserts up to `max_leaves` queued leaves into the tree and returns how many were inserted.
+    /// The leaves go in as one batch, so a crank adds a single root, recorded at `slot`, to the
+    /// root history however many leaves it inserts.
+    pub fn process<H: Hasher>(
+        queue: &mut CommitmentQueueAccount,
+        tree_account: &mut MerkleTreeAccount,
+        max_leaves: u64,
+        slot: u64,
+    ) -> Result<u64> {
+        let count = queue.len.min(max_leaves);
+        let leaves: Vec<[u8; 32]> = (0..count)
+            .map(|offset| queue.leaves[(queue.head + offset) as usize % COMMITMENT_QUEUE_SIZE])
+            .collect();
+        MerkleTree::append_batch::<H>(&leaves, tree_account, slot)?;
+        queue.head = (queue.head + count) % COMMITMENT_QUEUE_SIZE as u64;
+        queue.len -= count;
+        Ok(count)
+    }
+}
@@ -1,9 +1,6 @@
 use anchor_lang::prelude::*;
 use light_hasher::Poseidon;
-use anchor_lang::solana_program::hash::{hash};
 use anchor_lang::solana_program::sysvar::rent::Rent;
-use ark_ff::PrimeField;
-use ark_bn254::Fr;
 
 declare_id!("6JFJ27mebUcPSw1X5z5X6yKePQmuwQkusS7xNpE9kuUr");
 
@@ -11,7 +8,17 @@ pub mod merkle_tree;
 pub mod utils;
 pub mod groth16;
 pub mod errors;
-use merkle_tree::{ROOT_HISTORY_SIZE, DEFAULT_HEIGHT, MerkleTree};
+pub mod preflight;
+pub mod tree_set;
+pub mod commitment_queue;
+pub mod association_set;
+pub mod batch_deposit;
+use merkle_tree::{ROOT_HISTORY_SIZE, ROOT_CHECKPOINTS, DEFAULT_HEIGHT, MerkleTree};
+use preflight::TransactVerdict;
+use tree_set::{TreeSet, MAX_TREE_SHARDS};
+use commitment_queue::{CommitmentQueue, COMMITMENT_QUEUE_SIZE};
+use association_set::{AssociationSet, ASSOCIATION_PUBLIC_INPUTS, ASSOCIATION_ROOT_HISTORY_SIZE};
+use batch_deposit::{PendingBatch, BATCH_DEPOSIT_OUTPUTS, BATCH_DEPOSIT_PUBLIC_INPUTS, MAX_BATCH_CIPHERTEXT_LEN};
 
 #[program]
 pub mod zkcash {
@@ -26,18 +33,157 @@ pub mod zkcash {
         tree_account.next_index = 0;
         tree_account.root_index = 0;
         tree_account.bump = ctx.bumps.tree_account;
+        tree_account.tree_index = 0;
         tree_account.max_deposit_amount = 1_000_000_000; // 1 SOL default limit
 
         MerkleTree::initialize::<Poseidon>(tree_account);
+        tree_account.root_history_slots[0] = Clock::get()?.slot;
         
         let token_account = &mut ctx.accounts.tree_token_account;
         token_account.authority = ctx.accounts.authority.key();
         token_account.bump = ctx.bumps.tree_token_account;
+
+        // roots only expire by count, and there's no delay, auditor or denomination until the
+        // authority sets them
+        let pool_config = &mut ctx.accounts.pool_config;
+        pool_config.authority = ctx.accounts.authority.key();
+        pool_config.bump = ctx.bumps.pool_config;
         
         msg!("Sparse Merkle Tree initialized successfully with deposit limit: {} lamports", tree_account.max_deposit_amount);
         Ok(())
     }
 
+    /**
+     * Creates the pool config of a pool initialized before the config existed, with the
+     * defaults of `initialize`. Only the authority can call this.
+     */
+    pub fn initialize_pool_config(ctx: Context<InitializePoolConfig>) -> Result<()> {
+        let pool_config = &mut ctx.accounts.pool_config;
+        pool_config.authority = ctx.accounts.authority.key();
+        pool_config.bump = ctx.bumps.pool_config;
+
+        msg!("Pool config initialized");
+        Ok(())
+    }
+
+    /**
+     * Grows the tree of a pool initialized before tree accounts had a layout version to the
+     * current layout, see `MerkleTree::migrate`. The authority pays for the extra rent. Run
+     * this before any other instruction on the pool, they can't load the old layout.
+     */
+    pub fn migrate_tree_account(ctx: Context<MigrateTreeAccount>) -> Result<()> {
+        let tree_info = ctx.accounts.tree_account.to_account_info();
+        MerkleTree::check_legacy_account(&tree_info.try_borrow_data()?, &ctx.accounts.authority.key())?;
+
+        let size = 8 + std::mem::size_of::<MerkleTreeAccount>();
+        let rent = Rent::get()?.minimum_balance(size).saturating_sub(tree_info.lamports());
+        if rent > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: tree_info.clone(),
+                    },
+                ),
+                rent,
+            )?;
+        }
+        tree_info.realloc(size, true)?;
+
+        let mut data = tree_info.try_borrow_mut_data()?;
+        let tree_account: &mut MerkleTreeAccount = bytemuck::from_bytes_mut(&mut data[8..size]);
+        MerkleTree::migrate(tree_account, Clock::get()?.slot)?;
+
+        msg!("Tree account migrated to layout version {}", merkle_tree::MERKLE_TREE_ACCOUNT_VERSION);
+        Ok(())
+    }
+
+    /**
+     * Replaces a full shard of the tree set with a new tree. Anyone can call this, and each
+     * full shard can only be replaced once. Roots of the full tree stay valid, so its notes
+     * can still be spent.
+     */
+    pub fn rollover_tree(ctx: Context<RolloverTree>, tree_index: u32) -> Result<()> {
+        let tree_set = &mut ctx.accounts.tree_set;
+        TreeSet::initialize_if_empty(tree_set, ctx.accounts.authority.key(), ctx.bumps.tree_set);
+
+        let full_tree_account = ctx.accounts.full_tree_account.load()?;
+        require!(MerkleTree::is_full(&full_tree_account), ErrorCode::MerkleTreeNotFull);
+        require!(
+            TreeSet::rollover(tree_set, full_tree_account.tree_index)? == tree_index,
+            ErrorCode::InvalidTreeIndex
+        );
+
+        TreeSet::initialize_tree::<Poseidon>(
+            &mut *ctx.accounts.new_tree_account.load_init()?,
+            &full_tree_account,
+            tree_index,
+            ctx.bumps.new_tree_account,
+            Clock::get()?.slot,
+        );
+
+        msg!("Rolled over tree {} to tree {}", full_tree_account.tree_index, tree_index);
+        Ok(())
+    }
+
+    /**
+     * Adds a tree shard, so outputs can be appended to several trees in parallel instead of
+     * write-locking a single tree account. Only the authority can call this.
+     */
+    pub fn add_tree_shard(ctx: Context<AddTreeShard>, tree_index: u32) -> Result<()> {
+        let tree_set = &mut ctx.accounts.tree_set;
+        TreeSet::initialize_if_empty(tree_set, ctx.accounts.authority.key(), ctx.bumps.tree_set);
+
+        require!(TreeSet::add_shard(tree_set)? == tree_index, ErrorCode::InvalidTreeIndex);
+
+        TreeSet::initialize_tree::<Poseidon>(
+            &mut *ctx.accounts.new_tree_account.load_init()?,
+            &*ctx.accounts.tree_account.load()?,
+            tree_index,
+            ctx.bumps.new_tree_account,
+            Clock::get()?.slot,
+        );
+
+        msg!("Added tree shard {}, {} shards active", tree_index, tree_set.shard_count);
+        Ok(())
+    }
+
+    /**
+     * Gives a tree an insertion queue. From then on `transact` queues its outputs instead of
+     * hashing them into the tree, and `process_queue` inserts them. Only the authority can
+     * call this.
+     */
+    pub fn initialize_commitment_queue(ctx: Context<InitializeCommitmentQueue>) -> Result<()> {
+        let tree_account = &mut ctx.accounts.tree_account.load_mut()?;
+        CommitmentQueue::initialize(
+            &mut *ctx.accounts.commitment_queue.load_init()?,
+            ctx.accounts.tree_account.key(),
+            ctx.bumps.commitment_queue,
+        );
+        tree_account.queue_enabled = 1;
+
+        msg!("Commitment queue initialized for tree {}", tree_account.tree_index);
+        Ok(())
+    }
+
+    /**
+     * Inserts up to `max_leaves` queued commitments into the tree. Anyone can call this, the
+     * leaves and their order were fixed when they were queued. They go in as one batch with a
+     * single new root, so cranking can't push roots out of the root history faster than
+     * transacts fill the queue.
+     */
+    pub fn process_queue(ctx: Context<ProcessQueue>, max_leaves: u32) -> Result<()> {
+        let tree_account = &mut ctx.accounts.tree_account.load_mut()?;
+        let queue = &mut ctx.accounts.commitment_queue.load_mut()?;
+
+        let inserted = CommitmentQueue::process::<Poseidon>(queue, tree_account, max_leaves as u64, Clock::get()?.slot)?;
+        MerkleTree::checkpoint_root(tree_account, ctx.accounts.pool_config.maturity_delay);
+
+        msg!("Inserted {} queued commitments, {} left", inserted, queue.len);
+        Ok(())
+    }
+
     /**
      * Update the maximum deposit amount limit. Only the authority can call this.
      */
@@ -56,13 +202,302 @@ pub mod zkcash {
         Ok(())
     }
 
+    /**
+     * Sets how many slots a root stays valid for proofs, on top of the root history size.
+     * 0 disables the age limit. Only the authority can call this.
+     */
+    pub fn update_max_root_age(ctx: Context<UpdateMaxRootAge>, max_root_age: u64) -> Result<()> {
+        let pool_config = &mut ctx.accounts.pool_config;
+        require!(
+            max_root_age == 0 || pool_config.maturity_delay < max_root_age,
+            ErrorCode::InvalidMaturityDelay
+        );
+        pool_config.max_root_age = max_root_age;
+
+        msg!("Max root age updated to: {} slots", max_root_age);
+        Ok(())
+    }
+
+    /**
+     * Sets how many slots old a root must be before it can be used for a transaction that
+     * moves funds out of the pool, a withdrawal or a fee. Notes are only in roots created
+     * after they were inserted, so this is a cool-down between a deposit and spending it.
+     * 0 disables the delay. Only the authority can call this.
+     *
+     * Trees keep checkpoints of their roots sampled by slot, see `MerkleTree::checkpoint_root`,
+     * so a mature root stays usable however fast new roots push it out of the root history.
+     */
+    pub fn update_maturity_delay(ctx: Context<UpdateMaturityDelay>, maturity_delay: u64) -> Result<()> {
+        let pool_config = &mut ctx.accounts.pool_config;
+        require!(
+            pool_config.max_root_age == 0 || maturity_delay < pool_config.max_root_age,
+            ErrorCode::InvalidMaturityDelay
+        );
+        pool_config.maturity_delay = maturity_delay;
+
+        msg!("Maturity delay updated to: {} slots", maturity_delay);
+        Ok(())
+    }
+
+    /**
+     * Sets the X25519 public key of the pool's auditor. While it's set, every `transact` into
+     * any tree of the pool must carry a ciphertext of each output for the auditor, which is stored
+     * next to the output. The ciphertexts aren't proven to open to the outputs, so honest clients
+     * get audited but a sender can still hide an output from the auditor. A zero key removes the
+     * auditor. Only the authority can call this.
+     */
+    pub fn update_auditor_key(ctx: Context<UpdateAuditorKey>, auditor_key: [u8; 32]) -> Result<()> {
+        ctx.accounts.pool_config.auditor_key = auditor_key;
+
+        msg!("Auditor key updated");
+        Ok(())
+    }
+
+    /**
+     * Restricts deposits and withdrawals of the pool to fixed amounts, so they can't be
+     * linked by their amount. `transact` then only accepts an `ext_amount` whose absolute
+     * value is one of the denominations, or 0 for transfers inside the pool, which pay their
+     * relayer fee from their inputs. The fee itself isn't restricted. The denominations must be
+     * non-zero and sorted in increasing order. An empty list lifts the restriction. Only the
+     * authority can call this.
+     */
+    pub fn update_denominations(ctx: Context<UpdateDenominations>, denominations: Vec<u64>) -> Result<()> {
+        require!(utils::check_denomination_set(&denominations), ErrorCode::InvalidDenominations);
+
+        let pool_config = &mut ctx.accounts.pool_config;
+        pool_config.denominations = [0u64; MAX_DENOMINATIONS];
+        pool_config.denominations[..denominations.len()].copy_from_slice(&denominations);
+
+        msg!("Denominations updated to: {:?} lamports", denominations);
+        Ok(())
+    }
+
+    /**
+     * Stores the verifying key of the association circuit, `circuits/association.circom`, for
+     * the pool. Association proofs of every tree of the pool are checked against it. The key
+     * can only be set once, so the authority can't later swap in a key that accepts any
+     * proof. Only the authority can call this.
+     */
+    pub fn set_association_verifying_key(
+        ctx: Context<SetAssociationVerifyingKey>,
+        verifying_key: AssociationVerifyingKey,
+    ) -> Result<()> {
+        let association_verifying_key = &mut ctx.accounts.association_verifying_key;
+        association_verifying_key.authority = ctx.accounts.authority.key();
+        association_verifying_key.verifying_key = verifying_key;
+        association_verifying_key.bump = ctx.bumps.association_verifying_key;
+
+        msg!("Association verifying key set");
+        Ok(())
+    }
+
+    /**
+     * Creates the association set of a provider. The provider publishes roots of approved
+     * note commitments with `update_association_root`.
+     */
+    pub fn create_association_set(ctx: Context<CreateAssociationSet>) -> Result<()> {
+        AssociationSet::initialize(
+            &mut ctx.accounts.association_set,
+            ctx.accounts.provider.key(),
+            ctx.bumps.association_set,
+        );
+
+        msg!("Association set created for provider {}", ctx.accounts.provider.key());
+        Ok(())
+    }
+
+    /**
+     * Publishes a new root of the provider's association set. Only the provider can call this.
+     */
+    pub fn update_association_root(ctx: Context<UpdateAssociationRoot>, root: [u8; 32]) -> Result<()> {
+        let association_set = &mut ctx.accounts.association_set;
+        AssociationSet::update_root(association_set, root)?;
+
+        msg!("Association root {} published", association_set.root_count);
+        Ok(())
+    }
+
+    /**
+     * Stores the verifying key of the batch deposit circuit, `circuits/batchDeposit.circom`,
+     * for the pool. Like the association key, it can only be set once. Only the authority can
+     * call this.
+     */
+    pub fn set_batch_deposit_verifying_key(
+        ctx: Context<SetBatchDepositVerifyingKey>,
+        verifying_key: BatchDepositVerifyingKey,
+    ) -> Result<()> {
+        let batch_deposit_verifying_key = &mut ctx.accounts.batch_deposit_verifying_key;
+        batch_deposit_verifying_key.authority = ctx.accounts.authority.key();
+        batch_deposit_verifying_key.verifying_key = verifying_key;
+        batch_deposit_verifying_key.bump = ctx.bumps.batch_deposit_verifying_key;
+
+        msg!("Batch deposit verifying key set");
+        Ok(())
+    }
+
+    /**
+     * Adds outputs to the signer's pending batch deposit into the pool, creating it on the
+     * first call. A batch takes up to `BATCH_DEPOSIT_OUTPUTS` outputs, and is deposited with
+     * `batch_deposit`, see `PendingBatch`.
+     */
+    pub fn add_batch_outputs(ctx: Context<AddBatchOutputs>, outputs: Vec<BatchOutput>) -> Result<()> {
+        let batch = &mut ctx.accounts.batch_deposit;
+        batch.signer = ctx.accounts.signer.key();
+        batch.bump = ctx.bumps.batch_deposit;
+        PendingBatch::push_outputs(batch, outputs)?;
+
+        msg!("Batch deposit has {} outputs", batch.outputs.len());
+        Ok(())
+    }
+
+    /**
+     * Drops the signer's pending batch deposit and refunds its rent, without depositing.
+     */
+    pub fn cancel_batch_deposit(_ctx: Context<CancelBatchDeposit>) -> Result<()> {
+        msg!("Batch deposit cancelled");
+        Ok(())
+    }
+
+    /**
+     * Publishes the shielded pubkey and encryption key of the signing wallet, so others can
+     * send notes to the wallet address. Calling it again rotates the keys. Notes sent to
+     * the old keys stay spendable with the old keys.
+     */
+    pub fn register_account(
+        ctx: Context<RegisterAccount>,
+        shielded_pubkey: [u8; 32],
+        encryption_key: [u8; 32],
+    ) -> Result<()> {
+        require!(
+            utils::check_shielded_account_keys(shielded_pubkey, encryption_key),
+            ErrorCode::InvalidShieldedAccountKeys
+        );
+
+        let shielded_account = &mut ctx.accounts.shielded_account;
+        shielded_account.owner = ctx.accounts.owner.key();
+        shielded_account.shielded_pubkey = shielded_pubkey;
+        shielded_account.encryption_key = encryption_key;
+        shielded_account.bump = ctx.bumps.shielded_account;
+
+        msg!("Shielded account registered for {}", ctx.accounts.owner.key());
+        Ok(())
+    }
+
+    /**
+     * Removes the signing wallet from the registry and refunds the rent to it.
+     */
+    pub fn delete_account(ctx: Context<DeleteAccount>) -> Result<()> {
+        msg!("Shielded account deleted for {}", ctx.accounts.owner.key());
+        Ok(())
+    }
+
+    /**
+     * Runs the checks of `transact` without creating accounts or moving funds, and returns
+     * the first failing check as a `TransactVerdict`. Relayers can simulate this instruction
+     * to reject bad requests before paying for a failed `transact`. It takes the accounts of
+     * `transact`, read-only, and the same remaining accounts.
+     *
+     * The runtime strips trailing zeros from return data, so a `Valid` verdict, which encodes
+     * to a single zero byte, shows up as no return data. `decode_transact_verdict` in the
+     * interface crate handles this.
+     */
+    pub fn check_transact<'info>(
+        ctx: Context<'_, '_, '_, 'info, CheckTransact<'info>>,
+        proof: Proof,
+        ext_data: ExtData,
+    ) -> Result<TransactVerdict> {
+        let tree_account = ctx.accounts.tree_account.load()?;
+        let root_tree_account = ctx.accounts.root_tree_account.as_ref().map(|account| account.load()).transpose()?;
+        let commitment_queue = ctx.accounts.commitment_queue.as_ref().map(|account| account.load()).transpose()?;
+        let tree_set = ctx.accounts.tree_set.as_deref();
+        let tree_token_account_info = ctx.accounts.tree_token_account.to_account_info();
+        let recipient_info = ctx.accounts.recipient.to_account_info();
+        let fee_recipient_info = ctx.accounts.fee_recipient_account.to_account_info();
+        let rent = Rent::get()?;
+
+        let state = preflight::TransactState {
+            tree_account: &tree_account,
+            root_tree_account: root_tree_account.as_deref(),
+            commitment_queue: commitment_queue.as_deref(),
+            tree_set,
+            pool_config: &ctx.accounts.pool_config,
+            nullifiers_spent: [
+                [ctx.accounts.nullifier0.owner == &crate::ID, ctx.accounts.nullifier3.owner == &crate::ID],
+                [ctx.accounts.nullifier2.owner == &crate::ID, ctx.accounts.nullifier1.owner == &crate::ID],
+            ],
+            commitments_exist: [
+                [ctx.accounts.commitment0.owner == &crate::ID, ctx.accounts.commitment2.owner == &crate::ID],
+                [ctx.accounts.commitment3.owner == &crate::ID, ctx.accounts.commitment1.owner == &crate::ID],
+            ],
+            tree_token_account: &tree_token_account_info,
+            recipient: &recipient_info,
+            fee_recipient_account: &fee_recipient_info,
+            remaining_accounts: ctx.remaining_accounts,
+            association_set: ctx.accounts.association_set.as_deref().map(|account| &**account),
+            association_verifying_key: ctx.accounts.association_verifying_key.as_deref().map(|account| &**account),
+            current_slot: Clock::get()?.slot,
+            rent: &rent,
+        };
+        let verdict = preflight::check_transact(&state, &proof, &ext_data);
+
+        msg!("Transact preflight verdict: {:?}", verdict);
+        Ok(verdict)
+    }
+
     /**
      * Users deposit or withdraw from the program.
      * 
-     * Reentrant attacks are not possible, because nullifier creation is checked by anchor first.
+     * Reentrant attacks are not possible, because the nullifier accounts are checked first and
+     * created before any funds move.
+     *
+     * The inserted leaf indices, commitments and the new root are returned through
+     * `set_return_data` as a Borsh-encoded `TransactReturnData`.
+     *
+     * A withdrawal with `ext_data.payouts` is split across the payout recipients, passed as
+     * remaining accounts, and `recipient` receives nothing.
+     *
+     * If the tree has a commitment queue, the outputs are queued instead. The returned leaf
+     * indices are the ones they will be inserted at, and the returned root doesn't include
+     * them yet.
+     *
+     * Every tree of the pool shares one vault, so a note pays out the same whichever tree
+     * holds it and whichever shard the outputs go to. The circuit proves membership against a
+     * single root, so both inputs must be notes of the tree named by `root_tree_account`; a
+     * proof can't combine notes of different trees.
+     *
+     * With `ext_data.association`, the inputs are also proven to be in a recent root of an
+     * association set, passed as `association_set` together with the pool's
+     * `association_verifying_key`.
      */
-    pub fn transact(ctx: Context<Transact>, proof: Proof, ext_data: ExtData) -> Result<()> {
+    pub fn transact(ctx: Context<Transact>, proof: Proof, ext_data: ExtData) -> Result<TransactReturnData> {
+        // Check the nullifiers and commitments before any account is created, so a double spend
+        // fails with a dedicated error instead of the system program's "already in use".
+        // A nullifier is spent if it was recorded as either input, so both slots are checked.
+        let nullifiers_spent = [
+            [ctx.accounts.nullifier0.owner == &crate::ID, ctx.accounts.nullifier3.owner == &crate::ID],
+            [ctx.accounts.nullifier2.owner == &crate::ID, ctx.accounts.nullifier1.owner == &crate::ID],
+        ];
+        if let Some(index) = utils::spent_input(nullifiers_spent) {
+            msg!("Nullifier of input {} has already been spent", index);
+            return Err(error!(ErrorCode::NullifierAlreadySpent).with_account_name(format!("nullifier{}", index)));
+        }
+        let commitments_exist = [
+            [ctx.accounts.commitment0.owner == &crate::ID, ctx.accounts.commitment2.owner == &crate::ID],
+            [ctx.accounts.commitment3.owner == &crate::ID, ctx.accounts.commitment1.owner == &crate::ID],
+        ];
+        if let Some(index) = utils::existing_output(commitments_exist) {
+            msg!("Commitment of output {} already exists", index);
+            return Err(error!(ErrorCode::CommitmentAlreadyExists).with_account_name(format!("commitment{}", index)));
+        }
+
         let tree_account = &mut ctx.accounts.tree_account.load_mut()?;
+        let pool_config = &ctx.accounts.pool_config;
+
+        // the outputs go to an active tree of the tree set
+        require!(
+            TreeSet::accepts_outputs(ctx.accounts.tree_set.as_deref(), tree_account.tree_index),
+            ErrorCode::InvalidTreeIndex
+        );
 
         // check the authority is the same as the one in the accounts
         let authority_key = ctx.accounts.authority.key();
@@ -72,33 +507,100 @@ pub mod zkcash {
             ErrorCode::Unauthorized
         );
 
-        // check if proof.root is in the tree_account's proof history
-        require!(
-            MerkleTree::is_known_root(&tree_account, proof.root),
-            ErrorCode::UnknownRoot
-        );
+        // check if proof.root is in the proof history of the tree it was taken from, recent enough,
+        // and old enough if funds leave the pool
+        let current_slot = Clock::get()?.slot;
+        let moves_funds_out = ext_data.ext_amount < 0 || ext_data.fee > 0;
+        match &ctx.accounts.root_tree_account {
+            Some(root_tree_account) => MerkleTree::check_root(&*root_tree_account.load()?, pool_config, proof.root, current_slot, moves_funds_out)?,
+            None => MerkleTree::check_root(tree_account, pool_config, proof.root, current_slot, moves_funds_out)?,
+        }
 
         // check if the ext_data hashes to the same ext_data in the proof
-        let mut serialized_ext_data = Vec::new();
-        ext_data.serialize(&mut serialized_ext_data)?;
-        let calculated_ext_data_hash = hash(&serialized_ext_data).to_bytes();
-
         require!(
-            Fr::from_le_bytes_mod_order(&calculated_ext_data_hash) == Fr::from_be_bytes_mod_order(&proof.ext_data_hash),
+            utils::check_ext_data_hash(&ext_data, proof.ext_data_hash),
             ErrorCode::ExtDataHashMismatch
         );
 
+        // the auditor ciphertexts are part of the ext data hash, so the relayer can't swap them,
+        // but nothing proves they open to the outputs, see `has_auditor_outputs`
+        require!(
+            utils::has_auditor_outputs(pool_config.auditor_key, &ext_data),
+            ErrorCode::MissingAuditorOutputs
+        );
+
         require!(
             utils::check_public_amount(ext_data.ext_amount, ext_data.fee, proof.public_amount),
             ErrorCode::InvalidPublicAmountData
         );
         
+        require!(
+            utils::check_denomination(&pool_config.denominations, ext_data.ext_amount.unsigned_abs()),
+            ErrorCode::InvalidDenomination
+        );
+
+        // payouts must split exactly the withdrawn amount
+        require!(utils::check_payouts(&ext_data), ErrorCode::InvalidPayouts);
+
         let ext_amount = ext_data.ext_amount;
         let fee = ext_data.fee;
 
+        // withdraw-and-call only makes sense for withdrawals into the committed recipient
+        require!(
+            utils::check_withdraw_call(&ext_data, &ctx.accounts.recipient.key()),
+            ErrorCode::InvalidWithdrawCall
+        );
+
         // verify the proof
         require!(verify_proof(proof.clone(), VERIFYING_KEY), ErrorCode::InvalidProof);
 
+        // the association proof shares the nullifiers of the transaction proof, so it is about
+        // the inputs that were just proven
+        if let Some(association) = &ext_data.association {
+            let (Some(association_set), Some(association_verifying_key)) =
+                (&ctx.accounts.association_set, &ctx.accounts.association_verifying_key)
+            else {
+                return err!(ErrorCode::InvalidAssociationAccounts);
+            };
+            require_keys_eq!(
+                association_set.key(),
+                association.association_set,
+                ErrorCode::InvalidAssociationAccounts
+            );
+            require!(
+                AssociationSet::is_known_root(association_set, association.root),
+                ErrorCode::UnknownAssociationRoot
+            );
+            require!(
+                utils::verify_association_proof(association, &proof.input_nullifiers, &association_verifying_key.verifying_key),
+                ErrorCode::InvalidAssociationProof
+            );
+            msg!("Inputs proven to be in association set {}", association.association_set);
+        }
+
+        // mark both inputs as spent
+        let rent = Rent::get()?;
+        let signer_info = ctx.accounts.signer.to_account_info();
+        let system_program_info = ctx.accounts.system_program.to_account_info();
+        utils::init_pda_account(
+            &ctx.accounts.nullifier0,
+            &signer_info,
+            &system_program_info,
+            8 + std::mem::size_of::<NullifierAccount>(),
+            &[b"nullifier0", proof.input_nullifiers[0].as_ref(), &[ctx.bumps.nullifier0]],
+            &NullifierAccount { bump: ctx.bumps.nullifier0 },
+            &rent,
+        )?;
+        utils::init_pda_account(
+            &ctx.accounts.nullifier1,
+            &signer_info,
+            &system_program_info,
+            8 + std::mem::size_of::<NullifierAccount>(),
+            &[b"nullifier1", proof.input_nullifiers[1].as_ref(), &[ctx.bumps.nullifier1]],
+            &NullifierAccount { bump: ctx.bumps.nullifier1 },
+            &rent,
+        )?;
+
         if ext_amount > 0 {
             // Check deposit limit for deposits
             let deposit_amount = ext_amount as u64;
@@ -130,16 +632,20 @@ pub mod zkcash {
                 .map_err(|_| ErrorCode::InvalidExtAmount)?;
             require!(tree_token_account_info.lamports() >= ext_amount_abs, ErrorCode::InsufficientFundsForWithdrawal);
 
-            let tree_token_balance = tree_token_account_info.lamports();
-            let recipient_balance = recipient_account_info.lamports();
-            
-            let new_tree_token_balance = tree_token_balance.checked_sub(ext_amount_abs)
-                .ok_or(ErrorCode::ArithmeticOverflow)?;
-            let new_recipient_balance = recipient_balance.checked_add(ext_amount_abs)
-                .ok_or(ErrorCode::ArithmeticOverflow)?;
-                
-            **tree_token_account_info.try_borrow_mut_lamports()? = new_tree_token_balance;
-            **recipient_account_info.try_borrow_mut_lamports()? = new_recipient_balance;
+            match &ext_data.payouts {
+                Some(payouts) => utils::transfer_payouts(
+                    &tree_token_account_info,
+                    payouts,
+                    ctx.remaining_accounts,
+                    &rent,
+                )?,
+                None => utils::transfer_lamports_from_pda(
+                    &tree_token_account_info,
+                    &recipient_account_info,
+                    ext_amount_abs,
+                    &rent,
+                )?,
+            }
         }
         
         if fee > 0 {
@@ -148,33 +654,169 @@ pub mod zkcash {
 
             require!(tree_token_account_info.lamports() >= fee, ErrorCode::InsufficientFundsForFee);
 
-            let tree_token_balance = tree_token_account_info.lamports();
-            let fee_recipient_balance = fee_recipient_account_info.lamports();
-            
-            let new_tree_token_balance = tree_token_balance.checked_sub(fee)
-                .ok_or(ErrorCode::ArithmeticOverflow)?;
-            let new_fee_recipient_balance = fee_recipient_balance.checked_add(fee)
-                .ok_or(ErrorCode::ArithmeticOverflow)?;
-                
-            **tree_token_account_info.try_borrow_mut_lamports()? = new_tree_token_balance;
-            **fee_recipient_account_info.try_borrow_mut_lamports()? = new_fee_recipient_balance;
+            utils::transfer_lamports_from_pda(
+                &tree_token_account_info,
+                &fee_recipient_account_info,
+                fee,
+                &rent,
+            )?;
         }
 
-        let next_index_to_insert = tree_account.next_index;
-        MerkleTree::append::<Poseidon>(proof.output_commitments[0], tree_account)?;
-        MerkleTree::append::<Poseidon>(proof.output_commitments[1], tree_account)?;
+        require!(
+            (tree_account.queue_enabled != 0) == ctx.accounts.commitment_queue.is_some(),
+            ErrorCode::InvalidCommitmentQueue
+        );
+        let leaf_indices = match &ctx.accounts.commitment_queue {
+            Some(commitment_queue) => {
+                let queue = &mut commitment_queue.load_mut()?;
+                [
+                    CommitmentQueue::push(queue, tree_account, proof.output_commitments[0])?,
+                    CommitmentQueue::push(queue, tree_account, proof.output_commitments[1])?,
+                ]
+            }
+            None => {
+                let next_index_to_insert = tree_account.next_index;
+                MerkleTree::append_at_slot::<Poseidon>(proof.output_commitments[0], tree_account, current_slot)?;
+                MerkleTree::append_at_slot::<Poseidon>(proof.output_commitments[1], tree_account, current_slot)?;
+                MerkleTree::checkpoint_root(tree_account, ctx.accounts.pool_config.maturity_delay);
+
+                [
+                    next_index_to_insert,
+                    next_index_to_insert.checked_add(1).ok_or(ErrorCode::ArithmeticOverflow)?,
+                ]
+            }
+        };
 
-        ctx.accounts.commitment0.commitment = proof.output_commitments[0];
-        ctx.accounts.commitment0.encrypted_output = ext_data.encrypted_output1.clone();
-        ctx.accounts.commitment0.index = next_index_to_insert;
-        ctx.accounts.commitment0.bump = ctx.bumps.commitment0;
-        
-        ctx.accounts.commitment1.commitment = proof.output_commitments[1];
-        ctx.accounts.commitment1.encrypted_output = ext_data.encrypted_output2.clone();
-        ctx.accounts.commitment1.index = next_index_to_insert.checked_add(1)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
-        ctx.accounts.commitment1.bump = ctx.bumps.commitment1;
+        let (auditor_output1, auditor_output2) = match &ext_data.auditor_outputs {
+            Some(outputs) => (outputs.encrypted_output1.clone(), outputs.encrypted_output2.clone()),
+            None => (Vec::new(), Vec::new()),
+        };
+
+        utils::init_pda_account(
+            &ctx.accounts.commitment0,
+            &signer_info,
+            &system_program_info,
+            8 + std::mem::size_of::<CommitmentAccount>() + ext_data.encrypted_output1.len() + auditor_output1.len(),
+            &[b"commitment0", proof.output_commitments[0].as_ref(), &[ctx.bumps.commitment0]],
+            &CommitmentAccount {
+                commitment: proof.output_commitments[0],
+                encrypted_output: ext_data.encrypted_output1.clone(),
+                index: leaf_indices[0],
+                bump: ctx.bumps.commitment0,
+                auditor_output: auditor_output1,
+                view_tag: utils::view_tag(&ext_data.encrypted_output1),
+                tree_index: tree_account.tree_index,
+            },
+            &rent,
+        )?;
+        utils::init_pda_account(
+            &ctx.accounts.commitment1,
+            &signer_info,
+            &system_program_info,
+            8 + std::mem::size_of::<CommitmentAccount>() + ext_data.encrypted_output2.len() + auditor_output2.len(),
+            &[b"commitment1", proof.output_commitments[1].as_ref(), &[ctx.bumps.commitment1]],
+            &CommitmentAccount {
+                commitment: proof.output_commitments[1],
+                encrypted_output: ext_data.encrypted_output2.clone(),
+                index: leaf_indices[1],
+                bump: ctx.bumps.commitment1,
+                auditor_output: auditor_output2,
+                view_tag: utils::view_tag(&ext_data.encrypted_output2),
+                tree_index: tree_account.tree_index,
+            },
+            &rent,
+        )?;
+
+        let return_data = TransactReturnData {
+            version: TRANSACT_RETURN_DATA_VERSION,
+            leaf_indices,
+            commitments: proof.output_commitments,
+            root: tree_account.root,
+            root_index: tree_account.root_index,
+            tree_index: tree_account.tree_index,
+        };
+
+        // Invoke the committed target once the recipient holds the withdrawn funds.
+        // This runs before the return data is set, so the target can't overwrite it.
+        if let Some(call) = &ext_data.call {
+            utils::invoke_withdraw_call(call, ctx.remaining_accounts)?;
+        }
         
+        Ok(return_data)
+    }
+
+    /**
+     * Deposits `amount` into the outputs of the signer's pending batch, see `PendingBatch`.
+     * The proof shows that the hidden amounts of the outputs add up to `amount`. The outputs
+     * take consecutive leaves, and are published in a `BatchDepositEvent` instead of commitment
+     * accounts. The pending batch is closed, refunding its rent to the signer.
+     */
+    pub fn batch_deposit(ctx: Context<BatchDeposit>, amount: u64, proof: BatchDepositProof) -> Result<()> {
+        let tree_account = &mut ctx.accounts.tree_account.load_mut()?;
+        let pool_config = &ctx.accounts.pool_config;
+        let batch = &ctx.accounts.batch_deposit;
+
+        require!(!batch.outputs.is_empty(), ErrorCode::EmptyBatchDeposit);
+        require!(
+            TreeSet::accepts_outputs(ctx.accounts.tree_set.as_deref(), tree_account.tree_index),
+            ErrorCode::InvalidTreeIndex
+        );
+        require!(amount <= tree_account.max_deposit_amount, ErrorCode::DepositLimitExceeded);
+        require!(
+            utils::check_denomination(&pool_config.denominations, amount),
+            ErrorCode::InvalidDenomination
+        );
+        require!(
+            PendingBatch::has_auditor_outputs(batch, pool_config.auditor_key),
+            ErrorCode::MissingAuditorOutputs
+        );
+
+        let public_inputs = PendingBatch::public_inputs(batch, amount).ok_or(ErrorCode::InvalidBatchOutput)?;
+        require!(
+            utils::verify_batch_deposit_proof(&proof, &public_inputs, &ctx.accounts.batch_deposit_verifying_key.verifying_key),
+            ErrorCode::InvalidProof
+        );
+
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.signer.to_account_info(),
+                    to: ctx.accounts.tree_token_account.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        require!(
+            (tree_account.queue_enabled != 0) == ctx.accounts.commitment_queue.is_some(),
+            ErrorCode::InvalidCommitmentQueue
+        );
+        let commitments: Vec<[u8; 32]> = batch.outputs.iter().map(|output| output.commitment).collect();
+        let first_leaf_index = match &ctx.accounts.commitment_queue {
+            Some(commitment_queue) => {
+                let queue = &mut commitment_queue.load_mut()?;
+                let first_leaf_index = CommitmentQueue::push(queue, tree_account, commitments[0])?;
+                for commitment in &commitments[1..] {
+                    CommitmentQueue::push(queue, tree_account, *commitment)?;
+                }
+                first_leaf_index
+            }
+            None => {
+                let first_leaf_index = MerkleTree::append_batch::<Poseidon>(&commitments, tree_account, Clock::get()?.slot)?;
+                MerkleTree::checkpoint_root(tree_account, pool_config.maturity_delay);
+                first_leaf_index
+            }
+        };
+
+        emit!(BatchDepositEvent {
+            tree_account: ctx.accounts.tree_account.key(),
+            tree_index: tree_account.tree_index,
+            first_leaf_index,
+            outputs: batch.outputs.clone(),
+        });
+
+        msg!("Batch of {} outputs deposited at leaf {}", commitments.len(), first_leaf_index);
         Ok(())
     }
 }
@@ -199,63 +841,239 @@ pub struct ExtData {
     pub encrypted_output1: Vec<u8>,
     pub encrypted_output2: Vec<u8>,
     pub fee: u64,
+    /// Optional instruction to invoke after a withdrawal. It is part of the ext data hash,
+    /// so the target program and instruction data are bound to the proof.
+    pub call: Option<WithdrawCall>,
+    /// Ciphertexts of the outputs for the pool's auditor, required when the pool has one.
+    pub auditor_outputs: Option<AuditorOutputs>,
+    /// Splits a withdrawal across several recipients instead of paying `recipient`. The
+    /// recipient accounts are passed as remaining accounts, in the same order.
+    pub payouts: Option<Vec<Payout>>,
+    /// Proof that both inputs are in an association set. It is part of the ext data hash,
+    /// so the relayer can't strip it or point it at another set.
+    pub association: Option<AssociationProof>,
+}
+
+/// The target of a withdraw-and-call. The accounts for the target instruction are passed
+/// as remaining accounts, with the target program first, and must match `accounts`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct WithdrawCall {
+    pub program_id: Pubkey,
+    /// The accounts of the target instruction. They are part of the ext data hash, so the
+    /// relayer can't substitute them.
+    pub accounts: Vec<WithdrawCallAccount>,
+    pub data: Vec<u8>,
+}
+
+/// An account of a withdraw-and-call instruction. None of them is passed as a signer.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WithdrawCallAccount {
+    pub pubkey: Pubkey,
+    pub is_writable: bool,
+}
+
+/// The outputs encrypted to the auditor key of the pool, in the same order as the outputs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AuditorOutputs {
+    pub encrypted_output1: Vec<u8>,
+    pub encrypted_output2: Vec<u8>,
+}
+
+/// A proof of the association circuit, `circuits/association.circom`. It shows that every
+/// non-zero input of the transaction is a leaf of `root`, a root of `association_set`, with
+/// the nullifiers of the transaction proof as public inputs. Big-endian like `Proof`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AssociationProof {
+    pub association_set: Pubkey,
+    pub root: [u8; 32],
+    pub proof_a: [u8; 64],
+    pub proof_b: [u8; 128],
+    pub proof_c: [u8; 64],
+}
+
+/// Groth16 verifying key of the association circuit, big-endian like `VERIFYING_KEY`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssociationVerifyingKey {
+    pub alpha_g1: [u8; 64],
+    pub beta_g2: [u8; 128],
+    pub gamma_g2: [u8; 128],
+    pub delta_g2: [u8; 128],
+    pub ic: [[u8; 64]; ASSOCIATION_PUBLIC_INPUTS + 1],
+}
+
+/// An output of a batch deposit: the commitment of a note, and the note encrypted to its
+/// owner like `ExtData.encrypted_output1`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BatchOutput {
+    pub commitment: [u8; 32],
+    pub encrypted_output: Vec<u8>,
+    /// The note encrypted for the pool's auditor, required when the pool has one.
+    pub auditor_output: Vec<u8>,
+}
+
+/// A proof of the batch deposit circuit, `circuits/batchDeposit.circom`. Big-endian like `Proof`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BatchDepositProof {
+    pub proof_a: [u8; 64],
+    pub proof_b: [u8; 128],
+    pub proof_c: [u8; 64],
+}
+
+/// Groth16 verifying key of the batch deposit circuit, big-endian like `VERIFYING_KEY`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchDepositVerifyingKey {
+    pub alpha_g1: [u8; 64],
+    pub beta_g2: [u8; 128],
+    pub gamma_g2: [u8; 128],
+    pub delta_g2: [u8; 128],
+    pub ic: [[u8; 64]; BATCH_DEPOSIT_PUBLIC_INPUTS + 1],
+}
+
+/// The outputs of a `batch_deposit`, at leaves `first_leaf_index..first_leaf_index + outputs.len()`
+/// of the tree. In a tree with a commitment queue they are inserted by a later `process_queue`.
+#[event]
+pub struct BatchDepositEvent {
+    pub tree_account: Pubkey,
+    pub tree_index: u32,
+    pub first_leaf_index: u64,
+    pub outputs: Vec<BatchOutput>,
+}
+
+pub const MAX_PAYOUTS: usize = 8;
+
+/// One recipient of a split withdrawal.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Payout {
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
+
+pub const TRANSACT_RETURN_DATA_VERSION: u8 = 2;
+
+/// Return data of `transact`, so callers learn the inserted leaves without reading the tree.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransactReturnData {
+    pub version: u8,
+    pub leaf_indices: [u64; 2],
+    pub commitments: [[u8; 32]; 2],
+    pub root: [u8; 32],
+    pub root_index: u64,
+    /// The tree the outputs went to, the leaf indices and the root are the ones of this tree.
+    pub tree_index: u32,
 }
 
+pub const MAX_DENOMINATIONS: usize = 8;
+
 #[derive(Accounts)]
 #[instruction(proof: Proof, ext_data: ExtData)]
 pub struct Transact<'info> {
+    /// The shard the outputs are appended to, picked by the client. It must be one of the
+    /// active trees of `tree_set`.
     #[account(
         mut,
-        seeds = [b"merkle_tree", authority.key().as_ref()],
-        bump = tree_account.load()?.bump,
         has_one = authority @ ErrorCode::Unauthorized
     )]
     pub tree_account: AccountLoader<'info, MerkleTreeAccount>,
-    
-    /// Nullifier account to mark the first input as spent.
-    /// Using `init` without `init_if_needed` ensures that the transaction
-    /// will automatically fail with a system program error if this nullifier
-    /// has already been used (i.e., if the account already exists).
+
+    /// The tree whose root history holds `proof.root`, when it's not `tree_account`.
+    /// This lets notes of any shard or rolled over tree be spent into the chosen shard.
+    /// Both inputs of a proof share one root, so they have to come from the same tree.
     #[account(
-        init,
-        payer = signer,
-        space = 8 + std::mem::size_of::<NullifierAccount>(),
-        seeds = [b"nullifier0", proof.input_nullifiers[0].as_ref()],
-        bump
+        has_one = authority @ ErrorCode::Unauthorized
     )]
-    pub nullifier0: Account<'info, NullifierAccount>,
-    
-    /// Nullifier account to mark the second input as spent.
-    /// Using `init` without `init_if_needed` ensures that the transaction
-    /// will automatically fail with a system program error if this nullifier
-    /// has already been used (i.e., if the account already exists).
+    pub root_tree_account: Option<AccountLoader<'info, MerkleTreeAccount>>,
+
+    /// The insertion queue of `tree_account`. It must be passed exactly when the tree has one.
     #[account(
-        init,
-        payer = signer,
-        space = 8 + std::mem::size_of::<NullifierAccount>(),
+        mut,
+        has_one = tree_account @ ErrorCode::InvalidCommitmentQueue
+    )]
+    pub commitment_queue: Option<AccountLoader<'info, CommitmentQueueAccount>>,
+
+    /// The tree set of the pool, required unless `tree_account` is tree 0, see
+    /// `TreeSet::accepts_outputs`.
+    #[account(
+        seeds = [b"tree_set", authority.key().as_ref()],
+        bump = tree_set.bump
+    )]
+    pub tree_set: Option<Account<'info, TreeSetAccount>>,
+
+    /// The policy of the pool, shared by all of its trees.
+    #[account(
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump = pool_config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+    
+    /// CHECK: Nullifier account to mark the first input as spent. It is created in
+    /// `transact` after checking that it doesn't exist yet, so reusing a nullifier fails
+    /// with `NullifierAlreadySpent`.
+    #[account(
+        mut,
+        seeds = [b"nullifier0", proof.input_nullifiers[0].as_ref()],
+        bump
+    )]
+    pub nullifier0: UncheckedAccount<'info>,
+    
+    /// CHECK: Nullifier account to mark the second input as spent, created like `nullifier0`.
+    #[account(
+        mut,
         seeds = [b"nullifier1", proof.input_nullifiers[1].as_ref()],
         bump
     )]
-    pub nullifier1: Account<'info, NullifierAccount>,
+    pub nullifier1: UncheckedAccount<'info>,
+
+    /// CHECK: Where the second nullifier would be recorded if it had been spent as the first
+    /// input. Only read, a note is spent if its nullifier exists in either slot.
+    #[account(
+        seeds = [b"nullifier0", proof.input_nullifiers[1].as_ref()],
+        bump
+    )]
+    pub nullifier2: UncheckedAccount<'info>,
+
+    /// CHECK: Where the first nullifier would be recorded if it had been spent as the second
+    /// input, checked like `nullifier2`.
+    #[account(
+        seeds = [b"nullifier1", proof.input_nullifiers[0].as_ref()],
+        bump
+    )]
+    pub nullifier3: UncheckedAccount<'info>,
     
+    /// CHECK: Commitment account of the first output, created in `transact` after checking
+    /// that it doesn't exist yet.
     #[account(
-        init,
-        payer = signer,
-        space = 8 + std::mem::size_of::<CommitmentAccount>() + ext_data.encrypted_output1.len(),
+        mut,
         seeds = [b"commitment0", proof.output_commitments[0].as_ref()],
         bump
     )]
-    pub commitment0: Account<'info, CommitmentAccount>,
+    pub commitment0: UncheckedAccount<'info>,
     
+    /// CHECK: Commitment account of the second output, created like `commitment0`.
     #[account(
-        init,
-        payer = signer,
-        space = 8 + std::mem::size_of::<CommitmentAccount>() + ext_data.encrypted_output2.len(),
+        mut,
         seeds = [b"commitment1", proof.output_commitments[1].as_ref()],
         bump
     )]
-    pub commitment1: Account<'info, CommitmentAccount>,
+    pub commitment1: UncheckedAccount<'info>,
+
+    /// CHECK: Where the first output would be recorded if it had been created as the second
+    /// output. Only read, a commitment exists if its account exists in either slot.
+    #[account(
+        seeds = [b"commitment1", proof.output_commitments[0].as_ref()],
+        bump
+    )]
+    pub commitment2: UncheckedAccount<'info>,
+
+    /// CHECK: Where the second output would be recorded if it had been created as the first
+    /// output, checked like `commitment2`.
+    #[account(
+        seeds = [b"commitment0", proof.output_commitments[1].as_ref()],
+        bump
+    )]
+    pub commitment3: UncheckedAccount<'info>,
     
+    /// The vault shared by every tree of the pool.
     #[account(
         mut,
         seeds = [b"tree_token", authority.key().as_ref()],
@@ -264,20 +1082,157 @@ pub struct Transact<'info> {
     )]
     pub tree_token_account: Account<'info, TreeTokenAccount>,
     
-    #[account(mut)]
-    pub recipient: SystemAccount<'info>,
+    /// CHECK: Any writable, non-executable account can receive a withdrawal, including
+    /// program-owned vaults. Lamports are credited in `utils::transfer_lamports_from_pda`.
+    /// It must be the recipient committed in the ext data.
+    #[account(
+        mut,
+        constraint = recipient.key() == ext_data.recipient @ ErrorCode::RecipientMismatch
+    )]
+    pub recipient: UncheckedAccount<'info>,
     
+    /// CHECK: Same rules as `recipient`, the fee recipient can be owned by any program.
     #[account(mut)]
-    pub fee_recipient_account: SystemAccount<'info>,
+    pub fee_recipient_account: UncheckedAccount<'info>,
     
     /// The authority account is the account that created the tree and fee recipient PDAs
     pub authority: SystemAccount<'info>,
     
-    /// The account that is signing the transaction
+    /// The account that is signing the transaction. This can be a PDA signing through
+    /// `invoke_signed` when another program deposits from its own vault.
     #[account(mut)]
     pub signer: Signer<'info>,
     
     pub system_program: Program<'info, System>,
+
+    /// The association set named in `ext_data.association`, only needed with an association proof.
+    pub association_set: Option<Box<Account<'info, AssociationSetAccount>>>,
+
+    /// The verifying key of the association circuit for this pool, only needed with an
+    /// association proof.
+    #[account(
+        seeds = [b"association_verifying_key", authority.key().as_ref()],
+        bump = association_verifying_key.bump
+    )]
+    pub association_verifying_key: Option<Box<Account<'info, AssociationVerifyingKeyAccount>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(proof: Proof, ext_data: ExtData)]
+pub struct CheckTransact<'info> {
+    /// The shard the outputs would be appended to, like in `Transact`.
+    #[account(
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub tree_account: AccountLoader<'info, MerkleTreeAccount>,
+
+    /// The tree whose root history holds `proof.root`, when it's not `tree_account`.
+    #[account(
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub root_tree_account: Option<AccountLoader<'info, MerkleTreeAccount>>,
+
+    /// The insertion queue of `tree_account`, if the tree has one.
+    #[account(
+        has_one = tree_account @ ErrorCode::InvalidCommitmentQueue
+    )]
+    pub commitment_queue: Option<AccountLoader<'info, CommitmentQueueAccount>>,
+
+    /// The tree set of the pool, like in `Transact`.
+    #[account(
+        seeds = [b"tree_set", authority.key().as_ref()],
+        bump = tree_set.bump
+    )]
+    pub tree_set: Option<Account<'info, TreeSetAccount>>,
+
+    #[account(
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump = pool_config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+
+    /// CHECK: Only read to see whether the first nullifier has been spent.
+    #[account(
+        seeds = [b"nullifier0", proof.input_nullifiers[0].as_ref()],
+        bump
+    )]
+    pub nullifier0: UncheckedAccount<'info>,
+
+    /// CHECK: Only read to see whether the second nullifier has been spent.
+    #[account(
+        seeds = [b"nullifier1", proof.input_nullifiers[1].as_ref()],
+        bump
+    )]
+    pub nullifier1: UncheckedAccount<'info>,
+
+    /// CHECK: Only read to see whether the second nullifier has been spent as a first input.
+    #[account(
+        seeds = [b"nullifier0", proof.input_nullifiers[1].as_ref()],
+        bump
+    )]
+    pub nullifier2: UncheckedAccount<'info>,
+
+    /// CHECK: Only read to see whether the first nullifier has been spent as a second input.
+    #[account(
+        seeds = [b"nullifier1", proof.input_nullifiers[0].as_ref()],
+        bump
+    )]
+    pub nullifier3: UncheckedAccount<'info>,
+
+    /// CHECK: Only read to see whether the first output already exists.
+    #[account(
+        seeds = [b"commitment0", proof.output_commitments[0].as_ref()],
+        bump
+    )]
+    pub commitment0: UncheckedAccount<'info>,
+
+    /// CHECK: Only read to see whether the second output already exists.
+    #[account(
+        seeds = [b"commitment1", proof.output_commitments[1].as_ref()],
+        bump
+    )]
+    pub commitment1: UncheckedAccount<'info>,
+
+    /// CHECK: Only read to see whether the first output already exists as a second output.
+    #[account(
+        seeds = [b"commitment1", proof.output_commitments[0].as_ref()],
+        bump
+    )]
+    pub commitment2: UncheckedAccount<'info>,
+
+    /// CHECK: Only read to see whether the second output already exists as a first output.
+    #[account(
+        seeds = [b"commitment0", proof.output_commitments[1].as_ref()],
+        bump
+    )]
+    pub commitment3: UncheckedAccount<'info>,
+
+    /// The vault shared by every tree of the pool.
+    #[account(
+        seeds = [b"tree_token", authority.key().as_ref()],
+        bump = tree_token_account.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub tree_token_account: Account<'info, TreeTokenAccount>,
+
+    /// CHECK: Only read, checked like the `recipient` of `transact`.
+    pub recipient: UncheckedAccount<'info>,
+
+    /// CHECK: Only read, checked like the `fee_recipient_account` of `transact`.
+    pub fee_recipient_account: UncheckedAccount<'info>,
+
+    pub authority: SystemAccount<'info>,
+
+    /// Like in `Transact`.
+    pub association_set: Option<Box<Account<'info, AssociationSetAccount>>>,
+
+    /// Like in `Transact`.
+    #[account(
+        seeds = [b"association_verifying_key", authority.key().as_ref()],
+        bump = association_verifying_key.bump
+    )]
+    pub association_verifying_key: Option<Box<Account<'info, AssociationVerifyingKeyAccount>>>,
 }
 
 #[derive(Accounts)]
@@ -299,6 +1254,15 @@ pub struct Initialize<'info> {
         bump
     )]
     pub tree_token_account: Account<'info, TreeTokenAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + std::mem::size_of::<PoolConfigAccount>(),
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
     
     #[account(mut)]
     pub authority: Signer<'info>,
@@ -307,11 +1271,163 @@ pub struct Initialize<'info> {
 }
 
 #[derive(Accounts)]
-pub struct UpdateDepositLimit<'info> {
+pub struct InitializePoolConfig<'info> {
+    /// Tree 0 of the pool, so only the authority of an existing pool can create its config.
+    #[account(
+        seeds = [b"merkle_tree", authority.key().as_ref()],
+        bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub tree_account: AccountLoader<'info, MerkleTreeAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + std::mem::size_of::<PoolConfigAccount>(),
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateTreeAccount<'info> {
+    /// CHECK: a tree in the old layout can't be loaded, the handler checks its size,
+    /// discriminator and authority. Only tree 0 predates the layout version.
     #[account(
         mut,
+        owner = crate::ID,
         seeds = [b"merkle_tree", authority.key().as_ref()],
-        bump = tree_account.load()?.bump,
+        bump
+    )]
+    pub tree_account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(tree_index: u32)]
+pub struct RolloverTree<'info> {
+    /// Created on the first rollover or shard, with the tree from `initialize` as tree 0.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + std::mem::size_of::<TreeSetAccount>(),
+        seeds = [b"tree_set", authority.key().as_ref()],
+        bump
+    )]
+    pub tree_set: Account<'info, TreeSetAccount>,
+
+    #[account(
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub full_tree_account: AccountLoader<'info, MerkleTreeAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<MerkleTreeAccount>(),
+        seeds = [b"merkle_tree", authority.key().as_ref(), &tree_index.to_le_bytes()],
+        bump
+    )]
+    pub new_tree_account: AccountLoader<'info, MerkleTreeAccount>,
+
+    pub authority: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(tree_index: u32)]
+pub struct AddTreeShard<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + std::mem::size_of::<TreeSetAccount>(),
+        seeds = [b"tree_set", authority.key().as_ref()],
+        bump
+    )]
+    pub tree_set: Account<'info, TreeSetAccount>,
+
+    /// Any tree of the set, the new shard copies its deposit limit and root settings.
+    #[account(
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub tree_account: AccountLoader<'info, MerkleTreeAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + std::mem::size_of::<MerkleTreeAccount>(),
+        seeds = [b"merkle_tree", authority.key().as_ref(), &tree_index.to_le_bytes()],
+        bump
+    )]
+    pub new_tree_account: AccountLoader<'info, MerkleTreeAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeCommitmentQueue<'info> {
+    #[account(
+        mut,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub tree_account: AccountLoader<'info, MerkleTreeAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + std::mem::size_of::<CommitmentQueueAccount>(),
+        seeds = [b"commitment_queue", tree_account.key().as_ref()],
+        bump
+    )]
+    pub commitment_queue: AccountLoader<'info, CommitmentQueueAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessQueue<'info> {
+    #[account(mut)]
+    pub tree_account: AccountLoader<'info, MerkleTreeAccount>,
+
+    #[account(
+        mut,
+        has_one = tree_account @ ErrorCode::InvalidCommitmentQueue
+    )]
+    pub commitment_queue: AccountLoader<'info, CommitmentQueueAccount>,
+
+    /// The policy of the tree's pool, its maturity delay sets how often roots are checkpointed.
+    #[account(
+        seeds = [b"pool_config", tree_account.load()?.authority.as_ref()],
+        bump = pool_config.bump
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDepositLimit<'info> {
+    /// Any tree of the authority's tree set, usually the active one.
+    #[account(
+        mut,
         has_one = authority @ ErrorCode::Unauthorized
     )]
     pub tree_account: AccountLoader<'info, MerkleTreeAccount>,
@@ -320,12 +1436,297 @@ pub struct UpdateDepositLimit<'info> {
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateMaxRootAge<'info> {
+    #[account(
+        mut,
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump = pool_config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAuditorKey<'info> {
+    #[account(
+        mut,
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump = pool_config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDenominations<'info> {
+    #[account(
+        mut,
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump = pool_config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMaturityDelay<'info> {
+    #[account(
+        mut,
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump = pool_config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterAccount<'info> {
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + std::mem::size_of::<ShieldedAccount>(),
+        seeds = [b"shielded_account", owner.key().as_ref()],
+        bump
+    )]
+    pub shielded_account: Account<'info, ShieldedAccount>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DeleteAccount<'info> {
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"shielded_account", owner.key().as_ref()],
+        bump = shielded_account.bump,
+        has_one = owner @ ErrorCode::Unauthorized
+    )]
+    pub shielded_account: Account<'info, ShieldedAccount>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetAssociationVerifyingKey<'info> {
+    /// Created here, so the key can't be replaced once it is set.
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + std::mem::size_of::<AssociationVerifyingKeyAccount>(),
+        seeds = [b"association_verifying_key", authority.key().as_ref()],
+        bump
+    )]
+    pub association_verifying_key: Account<'info, AssociationVerifyingKeyAccount>,
+
+    /// Only the authority of an initialized pool can set its key.
+    #[account(
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump = pool_config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateAssociationSet<'info> {
+    #[account(
+        init,
+        payer = provider,
+        space = 8 + std::mem::size_of::<AssociationSetAccount>(),
+        seeds = [b"association_set", provider.key().as_ref()],
+        bump
+    )]
+    pub association_set: Account<'info, AssociationSetAccount>,
+
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAssociationRoot<'info> {
+    #[account(
+        mut,
+        seeds = [b"association_set", provider.key().as_ref()],
+        bump = association_set.bump,
+        has_one = provider @ ErrorCode::Unauthorized
+    )]
+    pub association_set: Account<'info, AssociationSetAccount>,
+
+    pub provider: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetBatchDepositVerifyingKey<'info> {
+    /// Created here, so the key can't be replaced once it is set.
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + std::mem::size_of::<BatchDepositVerifyingKeyAccount>(),
+        seeds = [b"batch_deposit_verifying_key", authority.key().as_ref()],
+        bump
+    )]
+    pub batch_deposit_verifying_key: Account<'info, BatchDepositVerifyingKeyAccount>,
+
+    /// Only the authority of an initialized pool can set its key.
+    #[account(
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump = pool_config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddBatchOutputs<'info> {
+    /// Sized for a full batch when created, so outputs can be added without a realloc.
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = BatchDepositAccount::SPACE,
+        seeds = [b"batch_deposit", authority.key().as_ref(), signer.key().as_ref()],
+        bump
+    )]
+    pub batch_deposit: Account<'info, BatchDepositAccount>,
+
+    /// CHECK: The pool the batch is deposited into, only used as a seed.
+    pub authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelBatchDeposit<'info> {
+    #[account(
+        mut,
+        close = signer,
+        seeds = [b"batch_deposit", authority.key().as_ref(), signer.key().as_ref()],
+        bump = batch_deposit.bump
+    )]
+    pub batch_deposit: Account<'info, BatchDepositAccount>,
+
+    /// CHECK: The pool the batch was for, only used as a seed.
+    pub authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BatchDeposit<'info> {
+    /// The shard the outputs are appended to, like in `Transact`.
+    #[account(
+        mut,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub tree_account: AccountLoader<'info, MerkleTreeAccount>,
+
+    /// The insertion queue of `tree_account`. It must be passed exactly when the tree has one.
+    #[account(
+        mut,
+        has_one = tree_account @ ErrorCode::InvalidCommitmentQueue
+    )]
+    pub commitment_queue: Option<AccountLoader<'info, CommitmentQueueAccount>>,
+
+    /// The tree set of the pool, required unless `tree_account` is tree 0.
+    #[account(
+        seeds = [b"tree_set", authority.key().as_ref()],
+        bump = tree_set.bump
+    )]
+    pub tree_set: Option<Account<'info, TreeSetAccount>>,
+
+    #[account(
+        seeds = [b"pool_config", authority.key().as_ref()],
+        bump = pool_config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub pool_config: Account<'info, PoolConfigAccount>,
+
+    /// The outputs to deposit, closed once they are in the tree.
+    #[account(
+        mut,
+        close = signer,
+        seeds = [b"batch_deposit", authority.key().as_ref(), signer.key().as_ref()],
+        bump = batch_deposit.bump
+    )]
+    pub batch_deposit: Box<Account<'info, BatchDepositAccount>>,
+
+    #[account(
+        seeds = [b"batch_deposit_verifying_key", authority.key().as_ref()],
+        bump = batch_deposit_verifying_key.bump
+    )]
+    pub batch_deposit_verifying_key: Box<Account<'info, BatchDepositVerifyingKeyAccount>>,
+
+    /// The vault shared by every tree of the pool.
+    #[account(
+        mut,
+        seeds = [b"tree_token", authority.key().as_ref()],
+        bump = tree_token_account.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub tree_token_account: Account<'info, TreeTokenAccount>,
+
+    pub authority: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
 #[account]
 pub struct TreeTokenAccount {
     pub authority: Pubkey,
     pub bump: u8,
 }
 
+/// The policy of a pool. Every tree of the pool reads it, so an update applies to all
+/// shards at once.
+#[account]
+#[derive(Default)]
+pub struct PoolConfigAccount {
+    pub authority: Pubkey,
+    /// How many slots a root stays valid, 0 for no age limit.
+    pub max_root_age: u64,
+    /// How many slots old a root must be to withdraw or pay a fee with it, 0 for no delay.
+    pub maturity_delay: u64,
+    /// X25519 public key that every output must also be encrypted to, zero for no auditor.
+    /// Advisory, the ciphertexts aren't proven to match the outputs.
+    pub auditor_key: [u8; 32],
+    /// The only amounts that can be deposited or withdrawn, in increasing order and padded
+    /// with zeros. All zeros for any amount.
+    pub denominations: [u64; MAX_DENOMINATIONS],
+    pub bump: u8,
+}
+
 #[account]
 pub struct NullifierAccount {
     /// This account's existence indicates that the nullifier has been used.
@@ -339,6 +1740,76 @@ pub struct CommitmentAccount {
     pub encrypted_output: Vec<u8>,
     pub index: u64,
     pub bump: u8,
+    /// The output encrypted for the pool's auditor, empty if the pool had none.
+    pub auditor_output: Vec<u8>,
+    /// The view tag of `encrypted_output`, `None` for outputs in the legacy format.
+    pub view_tag: Option<u8>,
+    /// The tree `index` is in, see `TreeSetAccount`.
+    pub tree_index: u32,
+}
+
+/// Tracks the trees of a pool. Tree 0 is the tree created by `initialize`, tree `i > 0`
+/// lives at the `[b"merkle_tree", authority, i]` PDA.
+#[account]
+pub struct TreeSetAccount {
+    pub authority: Pubkey,
+    /// The shards that take new outputs, clients pick one of them for their outputs.
+    pub active_trees: [u32; MAX_TREE_SHARDS],
+    pub shard_count: u32,
+    pub tree_count: u32,
+    pub bump: u8,
+}
+
+/// The shielded keys of a wallet, so notes can be sent to a Solana address.
+#[account]
+pub struct ShieldedAccount {
+    pub owner: Pubkey,
+    /// Poseidon public key that owns the notes sent to this wallet (big-endian).
+    pub shielded_pubkey: [u8; 32],
+    /// X25519 public key the notes are encrypted to.
+    pub encryption_key: [u8; 32],
+    pub bump: u8,
+}
+
+/// Roots of approved note commitments published by an association-set provider.
+#[account]
+pub struct AssociationSetAccount {
+    pub provider: Pubkey,
+    pub root_history: [[u8; 32]; ASSOCIATION_ROOT_HISTORY_SIZE],
+    /// Position of the current root in `root_history`.
+    pub root_index: u64,
+    /// Number of roots published so far.
+    pub root_count: u64,
+    pub bump: u8,
+}
+
+/// The verifying key association proofs of a pool are checked against.
+#[account]
+pub struct AssociationVerifyingKeyAccount {
+    pub authority: Pubkey,
+    pub verifying_key: AssociationVerifyingKey,
+    pub bump: u8,
+}
+
+/// The verifying key batch deposit proofs of a pool are checked against.
+#[account]
+pub struct BatchDepositVerifyingKeyAccount {
+    pub authority: Pubkey,
+    pub verifying_key: BatchDepositVerifyingKey,
+    pub bump: u8,
+}
+
+/// The outputs of a batch deposit collected so far, see `PendingBatch`.
+#[account]
+pub struct BatchDepositAccount {
+    pub signer: Pubkey,
+    pub outputs: Vec<BatchOutput>,
+    pub bump: u8,
+}
+
+impl BatchDepositAccount {
+    pub const SPACE: usize =
+        8 + 32 + 4 + BATCH_DEPOSIT_OUTPUTS * (32 + 2 * (4 + MAX_BATCH_CIPHERTEXT_LEN)) + 1;
 }
 
 #[account(zero_copy)]
@@ -351,7 +1822,37 @@ pub struct MerkleTreeAccount {
     pub root_index: u64,
     pub max_deposit_amount: u64,
     pub bump: u8,
-    // The pub _padding: [u8; 7] is needed because of the #[account(zero_copy)] attribute.
+    /// 1 once the tree has a commitment queue, so outputs are queued instead of inserted.
+    pub queue_enabled: u8,
+    /// Layout version, see `MERKLE_TREE_ACCOUNT_VERSION`. New fields go in `_reserved`, and
+    /// a layout that has to grow the account bumps it.
+    pub version: u8,
+    // The pub _padding: [u8; 1] is needed because of the #[account(zero_copy)] attribute.
+    pub _padding: [u8; 1],
+    /// Position of this tree in the tree set, 0 for the tree created by `initialize`.
+    pub tree_index: u32,
+    /// The slot each root of `root_history` was created in.
+    pub root_history_slots: [u64; ROOT_HISTORY_SIZE],
+    /// Roots sampled at most once per checkpoint interval, so the mature roots outlive a
+    /// flushed root history. Zero for no checkpoint yet.
+    pub checkpoint_roots: [[u8; 32]; ROOT_CHECKPOINTS],
+    /// The slot each checkpoint root was created in.
+    pub checkpoint_slots: [u64; ROOT_CHECKPOINTS],
+    /// Position of the latest checkpoint.
+    pub checkpoint_index: u64,
+    /// Zeroed space for later fields, so they don't need to grow the account.
+    pub _reserved: [u8; 8],
+}
+
+/// Commitments queued for `tree_account`, a ring buffer drained by `process_queue`.
+#[account(zero_copy)]
+pub struct CommitmentQueueAccount {
+    pub tree_account: Pubkey,
+    /// Position of the oldest queued leaf.
+    pub head: u64,
+    pub len: u64,
+    pub leaves: [[u8; 32]; COMMITMENT_QUEUE_SIZE],
+    pub bump: u8,
     pub _padding: [u8; 7],
 }
 
@@ -381,4 +1882,62 @@ pub enum ErrorCode {
     ArithmeticOverflow,
     #[msg("Deposit limit exceeded")]
     DepositLimitExceeded,
+    #[msg("Recipient can't be the tree token account or an executable account")]
+    InvalidRecipient,
+    #[msg("Transfer would leave a new recipient account below the rent-exempt minimum")]
+    RecipientNotRentExempt,
+    #[msg("Withdraw call requires a withdrawal to the recipient, the target program as the first remaining account and the committed accounts without signers")]
+    InvalidWithdrawCall,
+    #[msg("Nullifier has already been spent")]
+    NullifierAlreadySpent,
+    #[msg("Commitment already exists")]
+    CommitmentAlreadyExists,
+    #[msg("Merkle tree is full")]
+    MerkleTreeFull,
+    #[msg("Merkle tree is not full yet")]
+    MerkleTreeNotFull,
+    #[msg("Tree index doesn't match the tree set, or the tree doesn't take new outputs")]
+    InvalidTreeIndex,
+    #[msg("Tree set already has the maximum number of shards")]
+    TooManyTreeShards,
+    #[msg("Commitment queue is full, it has to be processed first")]
+    CommitmentQueueFull,
+    #[msg("Commitment queue must be passed exactly when the tree has one")]
+    InvalidCommitmentQueue,
+    #[msg("Root is too old, the proof has to be made against a more recent root")]
+    RootExpired,
+    #[msg("Root is too recent, withdrawals have to use a root older than the maturity delay")]
+    RootNotMature,
+    #[msg("Maturity delay must be less than the max root age")]
+    InvalidMaturityDelay,
+    #[msg("Pool has an auditor, every output needs an auditor ciphertext")]
+    MissingAuditorOutputs,
+    #[msg("Shielded pubkey must be a non-zero field element and the encryption key non-zero")]
+    InvalidShieldedAccountKeys,
+    #[msg("Payouts must split a withdrawal into 1 to 8 non-zero amounts, without a withdraw call")]
+    InvalidPayouts,
+    #[msg("Remaining accounts must be the writable payout recipients, in order")]
+    InvalidPayoutAccounts,
+    #[msg("Denominations must be at most 8 non-zero amounts in increasing order")]
+    InvalidDenominations,
+    #[msg("Pool only accepts deposits and withdrawals of its denominations")]
+    InvalidDenomination,
+    #[msg("Tree account isn't in the layout version this instruction expects")]
+    InvalidTreeAccountVersion,
+    #[msg("Recipient account doesn't match the recipient in the ext data")]
+    RecipientMismatch,
+    #[msg("Association root can't be zero")]
+    InvalidAssociationRoot,
+    #[msg("Association proof needs the association set it names and the pool's association verifying key")]
+    InvalidAssociationAccounts,
+    #[msg("Association root is not known in the association set")]
+    UnknownAssociationRoot,
+    #[msg("Association proof is invalid")]
+    InvalidAssociationProof,
+    #[msg("Batch deposit already has the maximum number of outputs")]
+    BatchDepositFull,
+    #[msg("Batch output needs a non-zero commitment below the field size, and ciphertexts of at most 256 bytes")]
+    InvalidBatchOutput,
+    #[msg("Batch deposit has no outputs")]
+    EmptyBatchDeposit,
 }
\ No newline at end of file
@@ -1,10 +1,19 @@
 // Adapted from https://github.com/Lightprotocol/light-protocol/blob/b2a236409bb7797615d217fbf4fff498c852d25e/sparse-merkle-tree/src/merkle_tree.rs
 use light_hasher::Hasher;
-use crate::{MerkleTreeAccount, ErrorCode};
+use crate::{MerkleTreeAccount, PoolConfigAccount, ErrorCode};
 use anchor_lang::prelude::*;
 
 pub const ROOT_HISTORY_SIZE: usize = 100;
 pub const DEFAULT_HEIGHT: usize = 26;
+pub const MAX_LEAVES: u64 = 1 << DEFAULT_HEIGHT;
+/// Number of root checkpoints a tree keeps next to its root history, see `checkpoint_root`.
+pub const ROOT_CHECKPOINTS: usize = 6;
+
+/// Layout version of `MerkleTreeAccount`. Trees created before the layout had a version are
+/// version 0, they end before `root_history_slots` and are grown by `migrate_tree_account`.
+pub const MERKLE_TREE_ACCOUNT_VERSION: u8 = 1;
+/// Size of a version 0 tree account, without the discriminator.
+pub const LEGACY_TREE_ACCOUNT_SIZE: usize = 4128;
 
 pub struct MerkleTree;
 
@@ -12,20 +21,42 @@ impl MerkleTree {
     pub fn initialize<H: Hasher>(tree_account: &mut MerkleTreeAccount) {
         // Initialize empty subtrees
         let zero_bytes = H::zero_bytes();
-        for i in 0..DEFAULT_HEIGHT {
-            tree_account.subtrees[i] = zero_bytes[i];
-        }
+        tree_account.subtrees.copy_from_slice(&zero_bytes[..DEFAULT_HEIGHT]);
 
         // Set initial root
         let initial_root = H::zero_bytes()[DEFAULT_HEIGHT];
         tree_account.root = initial_root;
         tree_account.root_history[0] = initial_root;
+        tree_account.version = MERKLE_TREE_ACCOUNT_VERSION;
+    }
+
+    /// Checks that `data` is a version 0 tree account of `authority`, which can't be loaded
+    /// with the current layout.
+    pub fn check_legacy_account(data: &[u8], authority: &Pubkey) -> Result<()> {
+        require!(
+            data.len() == 8 + LEGACY_TREE_ACCOUNT_SIZE && data.starts_with(MerkleTreeAccount::DISCRIMINATOR),
+            ErrorCode::InvalidTreeAccountVersion
+        );
+        require!(data[8..40] == authority.to_bytes(), ErrorCode::Unauthorized);
+        Ok(())
+    }
+
+    /// Upgrades a version 0 tree, once its account has been grown to the current layout. Its
+    /// roots have no creation slot, so they are all recorded at `slot`: they count as new for
+    /// the maturity delay and the max root age, which only delays withdrawals.
+    pub fn migrate(tree_account: &mut MerkleTreeAccount, slot: u64) -> Result<()> {
+        require!(tree_account.version == 0, ErrorCode::InvalidTreeAccountVersion);
+        tree_account.root_history_slots = [slot; ROOT_HISTORY_SIZE];
+        tree_account.version = MERKLE_TREE_ACCOUNT_VERSION;
+        Ok(())
     }
 
     pub fn append<H: Hasher>(
         leaf: [u8; 32],
         tree_account: &mut MerkleTreeAccount,
     ) -> Result<[[u8; 32]; DEFAULT_HEIGHT]> {
+        require!(!Self::is_full(tree_account), ErrorCode::MerkleTreeFull);
+
         let mut current_index = tree_account.next_index as usize;
         let mut current_level_hash = leaf;
         let mut left;
@@ -65,9 +96,168 @@ impl MerkleTree {
         Ok(proof)
     }
 
+    /// Appends a leaf and records `slot` as the slot its root was created in. `transact` and
+    /// `process_queue` use this so `is_valid_root` can expire roots by age.
+    pub fn append_at_slot<H: Hasher>(
+        leaf: [u8; 32],
+        tree_account: &mut MerkleTreeAccount,
+        slot: u64,
+    ) -> Result<[[u8; 32]; DEFAULT_HEIGHT]> {
+        let proof = Self::append::<H>(leaf, tree_account)?;
+        tree_account.root_history_slots[tree_account.root_index as usize] = slot;
+        Ok(proof)
+    }
+
+    /// Appends `leaves` in one update and records a single root for them, at `slot`. Nodes
+    /// shared by the new leaves are hashed once, so this takes about `2 * leaves.len() + height`
+    /// hashes instead of `leaves.len() * height`. Returns the index of the first leaf.
+    pub fn append_batch<H: Hasher>(
+        leaves: &[[u8; 32]],
+        tree_account: &mut MerkleTreeAccount,
+        slot: u64,
+    ) -> Result<u64> {
+        let first_index = tree_account.next_index;
+        let next_index = first_index
+            .checked_add(leaves.len() as u64)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        require!(next_index <= MAX_LEAVES, ErrorCode::MerkleTreeFull);
+        if leaves.is_empty() {
+            return Ok(first_index);
+        }
+
+        // The new nodes of each level, from `start` to `start + nodes.len() - 1`
+        let zero_bytes = H::zero_bytes();
+        let mut nodes = leaves.to_vec();
+        let mut start = first_index;
+        for (subtree, zero_byte) in tree_account.subtrees.iter_mut().zip(zero_bytes.iter()) {
+            let end = start + nodes.len() as u64 - 1;
+            // The left sibling of an odd first node was filled before this batch
+            let left_sibling = *subtree;
+
+            // The subtree keeps the latest left node, as `append` leaves it
+            if end % 2 == 0 {
+                *subtree = nodes[(end - start) as usize];
+            } else if end > start {
+                *subtree = nodes[(end - 1 - start) as usize];
+            }
+
+            nodes = (start / 2..=end / 2)
+                .map(|parent| {
+                    let (left, right) = (2 * parent, 2 * parent + 1);
+                    let left = if left < start { left_sibling } else { nodes[(left - start) as usize] };
+                    let right = if right <= end { nodes[(right - start) as usize] } else { *zero_byte };
+                    H::hashv(&[&left, &right]).unwrap()
+                })
+                .collect();
+            start /= 2;
+        }
+
+        let root = nodes[0];
+        tree_account.root = root;
+        tree_account.next_index = next_index;
+        let new_root_index = (tree_account.root_index as usize + 1) % ROOT_HISTORY_SIZE;
+        tree_account.root_index = new_root_index as u64;
+        tree_account.root_history[new_root_index] = root;
+        tree_account.root_history_slots[new_root_index] = slot;
+
+        Ok(first_index)
+    }
+
+    /// A full tree can't take more leaves, new outputs go to the next tree of the tree set.
+    pub fn is_full(tree_account: &MerkleTreeAccount) -> bool {
+        tree_account.next_index >= MAX_LEAVES
+    }
+
+    /// Slots between two checkpoints. The oldest of `ROOT_CHECKPOINTS` checkpoints is then at
+    /// least the maturity delay plus an interval older than the latest, so while the tree keeps
+    /// getting roots one of the checkpoints is always mature.
+    pub fn checkpoint_interval(maturity_delay: u64) -> u64 {
+        maturity_delay.div_ceil(ROOT_CHECKPOINTS as u64 - 2).max(1)
+    }
+
+    /// Keeps the current root as a checkpoint if the latest checkpoint is at least
+    /// `checkpoint_interval` slots older. Checkpoints are sampled by slot rather than by append,
+    /// so a burst of transactions can flush the root history but not the mature roots that
+    /// withdrawals rely on. Called after every append that records its slot.
+    pub fn checkpoint_root(tree_account: &mut MerkleTreeAccount, maturity_delay: u64) {
+        let slot = tree_account.root_history_slots[tree_account.root_index as usize];
+        let latest = tree_account.checkpoint_index as usize;
+        if tree_account.checkpoint_roots[latest] != [0u8; 32]
+            && slot < tree_account.checkpoint_slots[latest].saturating_add(Self::checkpoint_interval(maturity_delay))
+        {
+            return;
+        }
+
+        let next = (latest + 1) % ROOT_CHECKPOINTS;
+        tree_account.checkpoint_index = next as u64;
+        tree_account.checkpoint_roots[next] = tree_account.root;
+        tree_account.checkpoint_slots[next] = slot;
+    }
+
     pub fn is_known_root(tree_account: &MerkleTreeAccount, root: [u8; 32]) -> bool {
-        if root == [0u8; 32] {
+        Self::root_slot(tree_account, root).is_some()
+    }
+
+    /// A root is valid while it's in the root history or among the checkpoints, which caps the
+    /// number of valid roots, and, if the pool has a `max_root_age`, while it's at most that
+    /// many slots old.
+    pub fn is_valid_root(tree_account: &MerkleTreeAccount, root: [u8; 32], current_slot: u64, max_root_age: u64) -> bool {
+        let Some(slot) = Self::root_slot(tree_account, root) else {
             return false;
+        };
+
+        max_root_age == 0 || current_slot.saturating_sub(slot) <= max_root_age
+    }
+
+    /// A root is mature once it's at least `maturity_delay` slots old. It only contains notes
+    /// inserted before it was created, so spending against it enforces the delay on them.
+    pub fn is_mature_root(tree_account: &MerkleTreeAccount, root: [u8; 32], current_slot: u64, maturity_delay: u64) -> bool {
+        let Some(slot) = Self::root_slot(tree_account, root) else {
+            return false;
+        };
+
+        current_slot.saturating_sub(slot) >= maturity_delay
+    }
+
+    /// The slot `root` was created in, if it's still in the root history or a checkpoint.
+    fn root_slot(tree_account: &MerkleTreeAccount, root: [u8; 32]) -> Option<u64> {
+        if let Some(i) = Self::find_root(tree_account, root) {
+            return Some(tree_account.root_history_slots[i]);
+        }
+        if root == [0u8; 32] {
+            return None;
+        }
+
+        tree_account.checkpoint_roots
+            .iter()
+            .position(|checkpoint| *checkpoint == root)
+            .map(|i| tree_account.checkpoint_slots[i])
+    }
+
+    /// Checks `root` against the policy of the pool, a transaction must use a mature root if
+    /// it moves funds out.
+    pub fn check_root(
+        tree_account: &MerkleTreeAccount,
+        pool_config: &PoolConfigAccount,
+        root: [u8; 32],
+        current_slot: u64,
+        moves_funds_out: bool,
+    ) -> Result<()> {
+        require!(Self::is_known_root(tree_account, root), ErrorCode::UnknownRoot);
+        require!(
+            Self::is_valid_root(tree_account, root, current_slot, pool_config.max_root_age),
+            ErrorCode::RootExpired
+        );
+        require!(
+            !moves_funds_out || Self::is_mature_root(tree_account, root, current_slot, pool_config.maturity_delay),
+            ErrorCode::RootNotMature
+        );
+        Ok(())
+    }
+
+    fn find_root(tree_account: &MerkleTreeAccount, root: [u8; 32]) -> Option<usize> {
+        if root == [0u8; 32] {
+            return None;
         }
         
         let current_root_index = tree_account.root_index as usize;
@@ -75,7 +265,7 @@ impl MerkleTree {
         
         loop {
             if root == tree_account.root_history[i] {
-                return true;
+                return Some(i);
             }
             
             if i == 0 {
@@ -89,6 +279,6 @@ impl MerkleTree {
             }
         }
         
-        false
+        None
     }
 } 
\ No newline at end of file
@@ -0,0 +1,247 @@
+use crate::association_set::AssociationSet;
+use crate::commitment_queue::COMMITMENT_QUEUE_SIZE;
+use crate::merkle_tree::{MerkleTree, MAX_LEAVES};
+use crate::tree_set::TreeSet;
+use crate::utils::{
+    check_denomination, check_ext_data_hash, check_payout_accounts, check_payouts, check_public_amount,
+    check_withdraw_call, check_withdraw_call_accounts, existing_output, has_auditor_outputs, is_rent_exempt_transfer,
+    is_valid_recipient, spent_input, verify_association_proof, verify_proof, VERIFYING_KEY,
+};
+use crate::{
+    AssociationSetAccount, AssociationVerifyingKeyAccount, CommitmentQueueAccount, ExtData, MerkleTreeAccount,
+    PoolConfigAccount, Proof, TreeSetAccount,
+};
+use anchor_lang::prelude::*;
+
+/// The outcome of a `transact` preflight. The checks run in the same order as in
+/// `transact`, and the first failing one is reported.
+///
+/// New variants are only appended, so older clients keep decoding the existing ones.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactVerdict {
+    Valid,
+    UnknownRoot,
+    ExtDataHashMismatch,
+    InvalidPublicAmountData,
+    InvalidProof,
+    NullifierAlreadySpent { index: u8 },
+    DepositLimitExceeded,
+    InsufficientFundsForWithdrawal,
+    InsufficientFundsForFee,
+    RootExpired,
+    RootNotMature,
+    MissingAuditorOutputs,
+    InvalidPayouts,
+    InvalidDenomination,
+    CommitmentAlreadyExists { index: u8 },
+    InvalidWithdrawCall,
+    InvalidPayoutAccounts,
+    InvalidRecipient,
+    RecipientNotRentExempt,
+    InvalidCommitmentQueue,
+    CommitmentQueueFull,
+    MerkleTreeFull,
+    RecipientMismatch,
+    InvalidTreeIndex,
+    InvalidAssociationAccounts,
+    UnknownAssociationRoot,
+    InvalidAssociationProof,
+}
+
+/// The accounts `transact` would run against, as passed to `check_transact`.
+pub struct TransactState<'a, 'info> {
+    /// The shard the outputs are appended to.
+    pub tree_account: &'a MerkleTreeAccount,
+    /// The tree holding `proof.root`, when it's not `tree_account`.
+    pub root_tree_account: Option<&'a MerkleTreeAccount>,
+    /// The commitment queue passed for `tree_account`, if any.
+    pub commitment_queue: Option<&'a CommitmentQueueAccount>,
+    /// The tree set passed to `check_transact`, if any.
+    pub tree_set: Option<&'a TreeSetAccount>,
+    pub pool_config: &'a PoolConfigAccount,
+    /// For each input, whether its nullifier PDA already exists as input 0 and as input 1.
+    pub nullifiers_spent: [[bool; 2]; 2],
+    /// For each output, whether its commitment PDA already exists as output 0 and as output 1.
+    pub commitments_exist: [[bool; 2]; 2],
+    pub tree_token_account: &'a AccountInfo<'info>,
+    pub recipient: &'a AccountInfo<'info>,
+    pub fee_recipient_account: &'a AccountInfo<'info>,
+    /// The payout recipients, or the target program and accounts of a withdraw call.
+    pub remaining_accounts: &'a [AccountInfo<'info>],
+    /// The association set passed to `check_transact`, if any.
+    pub association_set: Option<&'a AssociationSetAccount>,
+    /// The association verifying key passed to `check_transact`, if any.
+    pub association_verifying_key: Option<&'a AssociationVerifyingKeyAccount>,
+    pub current_slot: u64,
+    pub rent: &'a Rent,
+}
+
+/**
+ * Runs the checks of `transact` without mutating any state.
+ *
+ * @param state The accounts `transact` would run against.
+ * @param proof The proof that would be submitted.
+ * @param ext_data The ext data that would be submitted.
+ * @return The first failing check, or `TransactVerdict::Valid`.
+ */
+pub fn check_transact(state: &TransactState, proof: &Proof, ext_data: &ExtData) -> TransactVerdict {
+    check_transact_with_verifier(state, proof, ext_data, |proof| verify_proof(proof.clone(), VERIFYING_KEY))
+}
+
+/**
+ * `check_transact` with the Groth16 check replaced by `verify`, so the checks that run after
+ * the proof can be tested without a prover.
+ */
+pub fn check_transact_with_verifier(
+    state: &TransactState,
+    proof: &Proof,
+    ext_data: &ExtData,
+    verify: impl Fn(&Proof) -> bool,
+) -> TransactVerdict {
+    // An account constraint in `transact`, so it fails before the handler runs
+    if state.recipient.key() != ext_data.recipient {
+        return TransactVerdict::RecipientMismatch;
+    }
+
+    if let Some(index) = spent_input(state.nullifiers_spent) {
+        return TransactVerdict::NullifierAlreadySpent { index: index as u8 };
+    }
+
+    if let Some(index) = existing_output(state.commitments_exist) {
+        return TransactVerdict::CommitmentAlreadyExists { index: index as u8 };
+    }
+
+    if !TreeSet::accepts_outputs(state.tree_set, state.tree_account.tree_index) {
+        return TransactVerdict::InvalidTreeIndex;
+    }
+
+    let root_tree_account = state.root_tree_account.unwrap_or(state.tree_account);
+    if !MerkleTree::is_known_root(root_tree_account, proof.root) {
+        return TransactVerdict::UnknownRoot;
+    }
+
+    if !MerkleTree::is_valid_root(root_tree_account, proof.root, state.current_slot, state.pool_config.max_root_age) {
+        return TransactVerdict::RootExpired;
+    }
+
+    let moves_funds_out = ext_data.ext_amount < 0 || ext_data.fee > 0;
+    if moves_funds_out
+        && !MerkleTree::is_mature_root(root_tree_account, proof.root, state.current_slot, state.pool_config.maturity_delay)
+    {
+        return TransactVerdict::RootNotMature;
+    }
+
+    if !check_ext_data_hash(ext_data, proof.ext_data_hash) {
+        return TransactVerdict::ExtDataHashMismatch;
+    }
+
+    if !has_auditor_outputs(state.pool_config.auditor_key, ext_data) {
+        return TransactVerdict::MissingAuditorOutputs;
+    }
+
+    if !check_public_amount(ext_data.ext_amount, ext_data.fee, proof.public_amount) {
+        return TransactVerdict::InvalidPublicAmountData;
+    }
+
+    if !check_denomination(&state.pool_config.denominations, ext_data.ext_amount.unsigned_abs()) {
+        return TransactVerdict::InvalidDenomination;
+    }
+
+    if !check_payouts(ext_data) {
+        return TransactVerdict::InvalidPayouts;
+    }
+
+    if !check_withdraw_call(ext_data, &state.recipient.key()) {
+        return TransactVerdict::InvalidWithdrawCall;
+    }
+
+    if !verify(proof) {
+        return TransactVerdict::InvalidProof;
+    }
+
+    if let Some(association) = &ext_data.association {
+        let (Some(association_set), Some(association_verifying_key)) = (state.association_set, state.association_verifying_key)
+        else {
+            return TransactVerdict::InvalidAssociationAccounts;
+        };
+        if AssociationSet::address(association_set) != Some(association.association_set) {
+            return TransactVerdict::InvalidAssociationAccounts;
+        }
+        if !AssociationSet::is_known_root(association_set, association.root) {
+            return TransactVerdict::UnknownAssociationRoot;
+        }
+        if !verify_association_proof(association, &proof.input_nullifiers, &association_verifying_key.verifying_key) {
+            return TransactVerdict::InvalidAssociationProof;
+        }
+    }
+
+    let tree_token_key = state.tree_token_account.key();
+    let mut remaining_balance = state.tree_token_account.lamports();
+    if ext_data.ext_amount > 0 {
+        if ext_data.ext_amount as u64 > state.tree_account.max_deposit_amount {
+            return TransactVerdict::DepositLimitExceeded;
+        }
+        remaining_balance = remaining_balance.saturating_add(ext_data.ext_amount as u64);
+    } else if ext_data.ext_amount < 0 {
+        let amount = ext_data.ext_amount.unsigned_abs();
+        match remaining_balance.checked_sub(amount) {
+            Some(balance) => remaining_balance = balance,
+            None => return TransactVerdict::InsufficientFundsForWithdrawal,
+        }
+
+        let transfers: Vec<(&AccountInfo, u64)> = match &ext_data.payouts {
+            Some(payouts) => {
+                if !check_payout_accounts(payouts, state.remaining_accounts) {
+                    return TransactVerdict::InvalidPayoutAccounts;
+                }
+                state.remaining_accounts.iter().zip(payouts).map(|(recipient, payout)| (recipient, payout.amount)).collect()
+            }
+            None => vec![(state.recipient, amount)],
+        };
+        for (recipient, amount) in transfers {
+            if let Some(verdict) = check_transfer(&tree_token_key, recipient, amount, state.rent) {
+                return verdict;
+            }
+        }
+    }
+
+    if ext_data.fee > 0 {
+        if remaining_balance < ext_data.fee {
+            return TransactVerdict::InsufficientFundsForFee;
+        }
+        if let Some(verdict) = check_transfer(&tree_token_key, state.fee_recipient_account, ext_data.fee, state.rent) {
+            return verdict;
+        }
+    }
+
+    if (state.tree_account.queue_enabled != 0) != state.commitment_queue.is_some() {
+        return TransactVerdict::InvalidCommitmentQueue;
+    }
+
+    // Both outputs need a leaf, queued outputs take the leaves after the queued ones
+    let queued = state.commitment_queue.map_or(0, |queue| queue.len);
+    if queued + 2 > COMMITMENT_QUEUE_SIZE as u64 {
+        return TransactVerdict::CommitmentQueueFull;
+    }
+    if state.tree_account.next_index.saturating_add(queued + 2) > MAX_LEAVES {
+        return TransactVerdict::MerkleTreeFull;
+    }
+
+    if let Some(call) = &ext_data.call {
+        if !check_withdraw_call_accounts(call, state.remaining_accounts) {
+            return TransactVerdict::InvalidWithdrawCall;
+        }
+    }
+
+    TransactVerdict::Valid
+}
+
+fn check_transfer(from: &Pubkey, to: &AccountInfo, amount: u64, rent: &Rent) -> Option<TransactVerdict> {
+    if !is_valid_recipient(from, to) {
+        return Some(TransactVerdict::InvalidRecipient);
+    }
+    if !is_rent_exempt_transfer(to, amount, rent) {
+        return Some(TransactVerdict::RecipientNotRentExempt);
+    }
+    None
+}
@@ -0,0 +1,97 @@
+use crate::merkle_tree::MerkleTree;
+use crate::{ErrorCode, MerkleTreeAccount, TreeSetAccount};
+use anchor_lang::prelude::*;
+use light_hasher::Hasher;
+
+pub const MAX_TREE_SHARDS: usize = 8;
+
+/// The trees of a pool. Tree 0 is the tree created by `initialize`, later trees are created
+/// as extra shards or to replace a full shard. Every tree stays valid for roots, but only the
+/// shards in `active_trees` have room for new outputs.
+pub struct TreeSet;
+
+impl TreeSet {
+    /// A tree set created after `initialize` starts with tree 0 as its only shard.
+    pub fn initialize_if_empty(tree_set: &mut TreeSetAccount, authority: Pubkey, bump: u8) {
+        if tree_set.tree_count > 0 {
+            return;
+        }
+
+        tree_set.authority = authority;
+        tree_set.active_trees = [0; MAX_TREE_SHARDS];
+        tree_set.shard_count = 1;
+        tree_set.tree_count = 1;
+        tree_set.bump = bump;
+    }
+
+    pub fn is_active(tree_set: &TreeSetAccount, tree_index: u32) -> bool {
+        Self::active_trees(tree_set).contains(&tree_index)
+    }
+
+    /// New outputs only go to active trees. A pool without a tree set only has tree 0. Once
+    /// the set exists, tree 0 stays active until it's full, so a transaction into tree 0 can
+    /// leave the set out and the full tree rejects the outputs.
+    pub fn accepts_outputs(tree_set: Option<&TreeSetAccount>, tree_index: u32) -> bool {
+        match tree_set {
+            Some(tree_set) => Self::is_active(tree_set, tree_index),
+            None => tree_index == 0,
+        }
+    }
+
+    pub fn active_trees(tree_set: &TreeSetAccount) -> &[u32] {
+        &tree_set.active_trees[..tree_set.shard_count as usize]
+    }
+
+    /// Adds a new shard and returns its tree index.
+    pub fn add_shard(tree_set: &mut TreeSetAccount) -> Result<u32> {
+        require!(
+            (tree_set.shard_count as usize) < MAX_TREE_SHARDS,
+            ErrorCode::TooManyTreeShards
+        );
+
+        let tree_index = tree_set.tree_count;
+        tree_set.active_trees[tree_set.shard_count as usize] = tree_index;
+        tree_set.shard_count += 1;
+        tree_set.tree_count = tree_set.tree_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        Ok(tree_index)
+    }
+
+    /// Replaces the full shard `full_tree_index` with a new tree and returns its tree index.
+    pub fn rollover(tree_set: &mut TreeSetAccount, full_tree_index: u32) -> Result<u32> {
+        let shard = Self::active_trees(tree_set)
+            .iter()
+            .position(|tree_index| *tree_index == full_tree_index)
+            .ok_or(ErrorCode::InvalidTreeIndex)?;
+
+        let tree_index = tree_set.tree_count;
+        tree_set.active_trees[shard] = tree_index;
+        tree_set.tree_count = tree_set.tree_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        Ok(tree_index)
+    }
+
+    /// Initializes a new tree of the set, with the deposit limit of `source_tree`. The rest of
+    /// the policy lives in the pool config, which every tree shares.
+    pub fn initialize_tree<H: Hasher>(
+        tree_account: &mut MerkleTreeAccount,
+        source_tree: &MerkleTreeAccount,
+        tree_index: u32,
+        bump: u8,
+        slot: u64,
+    ) {
+        tree_account.authority = source_tree.authority;
+        tree_account.next_index = 0;
+        tree_account.root_index = 0;
+        tree_account.bump = bump;
+        tree_account.tree_index = tree_index;
+        tree_account.max_deposit_amount = source_tree.max_deposit_amount;
+
+        MerkleTree::initialize::<H>(tree_account);
+        tree_account.root_history_slots[0] = slot;
+    }
+}
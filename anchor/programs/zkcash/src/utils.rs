@@ -1,11 +1,18 @@
-use crate::Proof;
-use crate::groth16::{Groth16Verifier, Groth16Verifyingkey};
+use crate::{
+    AssociationProof, AssociationVerifyingKey, BatchDepositProof, BatchDepositVerifyingKey, ErrorCode, ExtData, Payout,
+    Proof, WithdrawCall, ASSOCIATION_PUBLIC_INPUTS, BATCH_DEPOSIT_PUBLIC_INPUTS, MAX_DENOMINATIONS, MAX_PAYOUTS,
+};
+use crate::groth16::{is_less_than_bn254_field_size_be, Groth16Verifier, Groth16Verifyingkey};
 use ark_bn254;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
 use std::ops::Neg;
 use ark_bn254::Fr;
 use ark_ff::PrimeField;
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke;
+use anchor_lang::solana_program::hash::hash;
 
 type G1 = ark_bn254::g1::G1Affine;
 
@@ -103,8 +110,9 @@ pub fn check_public_amount(ext_amount: i64, fee: u64, public_amount_bytes: [u8;
         Fr::from(abs_ext_amount as u64)
     };
 
-    // return false if the deposit amount is barely enough to cover the fee
-    if ext_amount >= 0 && ext_amount_fr <= fee_fr {
+    // return false if the deposit amount is barely enough to cover the fee, a transfer inside
+    // the pool (ext_amount 0) pays its fee from the inputs like a withdrawal
+    if ext_amount > 0 && ext_amount_fr <= fee_fr {
         return false;
     }
 
@@ -123,6 +131,400 @@ pub fn check_public_amount(ext_amount: i64, fee: u64, public_amount_bytes: [u8;
     result_public_amount == provided_amount
 }
 
+/**
+ * Checks that the Borsh-serialized ext data hashes to the ext data hash in the proof.
+ *
+ * @param ext_data The ext data submitted with the proof.
+ * @param ext_data_hash The ext data hash public input of the proof (big-endian).
+ * @return Returns `true` if the SHA-256 of the ext data, as a field element, matches.
+ */
+pub fn check_ext_data_hash(ext_data: &ExtData, ext_data_hash: [u8; 32]) -> bool {
+    let mut serialized_ext_data = Vec::new();
+    if ext_data.serialize(&mut serialized_ext_data).is_err() {
+        return false;
+    }
+    let calculated_ext_data_hash = hash(&serialized_ext_data).to_bytes();
+
+    Fr::from_le_bytes_mod_order(&calculated_ext_data_hash) == Fr::from_be_bytes_mod_order(&ext_data_hash)
+}
+
+/**
+ * Finds an input whose nullifier has already been spent. Nullifier PDAs are seeded by the
+ * input slot they were spent in, so a nullifier is spent if either of its PDAs exists.
+ *
+ * @param nullifiers_spent For each input, whether its nullifier PDA exists as input 0 and
+ * as input 1.
+ * @return Returns the first input whose nullifier is spent in any slot, if any.
+ */
+pub fn spent_input(nullifiers_spent: [[bool; 2]; 2]) -> Option<usize> {
+    nullifiers_spent.iter().position(|slots| slots.iter().any(|spent| *spent))
+}
+
+/**
+ * Finds an output whose commitment already exists. Commitment PDAs are seeded by the output
+ * slot they were created in, so a commitment exists if either of its PDAs exists.
+ *
+ * @param commitments_exist For each output, whether its commitment PDA exists as output 0
+ * and as output 1.
+ * @return Returns the first output whose commitment exists in any slot, if any.
+ */
+pub fn existing_output(commitments_exist: [[bool; 2]; 2]) -> Option<usize> {
+    commitments_exist.iter().position(|slots| slots.iter().any(|exists| *exists))
+}
+
+/**
+ * Checks that every output carries a ciphertext for the auditor when the pool has one.
+ *
+ * This is advisory: the program can't decrypt the ciphertexts, and the circuit doesn't
+ * constrain them, so a sender can attach ciphertexts that don't open to the outputs. The
+ * check only stops clients from leaving them out by mistake.
+ *
+ * @param auditor_key The X25519 public key of the pool's auditor, zero if there is none.
+ * @param ext_data The ext data submitted with the proof.
+ * @return Returns `true` if no auditor is set or both auditor ciphertexts are present.
+ */
+pub fn has_auditor_outputs(auditor_key: [u8; 32], ext_data: &ExtData) -> bool {
+    if auditor_key == [0u8; 32] {
+        return true;
+    }
+
+    ext_data.auditor_outputs.as_ref().is_some_and(|outputs| {
+        !outputs.encrypted_output1.is_empty() && !outputs.encrypted_output2.is_empty()
+    })
+}
+
+/**
+ * Checks that an amount entering or leaving the pool is one of its denominations.
+ *
+ * @param denominations The denominations of the tree, padded with zeros.
+ * @param amount The absolute value of `ext_amount`.
+ * @return Returns `true` if the tree has no denominations, the amount is 0 (nothing enters
+ * or leaves the pool), or the amount is one of the denominations.
+ */
+pub fn check_denomination(denominations: &[u64; MAX_DENOMINATIONS], amount: u64) -> bool {
+    amount == 0 || denominations.iter().all(|denomination| *denomination == 0) || denominations.contains(&amount)
+}
+
+/**
+ * Checks a new denomination set before it is stored in a tree.
+ *
+ * @param denominations The new denominations, without padding.
+ * @return Returns `true` if there are at most `MAX_DENOMINATIONS` non-zero denominations in
+ * strictly increasing order.
+ */
+pub fn check_denomination_set(denominations: &[u64]) -> bool {
+    denominations.len() <= MAX_DENOMINATIONS
+        && denominations.first().map_or(true, |smallest| *smallest > 0)
+        && denominations.windows(2).all(|pair| pair[0] < pair[1])
+}
+
+/**
+ * Checks that the payouts of a split withdrawal add up to the withdrawn amount.
+ *
+ * @param ext_data The ext data submitted with the proof.
+ * @return Returns `true` if there are no payouts, or if the ext data is a withdrawal without
+ * a withdraw call and has 1 to `MAX_PAYOUTS` non-zero payouts summing to `|ext_amount|`.
+ */
+pub fn check_payouts(ext_data: &ExtData) -> bool {
+    let Some(payouts) = &ext_data.payouts else {
+        return true;
+    };
+    if ext_data.ext_amount >= 0 || ext_data.call.is_some() || payouts.is_empty() || payouts.len() > MAX_PAYOUTS {
+        return false;
+    }
+
+    let total = payouts.iter().try_fold(0u64, |total, payout| {
+        (payout.amount > 0).then_some(())?;
+        total.checked_add(payout.amount)
+    });
+    total == Some(ext_data.ext_amount.unsigned_abs())
+}
+
+/**
+ * Checks that a withdraw-and-call withdraws into the committed recipient.
+ *
+ * @param ext_data The ext data submitted with the proof.
+ * @param recipient The `recipient` account passed to `transact`.
+ * @return Returns `true` if there is no call, or if the ext data is a withdrawal to
+ * `recipient`.
+ */
+pub fn check_withdraw_call(ext_data: &ExtData, recipient: &Pubkey) -> bool {
+    ext_data.call.is_none() || (ext_data.ext_amount < 0 && *recipient == ext_data.recipient)
+}
+
+/**
+ * Checks the recipients of a split withdrawal against the committed payouts.
+ *
+ * @param payouts The committed payouts.
+ * @param remaining_accounts The accounts passed for the payouts.
+ * @return Returns `true` if the accounts are the payout recipients, writable and in order,
+ * without extra accounts.
+ */
+pub fn check_payout_accounts(payouts: &[Payout], remaining_accounts: &[AccountInfo]) -> bool {
+    remaining_accounts.len() == payouts.len()
+        && payouts
+            .iter()
+            .zip(remaining_accounts)
+            .all(|(payout, recipient)| recipient.key() == payout.recipient && recipient.is_writable)
+}
+
+/**
+ * Pays out a split withdrawal from a program-owned PDA.
+ *
+ * @param from The program-owned account to debit.
+ * @param payouts The committed payouts, already checked with `check_payouts`.
+ * @param remaining_accounts The payout recipients, writable and in the order of `payouts`.
+ * Extra accounts are rejected.
+ * @param rent The rent sysvar used for the rent-exempt check of new recipients.
+ */
+pub fn transfer_payouts(
+    from: &AccountInfo,
+    payouts: &[Payout],
+    remaining_accounts: &[AccountInfo],
+    rent: &Rent,
+) -> Result<()> {
+    require!(check_payout_accounts(payouts, remaining_accounts), ErrorCode::InvalidPayoutAccounts);
+
+    for (payout, recipient) in payouts.iter().zip(remaining_accounts) {
+        transfer_lamports_from_pda(from, recipient, payout.amount, rent)?;
+    }
+
+    Ok(())
+}
+
+/**
+ * Checks the keys of a shielded account before they are published in the registry.
+ *
+ * @param shielded_pubkey The Poseidon public key notes are owned by (big-endian).
+ * @param encryption_key The X25519 public key notes are encrypted to.
+ * @return Returns `true` if the shielded pubkey is a non-zero field element and the
+ * encryption key is non-zero.
+ */
+pub fn check_shielded_account_keys(shielded_pubkey: [u8; 32], encryption_key: [u8; 32]) -> bool {
+    shielded_pubkey != [0u8; 32]
+        && is_less_than_bn254_field_size_be(&shielded_pubkey)
+        && encryption_key != [0u8; 32]
+}
+
+/// Prefix of the envelopes written by the SDK, before their version byte. Legacy outputs
+/// start with a random IV, a single version byte would be mistaken for one in 256 of them.
+pub const ENVELOPE_MAGIC: [u8; 6] = *b"zkcash";
+
+/// Version of the note envelope written by the SDK: magic || version || ephemeral X25519
+/// key || view tag || ciphertext.
+pub const NOTE_CIPHERTEXT_VERSION: u8 = 1;
+pub const VIEW_TAG_OFFSET: usize = ENVELOPE_MAGIC.len() + 1 + 32;
+
+/**
+ * Reads the version of an envelope written by the SDK.
+ *
+ * @param encrypted_output An encrypted output or auditor ciphertext.
+ * @return Returns the version byte after `ENVELOPE_MAGIC`, or `None` for legacy outputs.
+ */
+pub fn envelope_version(encrypted_output: &[u8]) -> Option<u8> {
+    encrypted_output.strip_prefix(&ENVELOPE_MAGIC)?.first().copied()
+}
+
+/**
+ * Reads the view tag of an encrypted output, so scanners can find it in the commitment
+ * account. The program can't check the tag, a wrong one only makes the note hard to find
+ * for its recipient.
+ *
+ * @param encrypted_output The encrypted output submitted in the ext data.
+ * @return Returns the view tag, or `None` if the output isn't a versioned note envelope.
+ */
+pub fn view_tag(encrypted_output: &[u8]) -> Option<u8> {
+    match envelope_version(encrypted_output) {
+        Some(NOTE_CIPHERTEXT_VERSION) => encrypted_output.get(VIEW_TAG_OFFSET).copied(),
+        _ => None,
+    }
+}
+
+/**
+ * Checks that `to` can receive lamports from `from`.
+ *
+ * @return Returns `true` if `to` is not executable and is not `from` itself.
+ */
+pub fn is_valid_recipient(from: &Pubkey, to: &AccountInfo) -> bool {
+    *from != to.key() && !to.executable
+}
+
+/**
+ * Checks that `to` stays rent-exempt after receiving `amount` lamports.
+ *
+ * @return Returns `true` if `to` already exists, or if `amount` covers the rent-exempt
+ * minimum of an account of its size.
+ */
+pub fn is_rent_exempt_transfer(to: &AccountInfo, amount: u64, rent: &Rent) -> bool {
+    to.lamports() > 0 || amount >= rent.minimum_balance(to.data_len())
+}
+
+/**
+ * Moves lamports out of a program-owned PDA into an arbitrary writable account.
+ *
+ * The destination may be owned by any program (multisig vaults, DAO treasuries, token
+ * accounts), since the runtime allows crediting lamports to any writable account.
+ * Executable accounts and the source account itself are rejected, and a destination
+ * that does not exist yet must receive at least the rent-exempt minimum for its size.
+ *
+ * @param from The program-owned account to debit.
+ * @param to The writable account to credit.
+ * @param amount The number of lamports to move.
+ * @param rent The rent sysvar used for the rent-exempt check.
+ */
+pub fn transfer_lamports_from_pda(
+    from: &AccountInfo,
+    to: &AccountInfo,
+    amount: u64,
+    rent: &Rent,
+) -> Result<()> {
+    require!(is_valid_recipient(&from.key(), to), ErrorCode::InvalidRecipient);
+
+    // A recipient with no lamports does not exist yet, so it has to end up rent-exempt.
+    require!(is_rent_exempt_transfer(to, amount, rent), ErrorCode::RecipientNotRentExempt);
+
+    let from_balance = from.lamports();
+    let to_balance = to.lamports();
+
+    let new_from_balance = from_balance.checked_sub(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    let new_to_balance = to_balance.checked_add(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    **from.try_borrow_mut_lamports()? = new_from_balance;
+    **to.try_borrow_mut_lamports()? = new_to_balance;
+
+    Ok(())
+}
+
+/**
+ * Creates a program-owned PDA and writes its initial data, like Anchor's `init` constraint.
+ *
+ * An address that was pre-funded by someone else is topped up to the rent-exempt minimum,
+ * then allocated and assigned, so lamports sent to a future PDA can't block its creation.
+ *
+ * @param account The PDA to create.
+ * @param payer The signer paying for the account.
+ * @param system_program The system program.
+ * @param space The account size, including the 8-byte discriminator.
+ * @param signer_seeds The seeds of the PDA, including its bump.
+ * @param data The initial account data.
+ * @param rent The rent sysvar.
+ */
+pub fn init_pda_account<'info, T: AccountSerialize>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    signer_seeds: &[&[u8]],
+    data: &T,
+    rent: &Rent,
+) -> Result<()> {
+    let signer = &[signer_seeds];
+    let current_lamports = account.lamports();
+
+    if current_lamports == 0 {
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::CreateAccount { from: payer.clone(), to: account.clone() },
+                signer,
+            ),
+            rent.minimum_balance(space),
+            space as u64,
+            &crate::ID,
+        )?;
+    } else {
+        let required_lamports = rent.minimum_balance(space).max(1).saturating_sub(current_lamports);
+        if required_lamports > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    system_program::Transfer { from: payer.clone(), to: account.clone() },
+                ),
+                required_lamports,
+            )?;
+        }
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::Allocate { account_to_allocate: account.clone() },
+                signer,
+            ),
+            space as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::Assign { account_to_assign: account.clone() },
+                signer,
+            ),
+            &crate::ID,
+        )?;
+    }
+
+    let mut account_data = account.try_borrow_mut_data()?;
+    data.try_serialize(&mut &mut account_data[..])?;
+    Ok(())
+}
+
+/**
+ * Checks the remaining accounts of a withdraw-and-call against the committed call.
+ *
+ * @param call The committed target program, accounts and instruction data.
+ * @param remaining_accounts The target program followed by the accounts of its instruction.
+ * @return Returns `true` if the first account is the executable target program, which is not
+ * zkcash, and the rest are the committed accounts, in order, not signing, and writable where
+ * committed.
+ */
+pub fn check_withdraw_call_accounts(call: &WithdrawCall, remaining_accounts: &[AccountInfo]) -> bool {
+    let Some((program, accounts)) = remaining_accounts.split_first() else {
+        return false;
+    };
+
+    program.key() == call.program_id
+        && program.executable
+        && call.program_id != crate::ID
+        && accounts.len() == call.accounts.len()
+        && accounts.iter().zip(&call.accounts).all(|(account, committed)| {
+            account.key() == committed.pubkey
+                && !account.is_signer
+                && (account.is_writable || !committed.is_writable)
+        })
+}
+
+/**
+ * Invokes the instruction committed in `ExtData.call` after a withdrawal.
+ *
+ * The first remaining account must be the target program, and the rest must be the committed
+ * accounts, in order and writable where committed. They are passed to the target instruction
+ * as non-signers, and a remaining account that signed this transaction is rejected, so the
+ * target can't use the relayer's signature. The program never signs for its own PDAs here,
+ * and calling back into zkcash is rejected.
+ *
+ * @param call The committed target program, accounts and instruction data.
+ * @param remaining_accounts The target program followed by the accounts of its instruction.
+ */
+pub fn invoke_withdraw_call(call: &WithdrawCall, remaining_accounts: &[AccountInfo]) -> Result<()> {
+    require!(check_withdraw_call_accounts(call, remaining_accounts), ErrorCode::InvalidWithdrawCall);
+
+    let instruction = Instruction {
+        program_id: call.program_id,
+        accounts: call.accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.pubkey,
+                is_signer: false,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: call.data.clone(),
+    };
+
+    invoke(&instruction, remaining_accounts)?;
+    Ok(())
+}
+
 pub fn verify_proof(proof: Proof, verifying_key: Groth16Verifyingkey) -> bool {
     let mut public_inputs_vec: [[u8; 32]; 7] = [[0u8; 32]; 7];
 
@@ -134,37 +536,111 @@ pub fn verify_proof(proof: Proof, verifying_key: Groth16Verifyingkey) -> bool {
     public_inputs_vec[5] = proof.output_commitments[0];
     public_inputs_vec[6] = proof.output_commitments[1];
 
-     // First deserialize PROOF_A into a G1 point
-     let g1_point = G1::deserialize_with_mode(
-        &*[&change_endianness(&proof.proof_a[0..64]), &[0u8][..]].concat(),
+    let Some(proof_a) = negate_proof_a(&proof.proof_a) else {
+        return false;
+    };
+
+    let mut verifier = Groth16Verifier::new(
+        &proof_a,
+        &proof.proof_b,
+        &proof.proof_c,
+        &public_inputs_vec,
+        &verifying_key
+    ).unwrap();
+
+    verifier.verify().unwrap_or(false)
+}
+
+/**
+ * Verifies an association proof against the nullifiers of the transaction proof, so the
+ * proven inputs are the ones the transaction spends.
+ *
+ * @param association The association proof from the ext data.
+ * @param input_nullifiers The input nullifiers of the transaction proof.
+ * @param verifying_key The verifying key of the association circuit.
+ * @return Whether the proof is valid for `association.root` and the nullifiers.
+ */
+pub fn verify_association_proof(
+    association: &AssociationProof,
+    input_nullifiers: &[[u8; 32]; 2],
+    verifying_key: &AssociationVerifyingKey,
+) -> bool {
+    let public_inputs: [[u8; 32]; ASSOCIATION_PUBLIC_INPUTS] = [association.root, input_nullifiers[0], input_nullifiers[1]];
+    let verifying_key = Groth16Verifyingkey {
+        nr_pubinputs: ASSOCIATION_PUBLIC_INPUTS,
+        vk_alpha_g1: verifying_key.alpha_g1,
+        vk_beta_g2: verifying_key.beta_g2,
+        vk_gamme_g2: verifying_key.gamma_g2,
+        vk_delta_g2: verifying_key.delta_g2,
+        vk_ic: &verifying_key.ic,
+    };
+    let Some(proof_a) = negate_proof_a(&association.proof_a) else {
+        return false;
+    };
+
+    Groth16Verifier::new(&proof_a, &association.proof_b, &association.proof_c, &public_inputs, &verifying_key)
+        .and_then(|mut verifier| verifier.verify())
+        .unwrap_or(false)
+}
+
+/**
+ * Verifies a batch deposit proof, see `batch_deposit::PendingBatch::public_inputs`.
+ *
+ * @param proof The proof of the batch deposit circuit.
+ * @param public_inputs The deposited amount and the hash of the output commitments.
+ * @param verifying_key The verifying key of the batch deposit circuit.
+ * @return Whether the proof is valid for the public inputs.
+ */
+pub fn verify_batch_deposit_proof(
+    proof: &BatchDepositProof,
+    public_inputs: &[[u8; 32]; BATCH_DEPOSIT_PUBLIC_INPUTS],
+    verifying_key: &BatchDepositVerifyingKey,
+) -> bool {
+    let verifying_key = Groth16Verifyingkey {
+        nr_pubinputs: BATCH_DEPOSIT_PUBLIC_INPUTS,
+        vk_alpha_g1: verifying_key.alpha_g1,
+        vk_beta_g2: verifying_key.beta_g2,
+        vk_gamme_g2: verifying_key.gamma_g2,
+        vk_delta_g2: verifying_key.delta_g2,
+        vk_ic: &verifying_key.ic,
+    };
+    let Some(proof_a) = negate_proof_a(&proof.proof_a) else {
+        return false;
+    };
+
+    Groth16Verifier::new(&proof_a, &proof.proof_b, &proof.proof_c, public_inputs, &verifying_key)
+        .and_then(|mut verifier| verifier.verify())
+        .unwrap_or(false)
+}
+
+/**
+ * Negates `proof_a`, as the pairing check of the verifier expects.
+ *
+ * @param proof_a The big-endian G1 point of the proof.
+ * @return The negated point, or `None` if `proof_a` isn't a point of the curve.
+ */
+fn negate_proof_a(proof_a: &[u8; 64]) -> Option<[u8; 64]> {
+    // First deserialize PROOF_A into a G1 point
+    let g1_point = G1::deserialize_with_mode(
+        &*[&change_endianness(proof_a), &[0u8][..]].concat(),
         Compress::No,
         Validate::Yes,
     )
-    .unwrap();
-    
+    .ok()?;
+
     let mut proof_a_neg = [0u8; 65];
     g1_point
         .neg()
         .x
         .serialize_with_mode(&mut proof_a_neg[..32], Compress::No)
-        .unwrap();
+        .ok()?;
     g1_point
         .neg()
         .y
         .serialize_with_mode(&mut proof_a_neg[32..], Compress::No)
-        .unwrap();
-
-    let proof_a: [u8; 64] = change_endianness(&proof_a_neg[..64]).try_into().unwrap();
+        .ok()?;
 
-    let mut verifier = Groth16Verifier::new(
-        &proof_a,
-        &proof.proof_b,
-        &proof.proof_c,
-        &public_inputs_vec,
-        &verifying_key
-    ).unwrap();
-
-    verifier.verify().unwrap_or(false)
+    change_endianness(&proof_a_neg[..64]).try_into().ok()
 }
 
 pub fn change_endianness(bytes: &[u8]) -> Vec<u8> {
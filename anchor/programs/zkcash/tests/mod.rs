@@ -1 +1 @@
-mod unit; 
\ No newline at end of file
+mod unit;
@@ -0,0 +1,174 @@
+use anchor_lang::prelude::Pubkey;
+use ark_bn254::{Bn254, Fq, Fr, G1Affine, G2Affine};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::Groth16;
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_std::rand::{rngs::StdRng, SeedableRng};
+use zkcash::{
+    association_set::{AssociationSet, ASSOCIATION_ROOT_HISTORY_SIZE},
+    utils::verify_association_proof,
+    AssociationProof, AssociationSetAccount, AssociationVerifyingKey,
+};
+
+// Stands in for circuits/association.circom with the same public inputs: the root is x^2
+// and the nullifiers are x^3 and x^4 for a private x
+struct TestAssociationCircuit {
+    x: Fr,
+}
+
+impl ConstraintSynthesizer<Fr> for TestAssociationCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let x = self.x;
+        let root = cs.new_input_variable(|| Ok(x * x))?;
+        let nullifier0 = cs.new_input_variable(|| Ok(x * x * x))?;
+        let nullifier1 = cs.new_input_variable(|| Ok(x * x * x * x))?;
+        let witness = cs.new_witness_variable(|| Ok(x))?;
+        cs.enforce_constraint(lc!() + witness, lc!() + witness, lc!() + root)?;
+        cs.enforce_constraint(lc!() + root, lc!() + witness, lc!() + nullifier0)?;
+        cs.enforce_constraint(lc!() + nullifier0, lc!() + witness, lc!() + nullifier1)?;
+        Ok(())
+    }
+}
+
+pub fn fr_to_bytes(fr: Fr) -> [u8; 32] {
+    fr.into_bigint().to_bytes_be().try_into().unwrap()
+}
+
+pub fn fq_to_bytes(fq: Fq) -> [u8; 32] {
+    fq.into_bigint().to_bytes_be().try_into().unwrap()
+}
+
+pub fn g1_to_bytes(point: G1Affine) -> [u8; 64] {
+    [fq_to_bytes(point.x), fq_to_bytes(point.y)].concat().try_into().unwrap()
+}
+
+// The alt_bn128 syscalls take the imaginary part of each coordinate first
+pub fn g2_to_bytes(point: G2Affine) -> [u8; 128] {
+    [fq_to_bytes(point.x.c1), fq_to_bytes(point.x.c0), fq_to_bytes(point.y.c1), fq_to_bytes(point.y.c0)]
+        .concat()
+        .try_into()
+        .unwrap()
+}
+
+/// A verifying key of the test circuit and a proof for its root, with the nullifiers the
+/// proof is made for.
+pub struct TestAssociation {
+    pub verifying_key: AssociationVerifyingKey,
+    pub proof: AssociationProof,
+    pub input_nullifiers: [[u8; 32]; 2],
+}
+
+// Each x runs its own setup, so proofs for different x don't share a verifying key
+pub fn prove_test_association(association_set: Pubkey, x: u64) -> TestAssociation {
+    let rng = &mut StdRng::seed_from_u64(x);
+    let x = Fr::from(x);
+    let proving_key =
+        Groth16::<Bn254>::generate_random_parameters_with_reduction(TestAssociationCircuit { x }, rng).unwrap();
+    let proof = Groth16::<Bn254>::create_random_proof_with_reduction(TestAssociationCircuit { x }, &proving_key, rng).unwrap();
+
+    let vk = &proving_key.vk;
+    TestAssociation {
+        verifying_key: AssociationVerifyingKey {
+            alpha_g1: g1_to_bytes(vk.alpha_g1),
+            beta_g2: g2_to_bytes(vk.beta_g2),
+            gamma_g2: g2_to_bytes(vk.gamma_g2),
+            delta_g2: g2_to_bytes(vk.delta_g2),
+            ic: vk.gamma_abc_g1.iter().map(|point| g1_to_bytes(*point)).collect::<Vec<_>>().try_into().unwrap(),
+        },
+        proof: AssociationProof {
+            association_set,
+            root: fr_to_bytes(x * x),
+            proof_a: g1_to_bytes(proof.a),
+            proof_b: g2_to_bytes(proof.b),
+            proof_c: g1_to_bytes(proof.c),
+        },
+        input_nullifiers: [fr_to_bytes(x * x * x), fr_to_bytes(x * x * x * x)],
+    }
+}
+
+fn create_association_set() -> AssociationSetAccount {
+    let mut association_set = AssociationSetAccount {
+        provider: Pubkey::default(),
+        root_history: [[0u8; 32]; ASSOCIATION_ROOT_HISTORY_SIZE],
+        root_index: 0,
+        root_count: 0,
+        bump: 0,
+    };
+    AssociationSet::initialize(&mut association_set, Pubkey::new_unique(), 255);
+    association_set
+}
+
+fn root(i: u64) -> [u8; 32] {
+    let mut root = [0u8; 32];
+    root[24..].copy_from_slice(&(i + 1).to_be_bytes());
+    root
+}
+
+#[test]
+fn test_update_root() {
+    let mut association_set = create_association_set();
+    assert_eq!(AssociationSet::current_root(&association_set), None);
+
+    AssociationSet::update_root(&mut association_set, root(0)).unwrap();
+    AssociationSet::update_root(&mut association_set, root(1)).unwrap();
+
+    assert_eq!(AssociationSet::current_root(&association_set), Some(root(1)));
+    assert!(AssociationSet::is_known_root(&association_set, root(0)));
+    assert!(AssociationSet::is_known_root(&association_set, root(1)));
+    assert!(!AssociationSet::is_known_root(&association_set, root(2)));
+    assert!(!AssociationSet::is_known_root(&association_set, [0u8; 32]), "Zero root should always be rejected");
+
+    assert!(AssociationSet::update_root(&mut association_set, [0u8; 32]).is_err(), "Should reject a zero root");
+}
+
+#[test]
+fn test_old_roots_are_evicted() {
+    let mut association_set = create_association_set();
+
+    for i in 0..=ASSOCIATION_ROOT_HISTORY_SIZE as u64 {
+        AssociationSet::update_root(&mut association_set, root(i)).unwrap();
+    }
+
+    assert!(!AssociationSet::is_known_root(&association_set, root(0)), "Oldest root should be evicted");
+    for i in 1..=ASSOCIATION_ROOT_HISTORY_SIZE as u64 {
+        assert!(AssociationSet::is_known_root(&association_set, root(i)));
+    }
+    assert_eq!(AssociationSet::current_root(&association_set), Some(root(ASSOCIATION_ROOT_HISTORY_SIZE as u64)));
+}
+
+
+#[test]
+fn test_verify_association_proof() {
+    let association = prove_test_association(Pubkey::new_unique(), 3);
+    assert!(verify_association_proof(&association.proof, &association.input_nullifiers, &association.verifying_key));
+
+    let mut other_root = association.proof.clone();
+    other_root.root = fr_to_bytes(Fr::from(16u64));
+    assert!(
+        !verify_association_proof(&other_root, &association.input_nullifiers, &association.verifying_key),
+        "The proof is only valid for its root"
+    );
+
+    let swapped_nullifiers = [association.input_nullifiers[1], association.input_nullifiers[0]];
+    assert!(
+        !verify_association_proof(&association.proof, &swapped_nullifiers, &association.verifying_key),
+        "The proof is only valid for the nullifiers of the transaction it was made for"
+    );
+
+    let other_key = prove_test_association(Pubkey::new_unique(), 5).verifying_key;
+    assert!(!verify_association_proof(&association.proof, &association.input_nullifiers, &other_key));
+}
+
+#[test]
+fn test_verify_association_proof_rejects_invalid_points() {
+    let association = prove_test_association(Pubkey::new_unique(), 3);
+
+    let mut not_on_curve = association.proof.clone();
+    not_on_curve.proof_a[63] ^= 1;
+    assert!(!verify_association_proof(&not_on_curve, &association.input_nullifiers, &association.verifying_key));
+
+    let mut root_past_field = association.proof.clone();
+    root_past_field.root = [0xffu8; 32];
+    assert!(!verify_association_proof(&root_past_field, &association.input_nullifiers, &association.verifying_key));
+}
@@ -0,0 +1,144 @@
+use super::association_set_test::{fr_to_bytes, g1_to_bytes, g2_to_bytes};
+use anchor_lang::prelude::Pubkey;
+use ark_bn254::{Bn254, Fr};
+use ark_ff::PrimeField;
+use ark_groth16::Groth16;
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable};
+use ark_std::rand::{rngs::StdRng, SeedableRng};
+use light_hasher::{Hasher, Poseidon};
+use zkcash::{
+    batch_deposit::{PendingBatch, BATCH_DEPOSIT_OUTPUTS, MAX_BATCH_CIPHERTEXT_LEN},
+    utils::verify_batch_deposit_proof,
+    BatchDepositAccount, BatchDepositProof, BatchDepositVerifyingKey, BatchOutput,
+};
+
+// Stands in for circuits/batchDeposit.circom with the same public inputs: the amount is the
+// sum of two private amounts, and the outputs hash is passed through
+struct TestBatchDepositCircuit {
+    amounts: [u64; 2],
+    outputs_hash: Fr,
+}
+
+impl ConstraintSynthesizer<Fr> for TestBatchDepositCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let [amount0, amount1] = self.amounts.map(Fr::from);
+        let public_amount = cs.new_input_variable(|| Ok(amount0 + amount1))?;
+        let outputs_hash = cs.new_input_variable(|| Ok(self.outputs_hash))?;
+        let out_amount0 = cs.new_witness_variable(|| Ok(amount0))?;
+        let out_amount1 = cs.new_witness_variable(|| Ok(amount1))?;
+        let hash_witness = cs.new_witness_variable(|| Ok(self.outputs_hash))?;
+        cs.enforce_constraint(lc!() + out_amount0 + out_amount1, lc!() + Variable::One, lc!() + public_amount)?;
+        cs.enforce_constraint(lc!() + hash_witness, lc!() + Variable::One, lc!() + outputs_hash)?;
+        Ok(())
+    }
+}
+
+fn prove_test_batch_deposit(amounts: [u64; 2], batch: &BatchDepositAccount, seed: u64) -> (BatchDepositVerifyingKey, BatchDepositProof) {
+    let rng = &mut StdRng::seed_from_u64(seed);
+    let commitments: Vec<[u8; 32]> = batch.outputs.iter().map(|output| output.commitment).collect();
+    let outputs_hash = Fr::from_be_bytes_mod_order(&PendingBatch::outputs_hash(&commitments).unwrap());
+    let circuit = || TestBatchDepositCircuit { amounts, outputs_hash };
+
+    let proving_key = Groth16::<Bn254>::generate_random_parameters_with_reduction(circuit(), rng).unwrap();
+    let proof = Groth16::<Bn254>::create_random_proof_with_reduction(circuit(), &proving_key, rng).unwrap();
+
+    let vk = &proving_key.vk;
+    (
+        BatchDepositVerifyingKey {
+            alpha_g1: g1_to_bytes(vk.alpha_g1),
+            beta_g2: g2_to_bytes(vk.beta_g2),
+            gamma_g2: g2_to_bytes(vk.gamma_g2),
+            delta_g2: g2_to_bytes(vk.delta_g2),
+            ic: vk.gamma_abc_g1.iter().map(|point| g1_to_bytes(*point)).collect::<Vec<_>>().try_into().unwrap(),
+        },
+        BatchDepositProof { proof_a: g1_to_bytes(proof.a), proof_b: g2_to_bytes(proof.b), proof_c: g1_to_bytes(proof.c) },
+    )
+}
+
+fn output(i: u64) -> BatchOutput {
+    BatchOutput { commitment: fr_to_bytes(Fr::from(i + 1)), encrypted_output: vec![1u8; 200], auditor_output: Vec::new() }
+}
+
+fn empty_batch() -> BatchDepositAccount {
+    BatchDepositAccount { signer: Pubkey::new_unique(), outputs: Vec::new(), bump: 255 }
+}
+
+#[test]
+fn test_push_outputs() {
+    let mut batch = empty_batch();
+    PendingBatch::push_outputs(&mut batch, (0..3).map(output).collect()).unwrap();
+    PendingBatch::push_outputs(&mut batch, (3..BATCH_DEPOSIT_OUTPUTS as u64).map(output).collect()).unwrap();
+    assert_eq!(batch.outputs, (0..BATCH_DEPOSIT_OUTPUTS as u64).map(output).collect::<Vec<_>>());
+
+    assert!(PendingBatch::push_outputs(&mut batch, vec![output(9)]).is_err(), "Should reject outputs past a full batch");
+    assert_eq!(batch.outputs.len(), BATCH_DEPOSIT_OUTPUTS);
+}
+
+#[test]
+fn test_push_outputs_rejects_invalid_outputs() {
+    let invalid_outputs = [
+        BatchOutput { commitment: [0u8; 32], ..output(0) },
+        BatchOutput { commitment: [0xffu8; 32], ..output(0) },
+        BatchOutput { encrypted_output: Vec::new(), ..output(0) },
+        BatchOutput { encrypted_output: vec![1u8; MAX_BATCH_CIPHERTEXT_LEN + 1], ..output(0) },
+        BatchOutput { auditor_output: vec![1u8; MAX_BATCH_CIPHERTEXT_LEN + 1], ..output(0) },
+    ];
+    for invalid_output in invalid_outputs {
+        let mut batch = empty_batch();
+        assert!(PendingBatch::push_outputs(&mut batch, vec![output(1), invalid_output]).is_err());
+        assert!(batch.outputs.is_empty(), "A rejected call shouldn't add any output");
+    }
+}
+
+#[test]
+fn test_has_auditor_outputs() {
+    let mut batch = empty_batch();
+    PendingBatch::push_outputs(&mut batch, vec![output(0), BatchOutput { auditor_output: vec![2u8; 200], ..output(1) }]).unwrap();
+
+    assert!(PendingBatch::has_auditor_outputs(&batch, [0u8; 32]), "A pool without auditor needs no auditor outputs");
+    assert!(!PendingBatch::has_auditor_outputs(&batch, [7u8; 32]));
+
+    batch.outputs[0].auditor_output = vec![2u8; 200];
+    assert!(PendingBatch::has_auditor_outputs(&batch, [7u8; 32]));
+}
+
+#[test]
+fn test_outputs_hash_pads_with_zeros() {
+    let commitments = [output(0).commitment, output(1).commitment];
+    let mut padded = vec![[0u8; 32]; BATCH_DEPOSIT_OUTPUTS];
+    padded[..2].copy_from_slice(&commitments);
+    let inputs: Vec<&[u8]> = padded.iter().map(|commitment| commitment.as_slice()).collect();
+
+    assert_eq!(PendingBatch::outputs_hash(&commitments), Some(Poseidon::hashv(&inputs).unwrap()));
+    assert_ne!(PendingBatch::outputs_hash(&commitments), PendingBatch::outputs_hash(&commitments[..1]));
+    assert_eq!(PendingBatch::outputs_hash(&[[1u8; 32]; BATCH_DEPOSIT_OUTPUTS + 1]), None);
+}
+
+#[test]
+fn test_verify_batch_deposit_proof() {
+    let mut batch = empty_batch();
+    PendingBatch::push_outputs(&mut batch, vec![output(0), output(1)]).unwrap();
+    let (verifying_key, proof) = prove_test_batch_deposit([300, 700], &batch, 1);
+
+    let public_inputs = PendingBatch::public_inputs(&batch, 1_000).unwrap();
+    assert!(verify_batch_deposit_proof(&proof, &public_inputs, &verifying_key));
+
+    let more = PendingBatch::public_inputs(&batch, 1_001).unwrap();
+    assert!(!verify_batch_deposit_proof(&proof, &more, &verifying_key), "The proof is only valid for its amount");
+
+    let mut swapped = batch.clone();
+    swapped.outputs.swap(0, 1);
+    let swapped_inputs = PendingBatch::public_inputs(&swapped, 1_000).unwrap();
+    assert!(
+        !verify_batch_deposit_proof(&proof, &swapped_inputs, &verifying_key),
+        "The proof is only valid for the outputs it was made for, in their order"
+    );
+
+    let (other_key, _) = prove_test_batch_deposit([300, 700], &batch, 2);
+    assert!(!verify_batch_deposit_proof(&proof, &public_inputs, &other_key));
+
+    let mut not_on_curve = proof.clone();
+    not_on_curve.proof_a[63] ^= 1;
+    assert!(!verify_batch_deposit_proof(&not_on_curve, &public_inputs, &verifying_key));
+}
@@ -0,0 +1,105 @@
+use anchor_lang::prelude::Pubkey;
+use light_hasher::Poseidon;
+use std::mem::MaybeUninit;
+use zkcash::{
+    commitment_queue::{CommitmentQueue, COMMITMENT_QUEUE_SIZE},
+    merkle_tree::{MerkleTree, MAX_LEAVES},
+    CommitmentQueueAccount, MerkleTreeAccount,
+};
+
+fn create_test_account() -> MerkleTreeAccount {
+    let mut account = unsafe { MaybeUninit::<MerkleTreeAccount>::zeroed().assume_init() };
+    MerkleTree::initialize::<Poseidon>(&mut account);
+    account
+}
+
+fn create_queue() -> CommitmentQueueAccount {
+    let mut queue = unsafe { MaybeUninit::<CommitmentQueueAccount>::zeroed().assume_init() };
+    CommitmentQueue::initialize(&mut queue, Pubkey::new_unique(), 255);
+    queue
+}
+
+fn leaf(i: u64) -> [u8; 32] {
+    let mut leaf = [0u8; 32];
+    leaf[24..].copy_from_slice(&(i + 1).to_be_bytes());
+    leaf
+}
+
+#[test]
+fn test_queued_leaves_match_direct_appends() {
+    let mut tree_account = create_test_account();
+    let mut expected_account = create_test_account();
+    let mut queue = create_queue();
+
+    for i in 0..5 {
+        assert_eq!(CommitmentQueue::push(&mut queue, &tree_account, leaf(i)).unwrap(), i);
+        MerkleTree::append::<Poseidon>(leaf(i), &mut expected_account).unwrap();
+    }
+    assert_eq!(tree_account.next_index, 0, "Queued leaves must not be inserted yet");
+
+    assert_eq!(CommitmentQueue::process::<Poseidon>(&mut queue, &mut tree_account, 3, 0).unwrap(), 3);
+    assert_eq!(queue.len, 2);
+    // Leaves queued after a partial batch still get the next free index
+    assert_eq!(CommitmentQueue::push(&mut queue, &tree_account, leaf(5)).unwrap(), 5);
+    MerkleTree::append::<Poseidon>(leaf(5), &mut expected_account).unwrap();
+
+    assert_eq!(CommitmentQueue::process::<Poseidon>(&mut queue, &mut tree_account, 100, 0).unwrap(), 3);
+    assert_eq!(queue.len, 0);
+    assert_eq!(tree_account.next_index, 6);
+    assert_eq!(tree_account.root, expected_account.root);
+}
+
+#[test]
+fn test_queue_wraps_around() {
+    let mut tree_account = create_test_account();
+    let mut expected_account = create_test_account();
+    let mut queue = create_queue();
+
+    for i in 0..COMMITMENT_QUEUE_SIZE as u64 {
+        CommitmentQueue::push(&mut queue, &tree_account, leaf(i)).unwrap();
+        MerkleTree::append::<Poseidon>(leaf(i), &mut expected_account).unwrap();
+    }
+    assert!(CommitmentQueue::push(&mut queue, &tree_account, leaf(0)).is_err(), "Should reject pushes to a full queue");
+
+    CommitmentQueue::process::<Poseidon>(&mut queue, &mut tree_account, 10, 0).unwrap();
+    for i in COMMITMENT_QUEUE_SIZE as u64..COMMITMENT_QUEUE_SIZE as u64 + 10 {
+        assert_eq!(CommitmentQueue::push(&mut queue, &tree_account, leaf(i)).unwrap(), i);
+        MerkleTree::append::<Poseidon>(leaf(i), &mut expected_account).unwrap();
+    }
+
+    CommitmentQueue::process::<Poseidon>(&mut queue, &mut tree_account, u64::MAX, 0).unwrap();
+    assert_eq!(tree_account.root, expected_account.root);
+}
+
+#[test]
+fn test_push_rejects_leaves_past_tree_capacity() {
+    let mut tree_account = create_test_account();
+    let mut queue = create_queue();
+    tree_account.next_index = MAX_LEAVES - 1;
+
+    assert_eq!(CommitmentQueue::push(&mut queue, &tree_account, leaf(0)).unwrap(), MAX_LEAVES - 1);
+    assert!(CommitmentQueue::push(&mut queue, &tree_account, leaf(1)).is_err(), "Should reject leaves that don't fit in the tree");
+}
+
+#[test]
+fn test_process_adds_one_root_per_crank() {
+    let mut tree_account = create_test_account();
+    let mut queue = create_queue();
+    for i in 0..40 {
+        CommitmentQueue::push(&mut queue, &tree_account, leaf(i)).unwrap();
+    }
+    let old_root = tree_account.root;
+
+    // A full crank must not flush older roots out of the root history
+    assert_eq!(CommitmentQueue::process::<Poseidon>(&mut queue, &mut tree_account, 30, 7).unwrap(), 30);
+    assert_eq!(tree_account.root_index, 1);
+    assert_eq!(tree_account.root_history_slots[1], 7);
+    assert!(MerkleTree::is_known_root(&tree_account, old_root));
+
+    assert_eq!(CommitmentQueue::process::<Poseidon>(&mut queue, &mut tree_account, 30, 8).unwrap(), 10);
+    assert_eq!(tree_account.root_index, 2);
+
+    // Nothing left to insert, so no new root either
+    assert_eq!(CommitmentQueue::process::<Poseidon>(&mut queue, &mut tree_account, 30, 9).unwrap(), 0);
+    assert_eq!(tree_account.root_index, 2);
+}
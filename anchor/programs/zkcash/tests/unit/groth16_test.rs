@@ -1,13 +1,11 @@
 use zkcash::groth16::{Groth16Verifier, Groth16Verifyingkey, is_less_than_bn254_field_size_be};
 use zkcash::errors::Groth16Error;
-use ark_bn254;
 use ark_ff::PrimeField;
 use ark_bn254::Fr;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
 use zkcash::utils::{change_endianness, VERIFYING_KEY};
 use std::ops::Neg;
 use num_bigint::BigUint;
-use anchor_lang::prelude::msg;
 type G1 = ark_bn254::g1::G1Affine;
 
 // Updated proof data for the new circuit with mintAddress (4 inputs)
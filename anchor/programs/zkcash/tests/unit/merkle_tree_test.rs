@@ -1,6 +1,8 @@
 use light_hasher::{Poseidon, Hasher};
 use std::mem::MaybeUninit;
-use zkcash::{MerkleTreeAccount, merkle_tree::{MerkleTree, DEFAULT_HEIGHT}};
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::Discriminator;
+use zkcash::{MerkleTreeAccount, PoolConfigAccount, merkle_tree::{MerkleTree, DEFAULT_HEIGHT, LEGACY_TREE_ACCOUNT_SIZE, MAX_LEAVES, MERKLE_TREE_ACCOUNT_VERSION, ROOT_CHECKPOINTS, ROOT_HISTORY_SIZE}};
 
 // Helper function to create an initialized MerkleTreeAccount for testing
 fn create_test_account() -> MerkleTreeAccount {
@@ -28,8 +30,8 @@ fn test_new_empty() {
     assert_eq!(account.root, zero_bytes[DEFAULT_HEIGHT]);
     
     // All subtrees should be zero bytes
-    for i in 0..DEFAULT_HEIGHT {
-        assert_eq!(account.subtrees[i], zero_bytes[i]);
+    for (subtree, zero_byte) in account.subtrees.iter().zip(zero_bytes.iter()) {
+        assert_eq!(subtree, zero_byte);
     }
 }
 
@@ -273,8 +275,8 @@ fn test_is_known_root_with_appends() {
     }
     
     // Verify all roots are recognized
-    for i in 0..6 {
-        assert!(MerkleTree::is_known_root(&account, roots[i]), 
+    for (i, root) in roots.iter().enumerate() {
+        assert!(MerkleTree::is_known_root(&account, *root), 
             "Root at index {} should be recognized", i);
     }
     
@@ -307,12 +309,161 @@ fn test_is_known_root_circular_buffer() {
         "Overwritten root should not be recognized");
     
     // The most recent 100 roots should be recognized
-    for i in 2..102 {
-        assert!(MerkleTree::is_known_root(&account, all_roots[i]), 
+    for (i, root) in all_roots.iter().enumerate().skip(2) {
+        assert!(MerkleTree::is_known_root(&account, *root), 
             "Root at index {} should be recognized", i);
     }
 }
 
+#[test]
+fn test_is_valid_root_expires_by_age() {
+    let mut account = create_test_account();
+
+    let initial_root = account.root;
+    MerkleTree::append_at_slot::<Poseidon>([1u8; 32], &mut account, 100).unwrap();
+    let root_at_100 = account.root;
+    MerkleTree::append_at_slot::<Poseidon>([2u8; 32], &mut account, 120).unwrap();
+    assert_eq!(account.root_history_slots[account.root_index as usize], 120);
+
+    assert!(MerkleTree::is_valid_root(&account, root_at_100, 150, 50));
+    assert!(!MerkleTree::is_valid_root(&account, root_at_100, 151, 50), "Root older than max_root_age should be rejected");
+    assert!(MerkleTree::is_valid_root(&account, account.root, 170, 50));
+    assert!(!MerkleTree::is_valid_root(&account, initial_root, 120, 50));
+    // Expired roots are still known, so the error can tell them apart from unknown roots
+    assert!(MerkleTree::is_known_root(&account, root_at_100));
+
+    // Without an age limit only the root history size applies
+    assert!(MerkleTree::is_valid_root(&account, initial_root, u64::MAX, 0));
+    assert!(!MerkleTree::is_valid_root(&account, [42u8; 32], 0, 0));
+}
+
+#[test]
+fn test_check_root_enforces_maturity_delay_for_outflows() {
+    let mut account = create_test_account();
+    let pool_config = PoolConfigAccount { maturity_delay: 30, ..Default::default() };
+
+    MerkleTree::append_at_slot::<Poseidon>([1u8; 32], &mut account, 100).unwrap();
+    let root = account.root;
+
+    assert!(!MerkleTree::is_mature_root(&account, root, 129, 30));
+    assert!(MerkleTree::is_mature_root(&account, root, 130, 30));
+
+    // Transactions that keep funds in the pool can use the newest root right away
+    assert!(MerkleTree::check_root(&account, &pool_config, root, 100, false).is_ok());
+    assert!(MerkleTree::check_root(&account, &pool_config, root, 129, true).is_err(), "Immature root should be rejected for withdrawals");
+    assert!(MerkleTree::check_root(&account, &pool_config, root, 130, true).is_ok());
+}
+
+#[test]
+fn test_checkpoints_are_sampled_by_slot() {
+    let mut account = create_test_account();
+    assert_eq!(MerkleTree::checkpoint_interval(40), 10);
+    assert_eq!(MerkleTree::checkpoint_interval(0), 1);
+
+    MerkleTree::append_at_slot::<Poseidon>([1u8; 32], &mut account, 100).unwrap();
+    MerkleTree::checkpoint_root(&mut account, 40);
+    let checkpoint = account.root;
+    assert_eq!(account.checkpoint_roots[account.checkpoint_index as usize], checkpoint);
+
+    // Roots within the interval don't replace the latest checkpoint
+    MerkleTree::append_at_slot::<Poseidon>([2u8; 32], &mut account, 109).unwrap();
+    MerkleTree::checkpoint_root(&mut account, 40);
+    assert_eq!(account.checkpoint_roots[account.checkpoint_index as usize], checkpoint);
+
+    MerkleTree::append_at_slot::<Poseidon>([3u8; 32], &mut account, 110).unwrap();
+    MerkleTree::checkpoint_root(&mut account, 40);
+    assert_eq!(account.checkpoint_roots[account.checkpoint_index as usize], account.root);
+    assert_eq!(account.checkpoint_slots[account.checkpoint_index as usize], 110);
+}
+
+#[test]
+fn test_mature_root_survives_a_flushed_root_history() {
+    let mut account = create_test_account();
+    let pool_config = PoolConfigAccount { maturity_delay: 40, ..Default::default() };
+
+    MerkleTree::append_at_slot::<Poseidon>([1u8; 32], &mut account, 100).unwrap();
+    MerkleTree::checkpoint_root(&mut account, pool_config.maturity_delay);
+    let root = account.root;
+
+    // A burst of transactions fills the whole root history within a few slots
+    for i in 0..ROOT_HISTORY_SIZE as u64 {
+        let mut leaf = [0u8; 32];
+        leaf[24..].copy_from_slice(&(i + 2).to_be_bytes());
+        MerkleTree::append_at_slot::<Poseidon>(leaf, &mut account, 101 + i / 20).unwrap();
+        MerkleTree::checkpoint_root(&mut account, pool_config.maturity_delay);
+    }
+    assert!(!account.root_history.contains(&root));
+
+    assert!(MerkleTree::is_known_root(&account, root));
+    assert!(MerkleTree::check_root(&account, &pool_config, root, 140, true).is_ok());
+    assert!(MerkleTree::check_root(&account, &pool_config, root, 139, true).is_err(), "Checkpoints still wait for the maturity delay");
+}
+
+#[test]
+fn test_checkpoints_keep_a_mature_root_under_steady_traffic() {
+    let mut account = create_test_account();
+    let maturity_delay = 40;
+
+    // A root every slot, each checked right after it was created
+    for slot in 1..1_000u64 {
+        let mut leaf = [0u8; 32];
+        leaf[24..].copy_from_slice(&slot.to_be_bytes());
+        MerkleTree::append_at_slot::<Poseidon>(leaf, &mut account, slot).unwrap();
+        MerkleTree::checkpoint_root(&mut account, maturity_delay);
+
+        if slot > 2 * maturity_delay {
+            let mature = (0..ROOT_CHECKPOINTS).any(|i| {
+                slot - account.checkpoint_slots[i] >= maturity_delay
+                    && MerkleTree::is_mature_root(&account, account.checkpoint_roots[i], slot, maturity_delay)
+            });
+            assert!(mature, "No mature checkpoint at slot {}", slot);
+        }
+    }
+}
+
+#[test]
+fn test_legacy_layout_ends_before_root_history_slots() {
+    // The fields carved from the old padding are zero in old accounts: no queue, tree 0, version 0
+    assert_eq!(std::mem::offset_of!(MerkleTreeAccount, root_history_slots), LEGACY_TREE_ACCOUNT_SIZE);
+    assert_eq!(create_test_account().version, MERKLE_TREE_ACCOUNT_VERSION);
+}
+
+#[test]
+fn test_check_legacy_account() {
+    let authority = Pubkey::new_unique();
+    let mut data = vec![0u8; 8 + LEGACY_TREE_ACCOUNT_SIZE];
+    data[..8].copy_from_slice(MerkleTreeAccount::DISCRIMINATOR);
+    data[8..40].copy_from_slice(authority.as_ref());
+
+    assert!(MerkleTree::check_legacy_account(&data, &authority).is_ok());
+    assert!(MerkleTree::check_legacy_account(&data, &Pubkey::new_unique()).is_err(), "Only the authority can migrate");
+
+    let mut migrated = data.clone();
+    migrated.resize(8 + std::mem::size_of::<MerkleTreeAccount>(), 0);
+    assert!(MerkleTree::check_legacy_account(&migrated, &authority).is_err(), "Current trees are not migrated again");
+
+    data[0] ^= 1;
+    assert!(MerkleTree::check_legacy_account(&data, &authority).is_err(), "Other accounts are rejected");
+}
+
+#[test]
+fn test_migrate_records_roots_at_migration_slot() {
+    // A tree of the old layout, grown with zeroed bytes
+    let mut account = create_test_account();
+    MerkleTree::append::<Poseidon>([1u8; 32], &mut account).unwrap();
+    account.version = 0;
+    let root = account.root;
+
+    MerkleTree::migrate(&mut account, 500).unwrap();
+    assert_eq!(account.version, MERKLE_TREE_ACCOUNT_VERSION);
+    assert!(account.root_history_slots.iter().all(|slot| *slot == 500));
+    assert!(MerkleTree::is_valid_root(&account, root, 510, 10));
+    assert!(!MerkleTree::is_mature_root(&account, root, 510, 30), "Old roots wait for the maturity delay again");
+
+    assert!(MerkleTree::migrate(&mut account, 600).is_err(), "A tree is migrated once");
+    assert!(account.root_history_slots.iter().all(|slot| *slot == 500));
+}
+
 #[test]
 fn test_is_zero_root_always_rejected() {
     let mut account = create_test_account();
@@ -333,7 +484,7 @@ fn test_is_zero_root_always_rejected() {
 
 #[test]
 fn test_modification_of_root_history_is_rejected() {
-    let mut account = create_test_account();
+    let account = create_test_account();
     
     let initial_root = account.root;
     let mut modified_root = initial_root;
@@ -367,23 +518,28 @@ fn test_append_near_max_next_index() {
     // Create and initialize a test account
     let mut account = create_test_account();
     
-    // Set next_index to near maximum value (u64::MAX - 1)
-    account.next_index = u64::MAX - 1;
+    // Set next_index to the last free leaf of the tree
+    account.next_index = MAX_LEAVES - 1;
     
     // Create a test leaf
     let leaf = [1u8; 32];
     
-    // First append should succeed
+    // Appending the last leaf should succeed
     let result1 = MerkleTree::append::<Poseidon>(leaf, &mut account);
-    assert!(result1.is_ok(), "First append should succeed");
-    assert_eq!(account.next_index, u64::MAX, "next_index should be at maximum");
+    assert!(result1.is_ok(), "Appending the last leaf should succeed");
+    assert_eq!(account.next_index, MAX_LEAVES, "next_index should be at capacity");
+    assert!(MerkleTree::is_full(&account));
     
-    // Second append should fail due to overflow
+    // Appending to a full tree should fail
+    let root = account.root;
+    let subtrees = account.subtrees;
     let result2 = MerkleTree::append::<Poseidon>(leaf, &mut account);
-    assert!(result2.is_err(), "Second append should fail when next_index would overflow");
+    assert!(result2.is_err(), "Append should fail when the tree is full");
     
-    // Verify next_index remains at MAX after failed attempt
-    assert_eq!(account.next_index, u64::MAX, "next_index should remain at maximum after failed append");
+    // Verify the tree was not modified by the failed attempt
+    assert_eq!(account.next_index, MAX_LEAVES, "next_index should remain at capacity after failed append");
+    assert_eq!(account.root, root);
+    assert_eq!(account.subtrees, subtrees);
 }
 
 #[test]
@@ -438,4 +594,46 @@ fn test_multiple_appends_verify_index_increments() {
         let expected_index_after = start_index + i + 1;
         assert_eq!(account.next_index, expected_index_after, "next_index should be {} after append {}", expected_index_after, i);
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_append_batch_matches_sequential_appends() {
+    // Odd and even starting indices, and batches that cross subtree boundaries
+    for (prefix, batch) in [(0, 1), (0, 8), (1, 2), (3, 5), (5, 11), (7, 1), (6, 17)] {
+        let mut sequential = create_test_account();
+        let mut batched = create_test_account();
+        for i in 0..prefix {
+            let leaf = [i as u8 + 1; 32];
+            MerkleTree::append::<Poseidon>(leaf, &mut sequential).unwrap();
+            MerkleTree::append::<Poseidon>(leaf, &mut batched).unwrap();
+        }
+
+        let leaves: Vec<[u8; 32]> = (prefix..prefix + batch).map(|i| [i as u8 + 1; 32]).collect();
+        for leaf in &leaves {
+            MerkleTree::append::<Poseidon>(*leaf, &mut sequential).unwrap();
+        }
+        let first_index = MerkleTree::append_batch::<Poseidon>(&leaves, &mut batched, 42).unwrap();
+
+        assert_eq!(first_index, prefix as u64);
+        assert_eq!(batched.root, sequential.root, "prefix {prefix}, batch {batch}");
+        assert_eq!(batched.subtrees, sequential.subtrees, "prefix {prefix}, batch {batch}");
+        assert_eq!(batched.next_index, sequential.next_index);
+
+        // One root for the whole batch
+        assert_eq!(batched.root_index, prefix as u64 + 1);
+        assert_eq!(batched.root_history_slots[batched.root_index as usize], 42);
+
+        // Later appends continue from the same state
+        MerkleTree::append::<Poseidon>([9u8; 32], &mut sequential).unwrap();
+        MerkleTree::append::<Poseidon>([9u8; 32], &mut batched).unwrap();
+        assert_eq!(batched.root, sequential.root);
+    }
+}
+
+#[test]
+fn test_append_batch_rejects_overflow() {
+    let mut account = create_test_account();
+    account.next_index = MAX_LEAVES - 1;
+    let result = MerkleTree::append_batch::<Poseidon>(&[[1u8; 32], [2u8; 32]], &mut account, 0);
+    assert!(result.is_err());
+    assert_eq!(account.next_index, MAX_LEAVES - 1);
+}
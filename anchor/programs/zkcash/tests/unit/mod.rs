@@ -1,3 +1,8 @@
 mod merkle_tree_test; 
 mod utils_test;
-mod groth16_test;
\ No newline at end of file
+mod groth16_test;
+mod preflight_test;
+mod tree_set_test;
+mod commitment_queue_test;
+mod association_set_test;
+mod batch_deposit_test;
\ No newline at end of file
@@ -0,0 +1,623 @@
+use anchor_lang::prelude::{AccountInfo, AnchorSerialize, Pubkey, Rent};
+use anchor_lang::solana_program::hash::hash;
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use light_hasher::Poseidon;
+use std::mem::MaybeUninit;
+use super::association_set_test::prove_test_association;
+use zkcash::{
+    association_set::{AssociationSet, ASSOCIATION_ROOT_HISTORY_SIZE},
+    merkle_tree::{MerkleTree, MAX_LEAVES},
+    commitment_queue::COMMITMENT_QUEUE_SIZE,
+    preflight::{check_transact, check_transact_with_verifier, TransactState, TransactVerdict},
+    tree_set::{TreeSet, MAX_TREE_SHARDS},
+    AssociationSetAccount, AssociationVerifyingKeyAccount, CommitmentQueueAccount, ExtData, MerkleTreeAccount, Payout, PoolConfigAccount, Proof, TreeSetAccount, WithdrawCall, WithdrawCallAccount,
+};
+
+// Proof points from the groth16 tests, they deserialize but don't verify for these inputs
+const PROOF_A: [u8; 64] = [1, 120, 228, 36, 201, 133, 128, 26, 172, 217, 100, 136, 4, 173, 211, 147, 179, 148, 119, 103, 242, 130, 82, 6, 15, 222, 168, 228, 122, 212, 139, 89, 8, 65, 251, 25, 165, 57, 28, 83, 97, 107, 162, 60, 183, 231, 97, 58, 84, 72, 101, 68, 250, 34, 69, 201, 40, 181, 98, 128, 62, 70, 243, 17];
+
+const TREE_TOKEN_BALANCE: u64 = 1_000_000_000;
+
+// The recipient committed in the test ext data and passed as the `recipient` account
+const RECIPIENT: Pubkey = Pubkey::new_from_array([9u8; 32]);
+
+fn create_test_account() -> MerkleTreeAccount {
+    let mut account = unsafe { MaybeUninit::<MerkleTreeAccount>::zeroed().assume_init() };
+    account.max_deposit_amount = 1_000_000_000;
+    MerkleTree::initialize::<Poseidon>(&mut account);
+    account
+}
+
+// The account infos borrow their fields for the whole test
+fn account_info(key: Pubkey, lamports: u64, executable: bool) -> AccountInfo<'static> {
+    AccountInfo::new(
+        Box::leak(Box::new(key)),
+        false,
+        true,
+        Box::leak(Box::new(lamports)),
+        Box::leak(Vec::new().into_boxed_slice()),
+        Box::leak(Box::new(Pubkey::default())),
+        executable,
+        0,
+    )
+}
+
+// The accounts of a transact into a tree without a queue, nothing spent yet
+struct Accounts {
+    tree_account: MerkleTreeAccount,
+    pool_config: PoolConfigAccount,
+    tree_token_account: AccountInfo<'static>,
+    recipient: AccountInfo<'static>,
+    fee_recipient_account: AccountInfo<'static>,
+    rent: Rent,
+}
+
+impl Accounts {
+    fn new() -> Self {
+        Accounts {
+            tree_account: create_test_account(),
+            pool_config: PoolConfigAccount::default(),
+            tree_token_account: account_info(Pubkey::new_unique(), TREE_TOKEN_BALANCE, false),
+            recipient: account_info(RECIPIENT, 1_000_000, false),
+            fee_recipient_account: account_info(Pubkey::new_unique(), 1_000_000, false),
+            rent: Rent::default(),
+        }
+    }
+
+    fn state(&self) -> TransactState<'_, 'static> {
+        TransactState {
+            tree_account: &self.tree_account,
+            root_tree_account: None,
+            commitment_queue: None,
+            tree_set: None,
+            pool_config: &self.pool_config,
+            nullifiers_spent: [[false; 2]; 2],
+            commitments_exist: [[false; 2]; 2],
+            tree_token_account: &self.tree_token_account,
+            recipient: &self.recipient,
+            fee_recipient_account: &self.fee_recipient_account,
+            remaining_accounts: &[],
+            association_set: None,
+            association_verifying_key: None,
+            current_slot: 0,
+            rent: &self.rent,
+        }
+    }
+}
+
+// A tree set with tree 0 and tree 1 as shards
+fn create_tree_set() -> TreeSetAccount {
+    let mut tree_set = TreeSetAccount {
+        authority: Pubkey::default(),
+        active_trees: [0; MAX_TREE_SHARDS],
+        shard_count: 0,
+        tree_count: 0,
+        bump: 0,
+    };
+    TreeSet::initialize_if_empty(&mut tree_set, Pubkey::default(), 255);
+    TreeSet::add_shard(&mut tree_set).unwrap();
+    tree_set
+}
+
+fn fr_to_bytes(fr: Fr) -> [u8; 32] {
+    fr.into_bigint().to_bytes_be().try_into().unwrap()
+}
+
+fn test_ext_data(ext_amount: i64, fee: u64) -> ExtData {
+    ExtData {
+        recipient: RECIPIENT,
+        ext_amount,
+        encrypted_output1: vec![1u8; 16],
+        encrypted_output2: vec![2u8; 16],
+        fee,
+        call: None,
+        auditor_outputs: None,
+        payouts: None,
+        association: None,
+    }
+}
+
+// Builds a proof whose public inputs are consistent with the ext data and the tree
+fn test_proof(account: &MerkleTreeAccount, ext_data: &ExtData) -> Proof {
+    let mut serialized_ext_data = Vec::new();
+    ext_data.serialize(&mut serialized_ext_data).unwrap();
+    let ext_data_hash = Fr::from_le_bytes_mod_order(&hash(&serialized_ext_data).to_bytes());
+
+    let ext_amount = Fr::from(ext_data.ext_amount.unsigned_abs());
+    let public_amount = if ext_data.ext_amount >= 0 {
+        ext_amount - Fr::from(ext_data.fee)
+    } else {
+        -(ext_amount + Fr::from(ext_data.fee))
+    };
+
+    Proof {
+        proof_a: PROOF_A,
+        proof_b: [0u8; 128],
+        proof_c: [0u8; 64],
+        root: account.root,
+        public_amount: fr_to_bytes(public_amount),
+        ext_data_hash: fr_to_bytes(ext_data_hash),
+        input_nullifiers: [[1u8; 32], [2u8; 32]],
+        output_commitments: [[3u8; 32], [4u8; 32]],
+    }
+}
+
+// Checks with a verifier that accepts every proof, to reach the checks after the proof
+fn check_with_valid_proof(state: &TransactState, proof: &Proof, ext_data: &ExtData) -> TransactVerdict {
+    check_transact_with_verifier(state, proof, ext_data, |_| true)
+}
+
+#[test]
+fn test_check_transact_unknown_root() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(1_000, 10);
+    let mut proof = test_proof(&accounts.tree_account, &ext_data);
+    proof.root = [7u8; 32];
+
+    assert_eq!(check_transact(&accounts.state(), &proof, &ext_data), TransactVerdict::UnknownRoot);
+}
+
+#[test]
+fn test_check_transact_root_of_another_tree() {
+    let accounts = Accounts::new();
+    let mut root_tree = create_test_account();
+    MerkleTree::append::<Poseidon>([5u8; 32], &mut root_tree).unwrap();
+    let ext_data = test_ext_data(1_000, 10);
+    let proof = test_proof(&root_tree, &ext_data);
+
+    // The root is looked up in the root tree, not in the tree the outputs go to
+    assert_eq!(check_transact(&accounts.state(), &proof, &ext_data), TransactVerdict::UnknownRoot);
+    let state = TransactState { root_tree_account: Some(&root_tree), ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::Valid);
+}
+
+#[test]
+fn test_check_transact_immature_root() {
+    let mut accounts = Accounts::new();
+    accounts.pool_config.maturity_delay = 50;
+    accounts.tree_account.root_history_slots[0] = 1_000;
+
+    let withdrawal = test_ext_data(-1_000, 10);
+    let proof = test_proof(&accounts.tree_account, &withdrawal);
+    let state = TransactState { current_slot: 1_049, ..accounts.state() };
+    assert_eq!(check_transact(&state, &proof, &withdrawal), TransactVerdict::RootNotMature);
+    let state = TransactState { current_slot: 1_050, ..accounts.state() };
+    assert_ne!(check_transact(&state, &proof, &withdrawal), TransactVerdict::RootNotMature);
+
+    // A deposit without a fee moves nothing out of the pool
+    let deposit = test_ext_data(1_000, 0);
+    let proof = test_proof(&accounts.tree_account, &deposit);
+    let state = TransactState { current_slot: 1_000, ..accounts.state() };
+    assert_ne!(check_transact(&state, &proof, &deposit), TransactVerdict::RootNotMature);
+}
+
+#[test]
+fn test_check_transact_expired_root() {
+    let mut accounts = Accounts::new();
+    accounts.pool_config.max_root_age = 100;
+    accounts.tree_account.root_history_slots[0] = 1_000;
+    let ext_data = test_ext_data(1_000, 10);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+
+    let state = TransactState { current_slot: 1_101, ..accounts.state() };
+    assert_eq!(check_transact(&state, &proof, &ext_data), TransactVerdict::RootExpired);
+    let state = TransactState { current_slot: 1_100, ..accounts.state() };
+    assert_ne!(check_transact(&state, &proof, &ext_data), TransactVerdict::RootExpired);
+}
+
+#[test]
+fn test_check_transact_ext_data_hash_mismatch() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(1_000, 10);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+
+    let mut modified_ext_data = ext_data.clone();
+    modified_ext_data.encrypted_output1 = vec![5u8; 16];
+
+    assert_eq!(
+        check_transact(&accounts.state(), &proof, &modified_ext_data),
+        TransactVerdict::ExtDataHashMismatch
+    );
+}
+
+#[test]
+fn test_check_transact_invalid_public_amount() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(-1_000, 10);
+    let mut proof = test_proof(&accounts.tree_account, &ext_data);
+    proof.public_amount = fr_to_bytes(Fr::from(990u64));
+
+    assert_eq!(
+        check_transact(&accounts.state(), &proof, &ext_data),
+        TransactVerdict::InvalidPublicAmountData
+    );
+}
+
+#[test]
+fn test_check_transact_invalid_denomination() {
+    let mut accounts = Accounts::new();
+    accounts.pool_config.denominations[..2].copy_from_slice(&[100_000, 1_000_000]);
+
+    let ext_data = test_ext_data(-150_000, 10);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+    assert_eq!(
+        check_transact(&accounts.state(), &proof, &ext_data),
+        TransactVerdict::InvalidDenomination
+    );
+
+    let ext_data = test_ext_data(-100_000, 10);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+    assert_eq!(
+        check_transact(&accounts.state(), &proof, &ext_data),
+        TransactVerdict::InvalidProof,
+        "A denomination passes on to the proof check"
+    );
+}
+
+#[test]
+fn test_check_transact_transfer_in_denomination_pool() {
+    let mut accounts = Accounts::new();
+    accounts.pool_config.denominations[..2].copy_from_slice(&[100_000, 1_000_000]);
+
+    // Nothing enters or leaves the pool, only the relayer fee is paid from the inputs
+    let ext_data = test_ext_data(0, 5_000);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &ext_data), TransactVerdict::Valid);
+
+    let ext_data = test_ext_data(0, 0);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &ext_data), TransactVerdict::Valid);
+}
+
+#[test]
+fn test_check_transact_invalid_payouts() {
+    let accounts = Accounts::new();
+    let mut ext_data = test_ext_data(-1_000, 10);
+    ext_data.payouts = Some(vec![
+        Payout { recipient: Pubkey::new_unique(), amount: 400 },
+        Payout { recipient: Pubkey::new_unique(), amount: 500 },
+    ]);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+
+    assert_eq!(
+        check_transact(&accounts.state(), &proof, &ext_data),
+        TransactVerdict::InvalidPayouts
+    );
+}
+
+#[test]
+fn test_check_transact_invalid_proof() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(-1_000, 10);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+
+    // The proof is checked before the balance
+    let empty_vault = account_info(Pubkey::new_unique(), 0, false);
+    let state = TransactState { tree_token_account: &empty_vault, ..accounts.state() };
+    assert_eq!(
+        check_transact(&state, &proof, &ext_data),
+        TransactVerdict::InvalidProof
+    );
+}
+
+#[test]
+fn test_check_transact_spent_nullifier_in_either_slot() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(-1_000, 10);
+    let mut proof = test_proof(&accounts.tree_account, &ext_data);
+
+    // Nullifiers are checked first, like in `transact`
+    proof.root = [0u8; 32];
+    let state = TransactState { nullifiers_spent: [[true, false], [false, false]], ..accounts.state() };
+    assert_eq!(check_transact(&state, &proof, &ext_data), TransactVerdict::NullifierAlreadySpent { index: 0 });
+
+    // A note spent as the first input can't be spent again as the second one, and the other
+    // way around
+    let state = TransactState { nullifiers_spent: [[false, false], [true, false]], ..accounts.state() };
+    assert_eq!(check_transact(&state, &proof, &ext_data), TransactVerdict::NullifierAlreadySpent { index: 1 });
+    let state = TransactState { nullifiers_spent: [[false, true], [false, false]], ..accounts.state() };
+    assert_eq!(check_transact(&state, &proof, &ext_data), TransactVerdict::NullifierAlreadySpent { index: 0 });
+}
+
+#[test]
+fn test_check_transact_existing_commitment() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(1_000, 0);
+    let mut proof = test_proof(&accounts.tree_account, &ext_data);
+    proof.root = [0u8; 32];
+
+    let state = TransactState { commitments_exist: [[false, false], [false, true]], ..accounts.state() };
+    assert_eq!(check_transact(&state, &proof, &ext_data), TransactVerdict::CommitmentAlreadyExists { index: 1 });
+}
+
+#[test]
+fn test_check_transact_commitment_swapped_between_slots() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(1_000, 0);
+    let mut proof = test_proof(&accounts.tree_account, &ext_data);
+    proof.root = [0u8; 32];
+
+    // The first output was created earlier as a second output
+    let state = TransactState { commitments_exist: [[false, true], [false, false]], ..accounts.state() };
+    assert_eq!(check_transact(&state, &proof, &ext_data), TransactVerdict::CommitmentAlreadyExists { index: 0 });
+
+    // The second output was created earlier as a first output
+    let state = TransactState { commitments_exist: [[false, false], [true, false]], ..accounts.state() };
+    assert_eq!(check_transact(&state, &proof, &ext_data), TransactVerdict::CommitmentAlreadyExists { index: 1 });
+}
+
+#[test]
+fn test_check_transact_valid() {
+    let accounts = Accounts::new();
+
+    let deposit = test_ext_data(1_000, 0);
+    let proof = test_proof(&accounts.tree_account, &deposit);
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &deposit), TransactVerdict::Valid);
+
+    let withdrawal = test_ext_data(-1_000, 10);
+    let proof = test_proof(&accounts.tree_account, &withdrawal);
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &withdrawal), TransactVerdict::Valid);
+}
+
+#[test]
+fn test_check_transact_deposit_limit_exceeded() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(1_000_000_001, 0);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &ext_data), TransactVerdict::DepositLimitExceeded);
+}
+
+#[test]
+fn test_check_transact_insufficient_funds() {
+    let accounts = Accounts::new();
+
+    let withdrawal = test_ext_data(-(TREE_TOKEN_BALANCE as i64) - 1, 0);
+    let proof = test_proof(&accounts.tree_account, &withdrawal);
+    assert_eq!(
+        check_with_valid_proof(&accounts.state(), &proof, &withdrawal),
+        TransactVerdict::InsufficientFundsForWithdrawal
+    );
+
+    // The fee is paid from what is left after the withdrawal
+    let withdrawal = test_ext_data(-(TREE_TOKEN_BALANCE as i64) + 5, 10);
+    let proof = test_proof(&accounts.tree_account, &withdrawal);
+    assert_eq!(
+        check_with_valid_proof(&accounts.state(), &proof, &withdrawal),
+        TransactVerdict::InsufficientFundsForFee
+    );
+}
+
+#[test]
+fn test_check_transact_recipient_mismatch() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(-1_000, 0);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+
+    // The relayer can't pay a recipient other than the committed one
+    let other_recipient = account_info(Pubkey::new_unique(), 1_000_000, false);
+    let state = TransactState { recipient: &other_recipient, ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::RecipientMismatch);
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &ext_data), TransactVerdict::Valid);
+}
+
+#[test]
+fn test_check_transact_invalid_recipient() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(-1_000, 0);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+
+    let executable = account_info(RECIPIENT, 1_000_000, true);
+    let state = TransactState { recipient: &executable, ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::InvalidRecipient);
+
+    let new_account = account_info(RECIPIENT, 0, false);
+    let state = TransactState { recipient: &new_account, ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::RecipientNotRentExempt);
+}
+
+#[test]
+fn test_check_transact_payout_accounts() {
+    let accounts = Accounts::new();
+    let (first, second) = (account_info(Pubkey::new_unique(), 1_000_000, false), account_info(Pubkey::new_unique(), 1_000_000, false));
+    let mut ext_data = test_ext_data(-1_000, 10);
+    ext_data.payouts = Some(vec![
+        Payout { recipient: *first.key, amount: 400 },
+        Payout { recipient: *second.key, amount: 600 },
+    ]);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+
+    let swapped = [second.clone(), first.clone()];
+    let state = TransactState { remaining_accounts: &swapped, ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::InvalidPayoutAccounts);
+
+    let in_order = [first, second];
+    let state = TransactState { remaining_accounts: &in_order, ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::Valid);
+}
+
+#[test]
+fn test_check_transact_withdraw_call() {
+    let accounts = Accounts::new();
+    let target = account_info(Pubkey::new_unique(), 1_000_000, true);
+    let target_account = account_info(Pubkey::new_unique(), 1_000_000, false);
+    let mut ext_data = test_ext_data(-1_000, 0);
+    ext_data.call = Some(WithdrawCall {
+        program_id: *target.key,
+        accounts: vec![WithdrawCallAccount { pubkey: *target_account.key, is_writable: true }],
+        data: vec![1],
+    });
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+
+    // The call must withdraw, a deposit can't invoke anything
+    let mut deposit = ext_data.clone();
+    deposit.ext_amount = 1_000;
+    let deposit_proof = test_proof(&accounts.tree_account, &deposit);
+    assert_eq!(check_transact(&accounts.state(), &deposit_proof, &deposit), TransactVerdict::InvalidWithdrawCall);
+
+    // The committed accounts can't be substituted
+    let other_account = account_info(Pubkey::new_unique(), 1_000_000, false);
+    let substituted = [target.clone(), other_account];
+    let state = TransactState { remaining_accounts: &substituted, ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::InvalidWithdrawCall);
+
+    let committed = [target, target_account];
+    let state = TransactState { remaining_accounts: &committed, ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::Valid);
+}
+
+#[test]
+fn test_check_transact_commitment_queue() {
+    let mut accounts = Accounts::new();
+    let ext_data = test_ext_data(1_000, 0);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+    let mut queue = unsafe { MaybeUninit::<CommitmentQueueAccount>::zeroed().assume_init() };
+
+    // The queue must be passed exactly when the tree has one
+    let state = TransactState { commitment_queue: Some(&queue), ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::InvalidCommitmentQueue);
+
+    accounts.tree_account.queue_enabled = 1;
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &ext_data), TransactVerdict::InvalidCommitmentQueue);
+    let state = TransactState { commitment_queue: Some(&queue), ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::Valid);
+
+    queue.len = COMMITMENT_QUEUE_SIZE as u64 - 1;
+    let state = TransactState { commitment_queue: Some(&queue), ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::CommitmentQueueFull);
+}
+
+#[test]
+fn test_check_transact_full_tree() {
+    let mut accounts = Accounts::new();
+    let ext_data = test_ext_data(1_000, 0);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+
+    // Both outputs need a leaf
+    accounts.tree_account.next_index = MAX_LEAVES - 1;
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &ext_data), TransactVerdict::MerkleTreeFull);
+
+    accounts.tree_account.next_index = MAX_LEAVES - 2;
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &ext_data), TransactVerdict::Valid);
+}
+
+#[test]
+fn test_check_transact_follows_pool_config_in_every_shard() {
+    let mut accounts = Accounts::new();
+    let mut shard1 = create_test_account();
+    shard1.tree_index = 1;
+    let tree_set = create_tree_set();
+
+    let ext_data = test_ext_data(-150_000, 10);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+    let shard1_state = TransactState { tree_account: &shard1, tree_set: Some(&tree_set), ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &ext_data), TransactVerdict::Valid);
+    assert_eq!(check_with_valid_proof(&shard1_state, &proof, &ext_data), TransactVerdict::Valid);
+
+    // The update goes through one account, and no shard can be used to avoid it
+    accounts.pool_config.denominations[..2].copy_from_slice(&[100_000, 1_000_000]);
+    let shard1_state = TransactState { tree_account: &shard1, tree_set: Some(&tree_set), ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&accounts.state(), &proof, &ext_data), TransactVerdict::InvalidDenomination);
+    assert_eq!(check_with_valid_proof(&shard1_state, &proof, &ext_data), TransactVerdict::InvalidDenomination);
+}
+
+#[test]
+fn test_check_transact_inactive_tree() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(1_000, 0);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+    let mut tree_set = create_tree_set();
+    let mut shard1 = create_test_account();
+    shard1.tree_index = 1;
+
+    // Outputs only go to a shard when the tree set lists it
+    let state = TransactState { tree_account: &shard1, ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::InvalidTreeIndex);
+    let state = TransactState { tree_account: &shard1, tree_set: Some(&tree_set), ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::Valid);
+
+    // A rolled over shard doesn't take outputs anymore
+    TreeSet::rollover(&mut tree_set, 1).unwrap();
+    let state = TransactState { tree_account: &shard1, tree_set: Some(&tree_set), ..accounts.state() };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::InvalidTreeIndex);
+}
+
+#[test]
+fn test_check_transact_spends_notes_of_another_tree() {
+    let accounts = Accounts::new();
+    let ext_data = test_ext_data(-1_000, 10);
+    let proof = test_proof(&accounts.tree_account, &ext_data);
+    let tree_set = create_tree_set();
+    let mut shard1 = create_test_account();
+    shard1.tree_index = 1;
+
+    // Notes of tree 0 are withdrawn through shard 1 from the vault every tree shares
+    let state = TransactState {
+        tree_account: &shard1,
+        root_tree_account: Some(&accounts.tree_account),
+        tree_set: Some(&tree_set),
+        ..accounts.state()
+    };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::Valid);
+}
+
+
+#[test]
+fn test_check_transact_association_proof() {
+    let accounts = Accounts::new();
+    let provider = Pubkey::new_unique();
+    let (association_set_key, bump) = Pubkey::find_program_address(&[b"association_set", provider.as_ref()], &zkcash::ID);
+    let association = prove_test_association(association_set_key, 3);
+
+    let mut association_set = AssociationSetAccount {
+        provider: Pubkey::default(),
+        root_history: [[0u8; 32]; ASSOCIATION_ROOT_HISTORY_SIZE],
+        root_index: 0,
+        root_count: 0,
+        bump: 0,
+    };
+    AssociationSet::initialize(&mut association_set, provider, bump);
+    let association_verifying_key = AssociationVerifyingKeyAccount {
+        authority: Pubkey::default(),
+        verifying_key: association.verifying_key,
+        bump: 0,
+    };
+
+    let mut ext_data = test_ext_data(-1_000, 10);
+    ext_data.association = Some(association.proof.clone());
+    let mut proof = test_proof(&accounts.tree_account, &ext_data);
+    proof.input_nullifiers = association.input_nullifiers;
+
+    assert_eq!(
+        check_with_valid_proof(&accounts.state(), &proof, &ext_data),
+        TransactVerdict::InvalidAssociationAccounts,
+        "The association set and verifying key must be passed"
+    );
+
+    let state = TransactState {
+        association_set: Some(&association_set),
+        association_verifying_key: Some(&association_verifying_key),
+        ..accounts.state()
+    };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::UnknownAssociationRoot);
+
+    AssociationSet::update_root(&mut association_set, association.proof.root).unwrap();
+    let state = TransactState {
+        association_set: Some(&association_set),
+        association_verifying_key: Some(&association_verifying_key),
+        ..accounts.state()
+    };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::Valid);
+
+    // The association proof is bound to the nullifiers of the transaction proof
+    let mut other_inputs = proof.clone();
+    other_inputs.input_nullifiers = [[1u8; 32], [2u8; 32]];
+    assert_eq!(check_with_valid_proof(&state, &other_inputs, &ext_data), TransactVerdict::InvalidAssociationProof);
+
+    // A set of another provider doesn't stand in for the one named in the ext data
+    let mut other_set = association_set.clone();
+    other_set.provider = Pubkey::new_unique();
+    let state = TransactState { association_set: Some(&other_set), ..state };
+    assert_eq!(check_with_valid_proof(&state, &proof, &ext_data), TransactVerdict::InvalidAssociationAccounts);
+}
\ No newline at end of file
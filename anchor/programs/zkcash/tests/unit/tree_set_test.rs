@@ -0,0 +1,101 @@
+use anchor_lang::prelude::Pubkey;
+use light_hasher::{Hasher, Poseidon};
+use std::mem::MaybeUninit;
+use zkcash::{
+    merkle_tree::DEFAULT_HEIGHT,
+    tree_set::{TreeSet, MAX_TREE_SHARDS},
+    MerkleTreeAccount, TreeSetAccount,
+};
+
+fn create_tree_set() -> TreeSetAccount {
+    let mut tree_set = TreeSetAccount {
+        authority: Pubkey::default(),
+        active_trees: [0; MAX_TREE_SHARDS],
+        shard_count: 0,
+        tree_count: 0,
+        bump: 0,
+    };
+    TreeSet::initialize_if_empty(&mut tree_set, Pubkey::new_unique(), 255);
+    tree_set
+}
+
+#[test]
+fn test_initialize_if_empty_starts_with_tree_zero() {
+    let mut tree_set = create_tree_set();
+    assert_eq!(TreeSet::active_trees(&tree_set), &[0]);
+    assert_eq!(tree_set.tree_count, 1);
+
+    // A second initialization must not reset an existing tree set
+    TreeSet::add_shard(&mut tree_set).unwrap();
+    let authority = tree_set.authority;
+    TreeSet::initialize_if_empty(&mut tree_set, Pubkey::new_unique(), 1);
+    assert_eq!(TreeSet::active_trees(&tree_set), &[0, 1]);
+    assert_eq!(tree_set.authority, authority);
+}
+
+#[test]
+fn test_add_shard() {
+    let mut tree_set = create_tree_set();
+
+    for expected_index in 1..MAX_TREE_SHARDS as u32 {
+        assert_eq!(TreeSet::add_shard(&mut tree_set).unwrap(), expected_index);
+        assert!(TreeSet::is_active(&tree_set, expected_index));
+    }
+    assert_eq!(tree_set.shard_count as usize, MAX_TREE_SHARDS);
+
+    assert!(TreeSet::add_shard(&mut tree_set).is_err(), "Should reject more than MAX_TREE_SHARDS shards");
+    assert_eq!(tree_set.tree_count as usize, MAX_TREE_SHARDS);
+}
+
+#[test]
+fn test_rollover_replaces_full_shard() {
+    let mut tree_set = create_tree_set();
+    TreeSet::add_shard(&mut tree_set).unwrap();
+    TreeSet::add_shard(&mut tree_set).unwrap();
+
+    assert_eq!(TreeSet::rollover(&mut tree_set, 1).unwrap(), 3);
+    assert_eq!(TreeSet::active_trees(&tree_set), &[0, 3, 2]);
+    assert_eq!(tree_set.tree_count, 4);
+
+    // A full tree can only be rolled over once
+    assert!(TreeSet::rollover(&mut tree_set, 1).is_err());
+    // Trees that were never created can't be rolled over
+    assert!(TreeSet::rollover(&mut tree_set, 7).is_err());
+    assert_eq!(tree_set.tree_count, 4);
+}
+
+#[test]
+fn test_initialize_tree() {
+    let mut tree_account = unsafe { MaybeUninit::<MerkleTreeAccount>::zeroed().assume_init() };
+    let mut source_tree = unsafe { MaybeUninit::<MerkleTreeAccount>::zeroed().assume_init() };
+    let authority = Pubkey::new_unique();
+    source_tree.authority = authority;
+    source_tree.max_deposit_amount = 5_000;
+
+    TreeSet::initialize_tree::<Poseidon>(&mut tree_account, &source_tree, 3, 254, 42);
+
+    assert_eq!(tree_account.authority, authority);
+    assert_eq!(tree_account.tree_index, 3);
+    assert_eq!(tree_account.bump, 254);
+    assert_eq!(tree_account.max_deposit_amount, 5_000);
+    assert_eq!(tree_account.root_history_slots[0], 42);
+    assert_eq!(tree_account.next_index, 0);
+    assert_eq!(tree_account.root, Poseidon::zero_bytes()[DEFAULT_HEIGHT]);
+}
+
+#[test]
+fn test_accepts_outputs_only_for_active_trees() {
+    // Without a tree set the pool only has tree 0
+    assert!(TreeSet::accepts_outputs(None, 0));
+    assert!(!TreeSet::accepts_outputs(None, 1));
+
+    let mut tree_set = create_tree_set();
+    TreeSet::add_shard(&mut tree_set).unwrap();
+    assert!(TreeSet::accepts_outputs(Some(&tree_set), 0));
+    assert!(TreeSet::accepts_outputs(Some(&tree_set), 1));
+    assert!(!TreeSet::accepts_outputs(Some(&tree_set), 2), "Tree 2 doesn't exist yet");
+
+    assert_eq!(TreeSet::rollover(&mut tree_set, 1).unwrap(), 2);
+    assert!(!TreeSet::accepts_outputs(Some(&tree_set), 1), "A rolled over tree takes no outputs");
+    assert!(TreeSet::accepts_outputs(Some(&tree_set), 2));
+}
@@ -1,10 +1,12 @@
 use num_bigint::BigUint;
-use ark_bn254;
 use ark_ff::{PrimeField, BigInteger};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
 use std::ops::Neg;
 use ark_bn254::Fr;
-use zkcash::{groth16::{is_less_than_bn254_field_size_be, Groth16Verifyingkey}, utils::{change_endianness, check_public_amount, verify_proof}, Proof};
+use zkcash::{groth16::{is_less_than_bn254_field_size_be, Groth16Verifyingkey}, utils::{change_endianness, has_auditor_outputs, check_ext_data_hash, check_denomination, check_denomination_set, check_payouts, check_shielded_account_keys, check_public_amount, existing_output, spent_input, invoke_withdraw_call, transfer_lamports_from_pda, transfer_payouts, verify_proof, view_tag, envelope_version, ENVELOPE_MAGIC, NOTE_CIPHERTEXT_VERSION, VIEW_TAG_OFFSET}, AssociationProof, AuditorOutputs, ExtData, Payout, Proof, WithdrawCall, WithdrawCallAccount, MAX_DENOMINATIONS, MAX_PAYOUTS};
+use anchor_lang::prelude::{AccountInfo, Pubkey, Rent};
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::AnchorSerialize;
 
 type G1 = ark_bn254::g1::G1Affine;
 
@@ -138,14 +140,6 @@ fn u64_to_bytes(value: u64) -> [u8; 32] {
     fr_to_bytes(Fr::from(value))
 }
 
-// Helper function to create a byte array representing a very large i64 value (won't fit in i64)
-fn too_large_for_i64_to_bytes() -> [u8; 32] {
-    let mut bytes = [0u8; 32];
-    // Set to a value larger than i64::MAX in the last 8 bytes (big-endian)
-    bytes[24..32].copy_from_slice(&(i64::MAX as u64 + 1).to_be_bytes());
-    bytes
-}
-
 #[test]
 fn test_check_public_amount() {
     let ext_amount = 100;
@@ -186,6 +180,14 @@ fn test_check_public_amount_fee_larger_than_ext_amount() {
     assert!(!result, "Function should return false when fee > ext_amount");
 }
 
+#[test]
+fn test_check_public_amount_transfer_inside_pool() {
+    // Nothing enters or leaves the pool, the fee is paid from the inputs
+    assert!(check_public_amount(0, 10, fr_to_bytes(-Fr::from(10u64))));
+    assert!(check_public_amount(0, 0, u64_to_bytes(0)));
+    assert!(!check_public_amount(0, 10, u64_to_bytes(0)), "The fee has to come out of the inputs");
+}
+
 #[test]
 fn test_check_public_amount_invalid_ext_amount() {
     let ext_amount = i64::MAX;
@@ -457,7 +459,7 @@ fn negated_proof_a_verification_should_not_succeed() {
         output_commitments: [PUBLIC_INPUTS[3], PUBLIC_INPUTS[4]],
         public_amount: PUBLIC_INPUTS[5],
         ext_data_hash: PUBLIC_INPUTS[6],
-        proof_a: proof_a,
+        proof_a,
         proof_b: PROOF_B,
         proof_c: PROOF_C,
     };
@@ -631,4 +633,348 @@ fn test_check_public_amount_max_safe_values() {
     
     let result = check_public_amount(ext_amount, fee, public_amount_bytes);
     assert!(result, "Should handle maximum safe positive values");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_transfer_lamports_from_pda_to_program_owned_account() {
+    let program_id = Pubkey::new_unique();
+    let vault_program_id = Pubkey::new_unique();
+    let (from_key, to_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (mut from_lamports, mut to_lamports) = (10_000_000u64, 2_000_000u64);
+    let (mut from_data, mut to_data) = (vec![0u8; 41], vec![0u8; 64]);
+    let from = AccountInfo::new(&from_key, false, true, &mut from_lamports, &mut from_data, &program_id, false, 0);
+    let to = AccountInfo::new(&to_key, false, true, &mut to_lamports, &mut to_data, &vault_program_id, false, 0);
+
+    transfer_lamports_from_pda(&from, &to, 1_000_000, &Rent::default()).unwrap();
+
+    assert_eq!(from.lamports(), 9_000_000);
+    assert_eq!(to.lamports(), 3_000_000);
+}
+
+#[test]
+fn test_transfer_lamports_from_pda_to_new_account_below_rent_exemption() {
+    let program_id = Pubkey::new_unique();
+    let system_program_id = Pubkey::default();
+    let (from_key, to_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (mut from_lamports, mut to_lamports) = (10_000_000u64, 0u64);
+    let (mut from_data, mut to_data) = (vec![0u8; 41], vec![]);
+    let from = AccountInfo::new(&from_key, false, true, &mut from_lamports, &mut from_data, &program_id, false, 0);
+    let to = AccountInfo::new(&to_key, false, true, &mut to_lamports, &mut to_data, &system_program_id, false, 0);
+
+    let rent = Rent::default();
+    let minimum_balance = rent.minimum_balance(0);
+    assert!(transfer_lamports_from_pda(&from, &to, minimum_balance - 1, &rent).is_err(),
+        "Should reject creating a recipient below the rent-exempt minimum");
+    assert_eq!(to.lamports(), 0);
+
+    transfer_lamports_from_pda(&from, &to, minimum_balance, &rent).unwrap();
+    assert_eq!(to.lamports(), minimum_balance);
+}
+
+#[test]
+fn test_transfer_lamports_from_pda_rejects_invalid_recipients() {
+    let program_id = Pubkey::new_unique();
+    let (from_key, program_key, to_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let (mut from_lamports, mut program_lamports, mut to_lamports) = (10_000_000u64, 1_000_000u64, 1_000_000u64);
+    let (mut from_data, mut program_data, mut to_data) = (vec![0u8; 41], vec![0u8; 36], vec![]);
+    let from = AccountInfo::new(&from_key, false, true, &mut from_lamports, &mut from_data, &program_id, false, 0);
+    let executable = AccountInfo::new(&program_key, false, true, &mut program_lamports, &mut program_data, &program_id, true, 0);
+    let system_program_id = Pubkey::default();
+    let to = AccountInfo::new(&to_key, false, true, &mut to_lamports, &mut to_data, &system_program_id, false, 0);
+
+    assert!(transfer_lamports_from_pda(&from, &executable, 1_000, &Rent::default()).is_err(),
+        "Should reject executable recipients");
+    assert!(transfer_lamports_from_pda(&from, &from, 1_000, &Rent::default()).is_err(),
+        "Should reject paying the source account to itself");
+    assert!(transfer_lamports_from_pda(&from, &to, 20_000_000, &Rent::default()).is_err(),
+        "Should reject transfers larger than the source balance");
+    assert_eq!(from.lamports(), 10_000_000);
+}
+
+#[test]
+fn test_invoke_withdraw_call_rejects_invalid_target() {
+    let loader_id = Pubkey::new_unique();
+    let (target_key, other_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (mut target_lamports, mut other_lamports) = (1_000_000u64, 1_000_000u64);
+    let (mut target_data, mut other_data) = (vec![], vec![]);
+    let target = AccountInfo::new(&target_key, false, false, &mut target_lamports, &mut target_data, &loader_id, true, 0);
+    let other = AccountInfo::new(&other_key, false, false, &mut other_lamports, &mut other_data, &loader_id, false, 0);
+
+    let call = WithdrawCall { program_id: target_key, accounts: vec![], data: vec![1, 2, 3] };
+    assert!(invoke_withdraw_call(&call, &[]).is_err(),
+        "Should require the target program as the first remaining account");
+    assert!(invoke_withdraw_call(&call, &[other.clone(), target.clone()]).is_err(),
+        "Should reject a first remaining account that is not the committed program");
+
+    let non_executable_call = WithdrawCall { program_id: other_key, accounts: vec![], data: vec![] };
+    assert!(invoke_withdraw_call(&non_executable_call, &[other]).is_err(),
+        "Should reject non-executable targets");
+
+    let program_id = zkcash::ID;
+    let (mut program_lamports, mut program_data) = (1_000_000u64, vec![]);
+    let program = AccountInfo::new(&program_id, false, false, &mut program_lamports, &mut program_data, &loader_id, true, 0);
+    let self_call = WithdrawCall { program_id, accounts: vec![], data: vec![] };
+    assert!(invoke_withdraw_call(&self_call, &[program, target]).is_err(),
+        "Should reject calling back into zkcash");
+}
+
+#[test]
+fn test_invoke_withdraw_call_rejects_substituted_accounts() {
+    let loader_id = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let (target_key, vault_key, other_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let (mut target_lamports, mut vault_lamports, mut other_lamports) = (1_000_000u64, 1_000_000u64, 1_000_000u64);
+    let (mut target_data, mut vault_data, mut other_data) = (vec![], vec![], vec![]);
+    let target = AccountInfo::new(&target_key, false, false, &mut target_lamports, &mut target_data, &loader_id, true, 0);
+    let vault = AccountInfo::new(&vault_key, false, true, &mut vault_lamports, &mut vault_data, &owner, false, 0);
+    let other = AccountInfo::new(&other_key, false, true, &mut other_lamports, &mut other_data, &owner, false, 0);
+
+    let call = WithdrawCall {
+        program_id: target_key,
+        accounts: vec![WithdrawCallAccount { pubkey: vault_key, is_writable: true }],
+        data: vec![1],
+    };
+    assert!(invoke_withdraw_call(&call, &[target.clone(), other.clone()]).is_err(),
+        "Should reject an account that is not the committed one");
+    assert!(invoke_withdraw_call(&call, std::slice::from_ref(&target)).is_err(),
+        "Should reject missing accounts");
+    assert!(invoke_withdraw_call(&call, &[target.clone(), vault.clone(), other]).is_err(),
+        "Should reject extra accounts");
+
+    let mut readonly_vault = vault.clone();
+    readonly_vault.is_writable = false;
+    assert!(invoke_withdraw_call(&call, &[target.clone(), readonly_vault]).is_err(),
+        "Should reject a read-only account committed as writable");
+
+    let mut signing_vault = vault;
+    signing_vault.is_signer = true;
+    assert!(invoke_withdraw_call(&call, &[target, signing_vault]).is_err(),
+        "Should reject forwarding a signer");
+}
+
+#[test]
+fn test_spent_input_checks_both_slots() {
+    assert_eq!(spent_input([[false; 2]; 2]), None);
+    assert_eq!(spent_input([[true, false], [false, false]]), Some(0));
+    assert_eq!(spent_input([[false, false], [false, true]]), Some(1));
+    // The same nullifier spent through the other slot is still spent
+    assert_eq!(spent_input([[false, true], [false, false]]), Some(0));
+    assert_eq!(spent_input([[false, false], [true, false]]), Some(1));
+}
+
+#[test]
+fn test_existing_output_checks_both_slots() {
+    assert_eq!(existing_output([[false; 2]; 2]), None);
+    assert_eq!(existing_output([[true, false], [false, false]]), Some(0));
+    assert_eq!(existing_output([[false, false], [false, true]]), Some(1));
+    // A commitment created through the other slot still exists
+    assert_eq!(existing_output([[false, true], [false, false]]), Some(0));
+    assert_eq!(existing_output([[false, false], [true, false]]), Some(1));
+}
+
+#[test]
+fn test_has_auditor_outputs() {
+    let mut ext_data = ExtData {
+        recipient: Pubkey::new_unique(),
+        ext_amount: 1_000,
+        encrypted_output1: vec![1],
+        encrypted_output2: vec![2],
+        fee: 0,
+        call: None,
+        auditor_outputs: None,
+        payouts: None,
+        association: None,
+    };
+    let auditor_key = [9u8; 32];
+
+    assert!(has_auditor_outputs([0u8; 32], &ext_data), "Pools without an auditor don't need auditor outputs");
+    assert!(!has_auditor_outputs(auditor_key, &ext_data));
+
+    ext_data.auditor_outputs = Some(AuditorOutputs { encrypted_output1: vec![3], encrypted_output2: vec![] });
+    assert!(!has_auditor_outputs(auditor_key, &ext_data), "Every output needs an auditor ciphertext");
+
+    ext_data.auditor_outputs = Some(AuditorOutputs { encrypted_output1: vec![3], encrypted_output2: vec![4] });
+    assert!(has_auditor_outputs(auditor_key, &ext_data));
+}
+
+#[test]
+fn test_check_denomination() {
+    let mut denominations = [0u64; MAX_DENOMINATIONS];
+    assert!(check_denomination(&denominations, 1_234), "Pools without denominations take any amount");
+
+    denominations[..3].copy_from_slice(&[100_000_000, 1_000_000_000, 10_000_000_000]);
+    assert!(check_denomination(&denominations, 1_000_000_000));
+    assert!(check_denomination(&denominations, 0), "Transfers inside the pool move no amount");
+    assert!(!check_denomination(&denominations, 1_234));
+    assert!(!check_denomination(&denominations, 2_000_000_000));
+}
+
+#[test]
+fn test_check_denomination_set() {
+    assert!(check_denomination_set(&[]));
+    assert!(check_denomination_set(&[100, 1_000, 10_000]));
+    assert!(!check_denomination_set(&[0, 1_000]), "Denominations must be non-zero");
+    assert!(!check_denomination_set(&[1_000, 100]), "Denominations must be sorted");
+    assert!(!check_denomination_set(&[100, 100]), "Denominations must be distinct");
+    assert!(!check_denomination_set(&[1, 2, 3, 4, 5, 6, 7, 8, 9]));
+}
+
+fn payout_ext_data(ext_amount: i64, amounts: &[u64]) -> ExtData {
+    ExtData {
+        recipient: Pubkey::new_unique(),
+        ext_amount,
+        encrypted_output1: vec![1],
+        encrypted_output2: vec![2],
+        fee: 0,
+        call: None,
+        auditor_outputs: None,
+        payouts: Some(amounts.iter().map(|amount| Payout { recipient: Pubkey::new_unique(), amount: *amount }).collect()),
+        association: None,
+    }
+}
+
+#[test]
+fn test_check_payouts() {
+    assert!(check_payouts(&payout_ext_data(-600, &[100, 200, 300])));
+
+    let mut without_payouts = payout_ext_data(1_000, &[]);
+    without_payouts.payouts = None;
+    assert!(check_payouts(&without_payouts), "Transactions without payouts are unaffected");
+
+    assert!(!check_payouts(&payout_ext_data(-600, &[100, 200])), "Payouts must add up to the withdrawal");
+    assert!(!check_payouts(&payout_ext_data(-600, &[100, 200, 400])), "Payouts must add up to the withdrawal");
+    assert!(!check_payouts(&payout_ext_data(600, &[100, 200, 300])), "Payouts require a withdrawal");
+    assert!(!check_payouts(&payout_ext_data(-600, &[600, 0])), "Payouts must be non-zero");
+    assert!(!check_payouts(&payout_ext_data(-1, &[])));
+    assert!(!check_payouts(&payout_ext_data(-1, &[u64::MAX, 2])), "Overflowing payouts are rejected");
+
+    let amounts = vec![1u64; MAX_PAYOUTS + 1];
+    assert!(!check_payouts(&payout_ext_data(-(MAX_PAYOUTS as i64 + 1), &amounts)));
+    assert!(check_payouts(&payout_ext_data(-(MAX_PAYOUTS as i64), &amounts[..MAX_PAYOUTS])));
+
+    let mut with_call = payout_ext_data(-600, &[100, 200, 300]);
+    with_call.call = Some(WithdrawCall { program_id: Pubkey::new_unique(), accounts: vec![], data: vec![] });
+    assert!(!check_payouts(&with_call), "Payouts can't be combined with a withdraw call");
+}
+
+#[test]
+fn test_transfer_payouts() {
+    let program_id = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let (from_key, first_key, second_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let (mut from_lamports, mut first_lamports, mut second_lamports) = (10_000_000u64, 1_000_000u64, 1_000_000u64);
+    let (mut from_data, mut first_data, mut second_data) = (vec![0u8; 41], vec![], vec![]);
+    let from = AccountInfo::new(&from_key, false, true, &mut from_lamports, &mut from_data, &program_id, false, 0);
+    let first = AccountInfo::new(&first_key, false, true, &mut first_lamports, &mut first_data, &owner, false, 0);
+    let second = AccountInfo::new(&second_key, false, true, &mut second_lamports, &mut second_data, &owner, false, 0);
+    let payouts = vec![
+        Payout { recipient: first_key, amount: 2_000_000 },
+        Payout { recipient: second_key, amount: 3_000_000 },
+    ];
+    let rent = Rent::default();
+
+    assert!(transfer_payouts(&from, &payouts, std::slice::from_ref(&first), &rent).is_err(),
+        "Should require an account for every payout");
+    assert!(transfer_payouts(&from, &payouts, &[second.clone(), first.clone()], &rent).is_err(),
+        "Should require the accounts in the order of the payouts");
+    assert_eq!(from.lamports(), 10_000_000);
+
+    transfer_payouts(&from, &payouts, &[first.clone(), second.clone()], &rent).unwrap();
+    assert_eq!(from.lamports(), 5_000_000);
+    assert_eq!(first.lamports(), 3_000_000);
+    assert_eq!(second.lamports(), 4_000_000);
+}
+
+#[test]
+fn test_transfer_payouts_rejects_readonly_recipients() {
+    let program_id = Pubkey::new_unique();
+    let (from_key, to_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (mut from_lamports, mut to_lamports) = (10_000_000u64, 1_000_000u64);
+    let (mut from_data, mut to_data) = (vec![0u8; 41], vec![]);
+    let from = AccountInfo::new(&from_key, false, true, &mut from_lamports, &mut from_data, &program_id, false, 0);
+    let to = AccountInfo::new(&to_key, false, false, &mut to_lamports, &mut to_data, &program_id, false, 0);
+
+    let payouts = vec![Payout { recipient: to_key, amount: 1_000 }];
+    assert!(transfer_payouts(&from, &payouts, &[to], &Rent::default()).is_err());
+}
+
+#[test]
+fn test_check_shielded_account_keys() {
+    let mut shielded_pubkey = [0u8; 32];
+    shielded_pubkey[31] = 1;
+    let encryption_key = [7u8; 32];
+
+    assert!(check_shielded_account_keys(shielded_pubkey, encryption_key));
+    assert!(!check_shielded_account_keys([0u8; 32], encryption_key));
+    assert!(!check_shielded_account_keys(shielded_pubkey, [0u8; 32]));
+    assert!(!check_shielded_account_keys([0xffu8; 32], encryption_key), "Shielded pubkey must be a field element");
+}
+
+// Also pinned in scripts/__tests__/ext_data_hash.test.ts, so the TypeScript schema can't
+// drift from the Rust struct
+const EXT_DATA_HASH_FIXTURE: &str = "25f90ce92ce208d3aa36095cc5e01e70c87c13156e959c7617a981f3285a59fb";
+
+#[test]
+fn test_ext_data_hash_fixture() {
+    // Every optional field is set, so the fixture covers the whole layout
+    let ext_data = ExtData {
+        recipient: Pubkey::new_from_array([1u8; 32]),
+        ext_amount: -600,
+        encrypted_output1: vec![2, 3],
+        encrypted_output2: vec![4],
+        fee: 25,
+        call: Some(WithdrawCall {
+            program_id: Pubkey::new_from_array([5u8; 32]),
+            accounts: vec![
+                WithdrawCallAccount { pubkey: Pubkey::new_from_array([6u8; 32]), is_writable: true },
+                WithdrawCallAccount { pubkey: Pubkey::new_from_array([7u8; 32]), is_writable: false },
+            ],
+            data: vec![8, 9],
+        }),
+        auditor_outputs: Some(AuditorOutputs { encrypted_output1: vec![10], encrypted_output2: vec![11, 12] }),
+        payouts: Some(vec![
+            Payout { recipient: Pubkey::new_from_array([13u8; 32]), amount: 200 },
+            Payout { recipient: Pubkey::new_from_array([14u8; 32]), amount: 400 },
+        ]),
+        association: Some(AssociationProof {
+            association_set: Pubkey::new_from_array([15u8; 32]),
+            root: [16u8; 32],
+            proof_a: [17u8; 64],
+            proof_b: [18u8; 128],
+            proof_c: [19u8; 64],
+        }),
+    };
+
+    let mut serialized = Vec::new();
+    ext_data.serialize(&mut serialized).unwrap();
+    let ext_data_hash = hash(&serialized).to_bytes();
+    let hex: String = ext_data_hash.iter().map(|byte| format!("{byte:02x}")).collect();
+    assert_eq!(hex, EXT_DATA_HASH_FIXTURE);
+
+    // The proof carries the hash as a big-endian field element
+    let mut proof_hash = ext_data_hash;
+    proof_hash.reverse();
+    assert!(check_ext_data_hash(&ext_data, proof_hash));
+}
+
+#[test]
+fn test_view_tag() {
+    let mut encrypted_output = vec![0u8; 80];
+    encrypted_output[..ENVELOPE_MAGIC.len()].copy_from_slice(&ENVELOPE_MAGIC);
+    encrypted_output[ENVELOPE_MAGIC.len()] = NOTE_CIPHERTEXT_VERSION;
+    encrypted_output[VIEW_TAG_OFFSET] = 42;
+    assert_eq!(envelope_version(&encrypted_output), Some(NOTE_CIPHERTEXT_VERSION));
+    assert_eq!(view_tag(&encrypted_output), Some(42));
+
+    assert_eq!(view_tag(&encrypted_output[..VIEW_TAG_OFFSET]), None, "Truncated envelope has no view tag");
+    assert_eq!(view_tag(&[]), None);
+
+    encrypted_output[ENVELOPE_MAGIC.len()] = NOTE_CIPHERTEXT_VERSION + 1;
+    assert_eq!(view_tag(&encrypted_output), None, "Unknown versions have no view tag");
+
+    // A legacy output whose random IV starts with the version byte isn't an envelope
+    let mut legacy_output = vec![0xa5u8; 80];
+    legacy_output[0] = NOTE_CIPHERTEXT_VERSION;
+    assert_eq!(envelope_version(&legacy_output), None);
+    assert_eq!(view_tag(&legacy_output), None);
+}
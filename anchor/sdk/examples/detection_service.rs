@@ -0,0 +1,56 @@
+//! Reference scanning service: flags the outputs of a detection key in a local snapshot.
+//!
+//! Usage: `cargo run -p zkcash-sdk --example detection_service -- <snapshot> <detection key hex>`
+//!
+//! The snapshot holds the commitment accounts of the pool in the format of
+//! `detection::write_snapshot`. The wallet downloads and trial-decrypts only the printed
+//! tree and leaf indices.
+use std::fs::File;
+use std::io::BufReader;
+use std::process::ExitCode;
+use zkcash_sdk::detection::{DetectionKey, DetectionService};
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let [_, snapshot_path, detection_key] = &args[..] else {
+        eprintln!("Usage: detection_service <snapshot> <detection key hex>");
+        return ExitCode::FAILURE;
+    };
+
+    let Some(detection_key) = parse_hex(detection_key).and_then(|bytes| DetectionKey::from_bytes(&bytes).ok()) else {
+        eprintln!("Invalid detection key");
+        return ExitCode::FAILURE;
+    };
+    let service = match File::open(snapshot_path).map(BufReader::new) {
+        Ok(reader) => match DetectionService::from_snapshot(reader) {
+            Ok(service) => service,
+            Err(error) => {
+                eprintln!("{error}");
+                return ExitCode::FAILURE;
+            }
+        },
+        Err(error) => {
+            eprintln!("Can't open {snapshot_path}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let flagged = service.flagged_indices(&detection_key);
+    eprintln!(
+        "{} of {} outputs flagged at a false positive rate of 2^-{}",
+        flagged.len(),
+        service.accounts().len(),
+        detection_key.precision()
+    );
+    for (tree_index, index) in flagged {
+        println!("{tree_index} {index}");
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,107 @@
+use crate::registry::ShieldedRecipient;
+use crate::{Result, SdkError};
+use bech32::primitives::decode::CheckedHrpstring;
+use bech32::{Bech32m, Hrp};
+use std::fmt;
+use std::str::FromStr;
+use zkcash::groth16::is_less_than_bn254_field_size_be;
+
+pub const MAINNET_HRP: &str = "zkc";
+pub const DEVNET_HRP: &str = "zkctest";
+pub const SHIELDED_ADDRESS_VERSION: u8 = 0;
+
+const PAYLOAD_LEN: usize = 1 + 32 + 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Devnet,
+}
+
+impl Network {
+    pub fn hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => MAINNET_HRP,
+            Network::Devnet => DEVNET_HRP,
+        }
+    }
+}
+
+/// The text form of a shielded identity: the Poseidon public key that owns notes and the
+/// X25519 key they are encrypted to.
+///
+/// Encoded as bech32m with `zkc` (mainnet) or `zkctest` (devnet) as human-readable prefix,
+/// and `version || shielded_pubkey || encryption_key` as data. The checksum catches typos,
+/// and the prefix keeps devnet addresses out of mainnet transactions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShieldedAddress {
+    pub network: Network,
+    /// Poseidon public key that owns the notes (big-endian).
+    pub shielded_pubkey: [u8; 32],
+    /// X25519 public key the notes are encrypted to.
+    pub encryption_key: [u8; 32],
+}
+
+impl ShieldedAddress {
+    pub fn new(network: Network, shielded_pubkey: [u8; 32], encryption_key: [u8; 32]) -> Result<Self> {
+        if !is_less_than_bn254_field_size_be(&shielded_pubkey)
+            || shielded_pubkey == [0u8; 32]
+            || encryption_key == [0u8; 32]
+        {
+            return Err(SdkError::InvalidAddressKeys);
+        }
+        Ok(Self { network, shielded_pubkey, encryption_key })
+    }
+
+    pub fn from_recipient(network: Network, recipient: &ShieldedRecipient) -> Result<Self> {
+        Self::new(network, recipient.shielded_pubkey, recipient.encryption_key)
+    }
+
+    pub fn encode(&self) -> String {
+        let mut payload = [0u8; PAYLOAD_LEN];
+        payload[0] = SHIELDED_ADDRESS_VERSION;
+        payload[1..33].copy_from_slice(&self.shielded_pubkey);
+        payload[33..].copy_from_slice(&self.encryption_key);
+
+        let hrp = Hrp::parse(self.network.hrp()).expect("network prefixes are valid");
+        bech32::encode::<Bech32m>(hrp, &payload).expect("payload is within the bech32m length limit")
+    }
+
+    pub fn decode(address: &str) -> Result<Self> {
+        let checked = CheckedHrpstring::new::<Bech32m>(address).map_err(|_| SdkError::InvalidAddressEncoding)?;
+
+        let network = match checked.hrp().to_lowercase().as_str() {
+            MAINNET_HRP => Network::Mainnet,
+            DEVNET_HRP => Network::Devnet,
+            _ => return Err(SdkError::UnknownAddressPrefix),
+        };
+
+        let payload: Vec<u8> = checked.byte_iter().collect();
+        if payload.first() != Some(&SHIELDED_ADDRESS_VERSION) {
+            return Err(SdkError::UnsupportedAddressVersion);
+        }
+        if payload.len() != PAYLOAD_LEN {
+            return Err(SdkError::InvalidAddressEncoding);
+        }
+
+        Self::new(
+            network,
+            payload[1..33].try_into().unwrap(),
+            payload[33..].try_into().unwrap(),
+        )
+    }
+}
+
+impl fmt::Display for ShieldedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.encode())
+    }
+}
+
+impl FromStr for ShieldedAddress {
+    type Err = SdkError;
+
+    fn from_str(address: &str) -> Result<Self> {
+        Self::decode(address)
+    }
+}
@@ -0,0 +1,118 @@
+use crate::encryption::{open, random_secret, seal, EnvelopeFormat};
+use crate::{Note, Result, SdkError};
+use anchor_lang::{AnchorDeserialize, AnchorSerialize};
+use rand_core::{CryptoRng, RngCore};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zkcash::{AuditorOutputs, CommitmentAccount};
+
+pub const AUDITOR_CIPHERTEXT_VERSION: u8 = 1;
+
+/// The auditor decrypts every output, so its envelope has no view tag.
+const AUDITOR_ENVELOPE: EnvelopeFormat = EnvelopeFormat {
+    version: AUDITOR_CIPHERTEXT_VERSION,
+    kdf_info: b"zkcash-auditor-v1",
+    view_tag: false,
+    clue: false,
+};
+
+/// Encrypts `note` to the pool's auditor key. The output commitment is authenticated with
+/// the ciphertext, so a ciphertext can't be attached to another output.
+///
+/// Uses the envelope of `encryption::encrypt_note`, with its own key derivation info.
+pub fn encrypt_for_auditor<R: RngCore + CryptoRng>(
+    auditor_key: &[u8; 32],
+    commitment: &[u8; 32],
+    note: &Note,
+    rng: &mut R,
+) -> Vec<u8> {
+    let plaintext = note.try_to_vec().expect("Borsh serialization of a note can't fail");
+    seal(&AUDITOR_ENVELOPE, auditor_key, None, commitment, &plaintext, rng)
+}
+
+/// The auditor ciphertexts of both outputs of a transaction, in the order of the outputs, or
+/// `None` if the pool has no auditor and `auditor_key` is zero.
+pub fn auditor_outputs<R: RngCore + CryptoRng>(
+    auditor_key: &[u8; 32],
+    outputs: [(&[u8; 32], &Note); 2],
+    rng: &mut R,
+) -> Option<AuditorOutputs> {
+    if *auditor_key == [0u8; 32] {
+        return None;
+    }
+    let [(commitment1, note1), (commitment2, note2)] = outputs;
+    Some(AuditorOutputs {
+        encrypted_output1: encrypt_for_auditor(auditor_key, commitment1, note1, rng),
+        encrypted_output2: encrypt_for_auditor(auditor_key, commitment2, note2, rng),
+    })
+}
+
+/// One output of the pool as seen by the auditor. The program only checks that an auditor
+/// ciphertext is present, so a sender can attach one that doesn't open to the output; it then
+/// shows up as an error in `note`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub tree_index: u32,
+    pub index: u64,
+    pub commitment: [u8; 32],
+    /// The decrypted note, or why it couldn't be recovered. An output without an auditor
+    /// ciphertext was made before the pool had an auditor.
+    pub note: Option<Result<Note>>,
+}
+
+impl AuditEntry {
+    /// Whether the auditor recovered the note and it opens the output commitment. Nothing on
+    /// chain binds the auditor ciphertext to the output, so only verified entries are known;
+    /// any other entry is an output the auditor can't account for.
+    pub fn is_verified(&self) -> bool {
+        matches!(self.note, Some(Ok(_)))
+    }
+}
+
+/// Holds the auditor's X25519 secret key and decrypts the auditor ciphertexts of a pool.
+pub struct Auditor {
+    secret: StaticSecret,
+}
+
+impl Auditor {
+    pub fn new(secret_key: [u8; 32]) -> Self {
+        Self { secret: StaticSecret::from(secret_key) }
+    }
+
+    pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        Self { secret: random_secret(rng) }
+    }
+
+    /// The key to set with `update_auditor_key`.
+    pub fn public_key(&self) -> [u8; 32] {
+        PublicKey::from(&self.secret).to_bytes()
+    }
+
+    /// Decrypts an auditor ciphertext and checks the note against its output commitment.
+    pub fn decrypt(&self, ciphertext: &[u8], commitment: &[u8; 32]) -> Result<Note> {
+        let plaintext = open(&AUDITOR_ENVELOPE, &self.secret, commitment, ciphertext)?;
+        let note = Note::try_from_slice(&plaintext).map_err(|_| SdkError::DecryptionFailed)?;
+
+        if note.commitment()? != *commitment {
+            return Err(SdkError::CommitmentMismatch);
+        }
+        Ok(note)
+    }
+
+    /// Decrypts the history of a pool from its commitment accounts, ordered by tree and leaf
+    /// index. The history is only complete if every entry made while the pool had an auditor
+    /// `is_verified`, since senders choose what they encrypt to the auditor.
+    pub fn audit<'a>(&self, commitment_accounts: impl IntoIterator<Item = &'a CommitmentAccount>) -> Vec<AuditEntry> {
+        let mut entries: Vec<AuditEntry> = commitment_accounts
+            .into_iter()
+            .map(|account| AuditEntry {
+                tree_index: account.tree_index,
+                index: account.index,
+                commitment: account.commitment,
+                note: (!account.auditor_output.is_empty())
+                    .then(|| self.decrypt(&account.auditor_output, &account.commitment)),
+            })
+            .collect();
+        entries.sort_by_key(|entry| (entry.tree_index, entry.index));
+        entries
+    }
+}
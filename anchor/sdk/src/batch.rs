@@ -0,0 +1,87 @@
+use crate::auditor::encrypt_for_auditor;
+use crate::encryption::encrypt_note;
+use crate::{Note, Result, SdkError, ShieldedAddress};
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use rand_core::{CryptoRng, RngCore};
+use zkcash::batch_deposit::{PendingBatch, BATCH_DEPOSIT_OUTPUTS};
+use zkcash::utils::view_tag;
+use zkcash::{BatchDepositEvent, BatchOutput, CommitmentAccount};
+
+/// The notes of a batch deposit, with what the depositor submits for them: the outputs for
+/// `add_batch_outputs`, and the public inputs of the batch deposit proof.
+pub struct BatchDepositPlan {
+    /// The notes of the outputs, in order. They are the private inputs of the proof.
+    pub notes: Vec<Note>,
+    pub outputs: Vec<BatchOutput>,
+    /// The sum of the note amounts, the amount passed to `batch_deposit`.
+    pub amount: u64,
+    /// The hash of the output commitments, see `PendingBatch::outputs_hash`.
+    pub outputs_hash: [u8; 32],
+}
+
+/// Plans a batch deposit of a SOL note of each amount to its recipient, at most
+/// `BATCH_DEPOSIT_OUTPUTS` of them. With a pool auditor, every note is encrypted to the
+/// auditor too.
+pub fn batch_deposit_plan<R: RngCore + CryptoRng>(
+    recipients: &[(ShieldedAddress, u64)],
+    auditor_key: &[u8; 32],
+    rng: &mut R,
+) -> Result<BatchDepositPlan> {
+    if recipients.is_empty() || recipients.len() > BATCH_DEPOSIT_OUTPUTS {
+        return Err(SdkError::InvalidBatchSize);
+    }
+
+    let mut amount: u64 = 0;
+    let mut notes = Vec::with_capacity(recipients.len());
+    let mut outputs = Vec::with_capacity(recipients.len());
+    for (recipient, note_amount) in recipients {
+        if *note_amount == 0 {
+            return Err(SdkError::InvalidAmount);
+        }
+        amount = amount.checked_add(*note_amount).ok_or(SdkError::BatchAmountOverflow)?;
+
+        let note = Note::random_sol(*note_amount, recipient.shielded_pubkey, rng);
+        let commitment = note.commitment()?;
+        let auditor_output = if *auditor_key == [0u8; 32] {
+            Vec::new()
+        } else {
+            encrypt_for_auditor(auditor_key, &commitment, &note, rng)
+        };
+        outputs.push(BatchOutput {
+            commitment,
+            encrypted_output: encrypt_note(&recipient.encryption_key, &commitment, &note, rng),
+            auditor_output,
+        });
+        notes.push(note);
+    }
+
+    let commitments: Vec<[u8; 32]> = outputs.iter().map(|output| output.commitment).collect();
+    let outputs_hash = PendingBatch::outputs_hash(&commitments).ok_or(SdkError::HashFailed)?;
+    Ok(BatchDepositPlan { notes, outputs, amount, outputs_hash })
+}
+
+/// Decodes a `BatchDepositEvent` from the data of its `Program data:` log line, after base64
+/// decoding.
+pub fn decode_batch_deposit_event(data: &[u8]) -> Result<BatchDepositEvent> {
+    let mut data = data.strip_prefix(BatchDepositEvent::DISCRIMINATOR).ok_or(SdkError::InvalidAccountData)?;
+    BatchDepositEvent::deserialize(&mut data).map_err(|_| SdkError::InvalidAccountData)
+}
+
+/// The outputs of a batch deposit as the commitment accounts a `transact` would have created
+/// for them, so they are scanned like any other output.
+pub fn batch_deposit_accounts(event: &BatchDepositEvent) -> Vec<CommitmentAccount> {
+    event
+        .outputs
+        .iter()
+        .zip(event.first_leaf_index..)
+        .map(|(output, index)| CommitmentAccount {
+            commitment: output.commitment,
+            encrypted_output: output.encrypted_output.clone(),
+            index,
+            bump: 0,
+            auditor_output: output.auditor_output.clone(),
+            view_tag: view_tag(&output.encrypted_output),
+            tree_index: event.tree_index,
+        })
+        .collect()
+}
@@ -0,0 +1,213 @@
+use crate::auditor::auditor_outputs;
+use crate::encryption::encrypt_note;
+use crate::keys::{negated_field_element, SpendingKey};
+use crate::registry::AccountSource;
+use crate::selection::{dummy_input, dummy_output};
+use crate::{Network, Note, Result, SdkError, ShieldedAddress};
+use anchor_lang::prelude::Pubkey;
+use bech32::primitives::decode::CheckedHrpstring;
+use bech32::{Bech32m, Hrp};
+use rand_core::{CryptoRng, RngCore};
+use zkcash::ExtData;
+use zkcash_interface::nullifier_account_addresses;
+
+pub const MAINNET_CLAIM_HRP: &str = "zkcnote";
+pub const DEVNET_CLAIM_HRP: &str = "zkcnotetest";
+pub const CLAIM_NOTE_VERSION: u8 = 0;
+
+const PAYLOAD_LEN: usize = 1 + 32 + 4 + 32 + 8 + 32 + 32 + 8;
+
+/// A note handed to someone else, with the key that spends it: whoever has the string can
+/// claim it. The depositor makes an output owned by a fresh `SpendingKey` and shares it
+/// once the output is in the tree.
+///
+/// Encoded as bech32m with `zkcnote` (mainnet) or `zkcnotetest` (devnet) as prefix, and
+/// `version || pool || tree_index || private_key || amount || blinding || mint || leaf_index`
+/// as data, integers in big endian. The checksum catches truncated links.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClaimNote {
+    pub network: Network,
+    /// The pool authority, tree accounts are derived from it.
+    pub pool: Pubkey,
+    pub tree_index: u32,
+    pub spending_key: SpendingKey,
+    pub note: Note,
+    pub leaf_index: u64,
+}
+
+impl ClaimNote {
+    /// A note of `amount` owned by a fresh key, to deposit before the claim note is shared.
+    pub fn new_note<R: RngCore + CryptoRng>(amount: u64, mint: [u8; 32], rng: &mut R) -> Result<(SpendingKey, Note)> {
+        let spending_key = SpendingKey::random(rng);
+        let mut note = Note::random_sol(amount, spending_key.public_key()?, rng);
+        note.mint = mint;
+        Ok((spending_key, note))
+    }
+
+    pub fn encode(&self) -> String {
+        let mut payload = Vec::with_capacity(PAYLOAD_LEN);
+        payload.push(CLAIM_NOTE_VERSION);
+        payload.extend_from_slice(self.pool.as_ref());
+        payload.extend_from_slice(&self.tree_index.to_be_bytes());
+        payload.extend_from_slice(&self.spending_key.private_key());
+        payload.extend_from_slice(&self.note.amount.to_be_bytes());
+        payload.extend_from_slice(&self.note.blinding);
+        payload.extend_from_slice(&self.note.mint);
+        payload.extend_from_slice(&self.leaf_index.to_be_bytes());
+
+        let hrp = Hrp::parse(claim_hrp(self.network)).expect("network prefixes are valid");
+        bech32::encode::<Bech32m>(hrp, &payload).expect("payload is within the bech32m length limit")
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self> {
+        let checked = CheckedHrpstring::new::<Bech32m>(encoded).map_err(|_| SdkError::InvalidClaimNote)?;
+        let network = match checked.hrp().to_lowercase().as_str() {
+            MAINNET_CLAIM_HRP => Network::Mainnet,
+            DEVNET_CLAIM_HRP => Network::Devnet,
+            _ => return Err(SdkError::UnknownClaimNotePrefix),
+        };
+
+        let payload: Vec<u8> = checked.byte_iter().collect();
+        if payload.first() != Some(&CLAIM_NOTE_VERSION) {
+            return Err(SdkError::UnsupportedClaimNoteVersion);
+        }
+        if payload.len() != PAYLOAD_LEN {
+            return Err(SdkError::InvalidClaimNote);
+        }
+
+        let spending_key = SpendingKey::new(payload[37..69].try_into().unwrap());
+        let note = Note {
+            amount: u64::from_be_bytes(payload[69..77].try_into().unwrap()),
+            owner: spending_key.public_key()?,
+            blinding: payload[77..109].try_into().unwrap(),
+            mint: payload[109..141].try_into().unwrap(),
+        };
+        Ok(Self {
+            network,
+            pool: Pubkey::new_from_array(payload[1..33].try_into().unwrap()),
+            tree_index: u32::from_be_bytes(payload[33..37].try_into().unwrap()),
+            spending_key,
+            note,
+            leaf_index: u64::from_be_bytes(payload[141..].try_into().unwrap()),
+        })
+    }
+
+    /// A link to `base_url` with the note in the fragment, which browsers don't send to the
+    /// server. The same string fits a QR code.
+    pub fn to_url(&self, base_url: &str) -> String {
+        format!("{base_url}#{}", self.encode())
+    }
+
+    /// Decodes a claim link, or a bare claim note.
+    pub fn from_url(url: &str) -> Result<Self> {
+        Self::decode(url.rsplit_once('#').map_or(url, |(_, fragment)| fragment))
+    }
+
+    pub fn commitment(&self) -> Result<[u8; 32]> {
+        self.note.commitment()
+    }
+
+    pub fn nullifier(&self) -> Result<[u8; 32]> {
+        self.spending_key.nullifier(&self.commitment()?, self.leaf_index)
+    }
+
+    /// Whether the note was claimed already, or swept back by its depositor.
+    pub fn is_claimed(&self, source: &impl AccountSource) -> Result<bool> {
+        let nullifier = self.nullifier()?;
+        Ok(nullifier_account_addresses(&nullifier).iter().any(|address| source.get_account_data(address).is_some()))
+    }
+}
+
+fn claim_hrp(network: Network) -> &'static str {
+    match network {
+        Network::Mainnet => MAINNET_CLAIM_HRP,
+        Network::Devnet => DEVNET_CLAIM_HRP,
+    }
+}
+
+/// An input of a transaction, with what the prover needs besides its Merkle path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputWitness {
+    pub note: Note,
+    pub private_key: [u8; 32],
+    pub leaf_index: u64,
+    pub nullifier: [u8; 32],
+}
+
+/// The witness and ext data of the `transact` that sweeps a claim note to its claimant. The
+/// Merkle path of the claimed note and the Groth16 proof still come from the prover.
+#[derive(Clone)]
+pub struct ClaimTransaction {
+    pub inputs: [InputWitness; 2],
+    pub outputs: [Note; 2],
+    pub output_commitments: [[u8; 32]; 2],
+    /// The public amount input of the proof, `-fee` in the field.
+    pub public_amount: [u8; 32],
+    /// `ext_amount` is 0, and the relayer `fee` is paid from the note.
+    pub ext_data: ExtData,
+}
+
+/// Builds the claim of `claim_note` to `claimant`: the note and a dummy input go in, the
+/// amount minus `fee` goes to a new note of the claimant, and a dummy output pads the
+/// outputs, in random order. Nothing is withdrawn, so `recipient` only has to match the
+/// `recipient` account of the instruction, and the fee goes to `fee_recipient_account`.
+/// Both outputs are also encrypted to the pool's `auditor_key`, zero if it has none.
+pub fn claim_transaction<R: RngCore + CryptoRng>(
+    claim_note: &ClaimNote,
+    claimant: &ShieldedAddress,
+    recipient: Pubkey,
+    fee: u64,
+    auditor_key: &[u8; 32],
+    rng: &mut R,
+) -> Result<ClaimTransaction> {
+    let amount = claim_note.note.amount.checked_sub(fee).ok_or(SdkError::InsufficientFunds)?;
+    let private_key = claim_note.spending_key.private_key();
+    let mint = claim_note.note.mint;
+
+    let dummy = dummy_input(claim_note.note.owner, mint, rng);
+    let inputs = [
+        InputWitness {
+            note: claim_note.note.clone(),
+            private_key,
+            leaf_index: claim_note.leaf_index,
+            nullifier: claim_note.nullifier()?,
+        },
+        InputWitness {
+            nullifier: claim_note.spending_key.nullifier(&dummy.commitment()?, 0)?,
+            note: dummy,
+            private_key,
+            leaf_index: 0,
+        },
+    ];
+
+    let mut output = Note::random_sol(amount, claimant.shielded_pubkey, rng);
+    output.mint = mint;
+    let output_commitment = output.commitment()?;
+    let encrypted_output = encrypt_note(&claimant.encryption_key, &output_commitment, &output, rng);
+    let padding = dummy_output(mint, rng)?;
+
+    let mut outputs = [(output, output_commitment, encrypted_output), (padding.note, padding.commitment, padding.encrypted_output)];
+    if rng.next_u32() & 1 == 1 {
+        outputs.swap(0, 1);
+    }
+    let [(output1, commitment1, encrypted_output1), (output2, commitment2, encrypted_output2)] = outputs;
+    let auditor_outputs = auditor_outputs(auditor_key, [(&commitment1, &output1), (&commitment2, &output2)], rng);
+
+    Ok(ClaimTransaction {
+        inputs,
+        outputs: [output1, output2],
+        output_commitments: [commitment1, commitment2],
+        public_amount: negated_field_element(fee),
+        ext_data: ExtData {
+            recipient,
+            ext_amount: 0,
+            encrypted_output1,
+            encrypted_output2,
+            fee,
+            call: None,
+            auditor_outputs,
+            payouts: None,
+            association: None,
+        },
+    })
+}
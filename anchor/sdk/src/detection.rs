@@ -0,0 +1,251 @@
+use crate::encryption::{envelope_version, CLUE_OFFSET, NOTE_CIPHERTEXT_VERSION};
+use crate::note::decode_commitment_account;
+use crate::{Result, SdkError};
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_TABLE;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256, Sha512};
+use std::io::{Read, Write};
+use zkcash::CommitmentAccount;
+
+/// Number of key pairs in a clue key, so the lowest false positive rate is 2^-24.
+pub const MAX_PRECISION: usize = 24;
+pub const CLUE_LEN: usize = 32 + 32 + MAX_PRECISION / 8;
+
+const CLUE_BIT_DOMAIN: &[u8] = b"zkcash-clue-bit-v1";
+const CLUE_SCALAR_DOMAIN: &[u8] = b"zkcash-clue-scalar-v1";
+
+/// Secret key for fuzzy message detection (FMD2 of Beck, Len, Miers and Green, 2021).
+///
+/// A sender attaches a clue made from the recipient's `ClueKey` to every output. A
+/// `DetectionKey` extracted with precision `n` flags every clue made for this key, and each
+/// other clue with probability 2^-n. A scanning server holding it learns which outputs may
+/// belong to the user, but can't decrypt them, and can't tell true matches from false ones.
+pub struct DetectionSecretKey {
+    keys: Vec<Scalar>,
+}
+
+/// The public key senders make clues with, `MAX_PRECISION` Ristretto points.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClueKey {
+    points: Vec<RistrettoPoint>,
+}
+
+/// The first `precision` secret keys, handed to a scanning server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DetectionKey {
+    keys: Vec<Scalar>,
+}
+
+impl DetectionSecretKey {
+    pub fn new(keys: [[u8; 32]; MAX_PRECISION]) -> Self {
+        Self { keys: keys.iter().map(|key| Scalar::from_bytes_mod_order(*key)).collect() }
+    }
+
+    pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        Self { keys: (0..MAX_PRECISION).map(|_| random_scalar(rng)).collect() }
+    }
+
+    pub fn clue_key(&self) -> ClueKey {
+        ClueKey { points: self.keys.iter().map(|key| key * &RISTRETTO_BASEPOINT_TABLE).collect() }
+    }
+
+    /// A detection key with a false positive rate of 2^-precision. A higher precision
+    /// downloads fewer outputs, but tells the server more about which ones are ours.
+    pub fn detection_key(&self, precision: usize) -> Result<DetectionKey> {
+        if precision == 0 || precision > MAX_PRECISION {
+            return Err(SdkError::InvalidPrecision);
+        }
+        Ok(DetectionKey { keys: self.keys[..precision].to_vec() })
+    }
+}
+
+impl ClueKey {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.points.iter().flat_map(|point| point.compress().to_bytes()).collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != MAX_PRECISION * 32 {
+            return Err(SdkError::InvalidDetectionKey);
+        }
+        let points = bytes
+            .chunks_exact(32)
+            .map(|chunk| CompressedRistretto::from_slice(chunk).decompress().ok_or(SdkError::InvalidDetectionKey))
+            .collect::<Result<_>>()?;
+        Ok(Self { points })
+    }
+
+    /// A clue that the matching detection keys flag.
+    pub fn create_clue<R: RngCore + CryptoRng>(&self, rng: &mut R) -> [u8; CLUE_LEN] {
+        let r = random_scalar(rng);
+        let z = random_scalar(rng);
+        let p = (&r * &RISTRETTO_BASEPOINT_TABLE).compress();
+        let q = (&z * &RISTRETTO_BASEPOINT_TABLE).compress();
+
+        // Each bit is set so that it decrypts to 1 under the matching secret key
+        let mut bits = [0u8; MAX_PRECISION / 8];
+        for (i, point) in self.points.iter().enumerate() {
+            if !clue_bit(&p, &(r * point).compress(), &q) {
+                bits[i / 8] |= 1 << (i % 8);
+            }
+        }
+
+        let m = clue_scalar(&p, &bits);
+        let y = (z - m) * r.invert();
+
+        let mut clue = [0u8; CLUE_LEN];
+        clue[..32].copy_from_slice(p.as_bytes());
+        clue[32..64].copy_from_slice(y.as_bytes());
+        clue[64..].copy_from_slice(&bits);
+        clue
+    }
+}
+
+/// A clue for an output without a clue key. It has the same length and distribution as a
+/// real clue, and any detection key flags it with its false positive rate.
+pub fn random_clue<R: RngCore + CryptoRng>(rng: &mut R) -> [u8; CLUE_LEN] {
+    let mut clue = [0u8; CLUE_LEN];
+    clue[..32].copy_from_slice((&random_scalar(rng) * &RISTRETTO_BASEPOINT_TABLE).compress().as_bytes());
+    clue[32..64].copy_from_slice(random_scalar(rng).as_bytes());
+    rng.fill_bytes(&mut clue[64..]);
+    clue
+}
+
+impl DetectionKey {
+    pub fn precision(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.keys.iter().flat_map(|key| key.to_bytes()).collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.is_empty() || bytes.len() % 32 != 0 || bytes.len() > MAX_PRECISION * 32 {
+            return Err(SdkError::InvalidDetectionKey);
+        }
+        let keys = bytes
+            .chunks_exact(32)
+            .map(|chunk| Scalar::from_canonical_bytes(chunk.try_into().unwrap()).ok_or(SdkError::InvalidDetectionKey))
+            .collect::<Result<_>>()?;
+        Ok(Self { keys })
+    }
+
+    /// Whether a clue may have been made for this key. Malformed clues are never flagged.
+    pub fn detect(&self, clue: &[u8]) -> bool {
+        let Ok(clue) = <&[u8; CLUE_LEN]>::try_from(clue) else {
+            return false;
+        };
+        let p = CompressedRistretto::from_slice(&clue[..32]);
+        let (Some(p_point), Some(y)) = (p.decompress(), Scalar::from_canonical_bytes(clue[32..64].try_into().unwrap()))
+        else {
+            return false;
+        };
+        let bits = &clue[64..];
+
+        // y = (z - m) / r, so y·P + m·G = z·G is the Q the sender hashed
+        let m = clue_scalar(&p, bits);
+        let q = (y * p_point + &m * &RISTRETTO_BASEPOINT_TABLE).compress();
+
+        self.keys.iter().enumerate().all(|(i, key)| {
+            let bit = bits[i / 8] >> (i % 8) & 1 == 1;
+            clue_bit(&p, &(key * p_point).compress(), &q) != bit
+        })
+    }
+
+    /// Whether a commitment account may hold an output for this key. Outputs that aren't
+    /// versioned note envelopes carry no clue and are always flagged.
+    pub fn detect_output(&self, account: &CommitmentAccount) -> bool {
+        match envelope_version(&account.encrypted_output) {
+            Some(NOTE_CIPHERTEXT_VERSION) => account
+                .encrypted_output
+                .get(CLUE_OFFSET..CLUE_OFFSET + CLUE_LEN)
+                .is_some_and(|clue| self.detect(clue)),
+            _ => true,
+        }
+    }
+}
+
+/// The reference scanning service: holds the detection keys of its users and flags their
+/// outputs in a local snapshot of the pool's commitment accounts.
+#[derive(Default)]
+pub struct DetectionService {
+    accounts: Vec<CommitmentAccount>,
+}
+
+impl DetectionService {
+    pub fn new(accounts: Vec<CommitmentAccount>) -> Self {
+        Self { accounts }
+    }
+
+    pub fn from_snapshot(reader: impl Read) -> Result<Self> {
+        Ok(Self::new(read_snapshot(reader)?))
+    }
+
+    pub fn accounts(&self) -> &[CommitmentAccount] {
+        &self.accounts
+    }
+
+    /// The tree and leaf indices of the outputs flagged for `detection_key`, in snapshot order.
+    pub fn flagged_indices(&self, detection_key: &DetectionKey) -> Vec<(u32, u64)> {
+        self.accounts
+            .iter()
+            .filter(|account| detection_key.detect_output(account))
+            .map(|account| (account.tree_index, account.index))
+            .collect()
+    }
+}
+
+/// Reads a snapshot: the raw data of commitment accounts, each prefixed with its length as a
+/// little-endian u32, as written by `write_snapshot`.
+pub fn read_snapshot(mut reader: impl Read) -> Result<Vec<CommitmentAccount>> {
+    let mut snapshot = Vec::new();
+    reader.read_to_end(&mut snapshot).map_err(|_| SdkError::InvalidSnapshot)?;
+
+    let mut accounts = Vec::new();
+    let mut rest = &snapshot[..];
+    while !rest.is_empty() {
+        let (length, tail) = rest.split_first_chunk::<4>().ok_or(SdkError::InvalidSnapshot)?;
+        let length = u32::from_le_bytes(*length) as usize;
+        if tail.len() < length {
+            return Err(SdkError::InvalidSnapshot);
+        }
+        let (data, tail) = tail.split_at(length);
+        accounts.push(decode_commitment_account(data)?);
+        rest = tail;
+    }
+    Ok(accounts)
+}
+
+/// Writes the raw data of commitment accounts, as returned by `getProgramAccounts`.
+pub fn write_snapshot<'a>(mut writer: impl Write, account_data: impl IntoIterator<Item = &'a [u8]>) -> Result<()> {
+    for data in account_data {
+        let length = u32::try_from(data.len()).map_err(|_| SdkError::InvalidSnapshot)?;
+        writer.write_all(&length.to_le_bytes()).map_err(|_| SdkError::InvalidSnapshot)?;
+        writer.write_all(data).map_err(|_| SdkError::InvalidSnapshot)?;
+    }
+    Ok(())
+}
+
+fn random_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Scalar {
+    let mut bytes = [0u8; 64];
+    rng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+fn clue_bit(p: &CompressedRistretto, shared: &CompressedRistretto, q: &CompressedRistretto) -> bool {
+    let hash = Sha256::new()
+        .chain_update(CLUE_BIT_DOMAIN)
+        .chain_update(p.as_bytes())
+        .chain_update(shared.as_bytes())
+        .chain_update(q.as_bytes())
+        .finalize();
+    hash[0] & 1 == 1
+}
+
+fn clue_scalar(p: &CompressedRistretto, bits: &[u8]) -> Scalar {
+    let hash = Sha512::new().chain_update(CLUE_SCALAR_DOMAIN).chain_update(p.as_bytes()).chain_update(bits).finalize();
+    Scalar::from_bytes_mod_order_wide(&hash.into())
+}
@@ -0,0 +1,370 @@
+use crate::detection::{random_clue, ClueKey, CLUE_LEN};
+use crate::keys::SpendingKey;
+use crate::{Note, Result, SdkError};
+use aes::cipher::{KeyIvInit, StreamCipher};
+use anchor_lang::{AnchorDeserialize, AnchorSerialize};
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use num_bigint::BigUint;
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+use zkcash::CommitmentAccount;
+
+pub use zkcash::utils::{envelope_version, ENVELOPE_MAGIC, NOTE_CIPHERTEXT_VERSION, VIEW_TAG_OFFSET};
+
+/// The magic and the version byte.
+const ENVELOPE_PREFIX_LEN: usize = ENVELOPE_MAGIC.len() + 1;
+
+/// The detection clue follows the view tag.
+pub const CLUE_OFFSET: usize = VIEW_TAG_OFFSET + 1;
+
+const VIEW_TAG_DOMAIN: &[u8] = b"zkcash-view-tag-v1";
+
+/// Layout of an envelope made by `seal`: `ENVELOPE_MAGIC` (6 bytes) || version (1 byte) ||
+/// ephemeral X25519 public key (32 bytes) || view tag (1 byte, if the format has one) || detection clue (`CLUE_LEN`
+/// bytes, if the format has one) || ChaCha20-Poly1305 ciphertext.
+///
+/// The key is derived with HKDF-SHA256 from the shared secret, salted with both public keys,
+/// with `kdf_info` separating the uses of the envelope. Every key is used once, so the nonce
+/// is zero.
+pub(crate) struct EnvelopeFormat {
+    pub version: u8,
+    pub kdf_info: &'static [u8],
+    pub view_tag: bool,
+    pub clue: bool,
+}
+
+impl EnvelopeFormat {
+    fn header_len(&self) -> usize {
+        ENVELOPE_PREFIX_LEN + 32 + self.view_tag as usize + if self.clue { CLUE_LEN } else { 0 }
+    }
+}
+
+const NOTE_ENVELOPE: EnvelopeFormat = EnvelopeFormat {
+    version: NOTE_CIPHERTEXT_VERSION,
+    kdf_info: b"zkcash-note-v1",
+    view_tag: true,
+    clue: true,
+};
+
+/// Encrypts `plaintext` to `recipient_key` with a fresh ephemeral X25519 key.
+pub(crate) fn seal<R: RngCore + CryptoRng>(
+    format: &EnvelopeFormat,
+    recipient_key: &[u8; 32],
+    clue: Option<&[u8; CLUE_LEN]>,
+    aad: &[u8],
+    plaintext: &[u8],
+    rng: &mut R,
+) -> Vec<u8> {
+    let recipient_key = PublicKey::from(*recipient_key);
+    let ephemeral_secret = random_secret(rng);
+    let ephemeral_key = PublicKey::from(&ephemeral_secret);
+    let shared_secret = ephemeral_secret.diffie_hellman(&recipient_key);
+
+    let cipher = derive_cipher(format.kdf_info, &shared_secret, &ephemeral_key, &recipient_key);
+    let ciphertext = cipher
+        .encrypt(&Nonce::default(), Payload { msg: plaintext, aad })
+        .expect("ChaCha20-Poly1305 encryption can't fail for short messages");
+
+    let mut output = Vec::with_capacity(format.header_len() + ciphertext.len());
+    output.extend_from_slice(&ENVELOPE_MAGIC);
+    output.push(format.version);
+    output.extend_from_slice(ephemeral_key.as_bytes());
+    if format.view_tag {
+        output.push(view_tag(&shared_secret));
+    }
+    debug_assert_eq!(format.clue, clue.is_some());
+    if let Some(clue) = clue {
+        output.extend_from_slice(clue);
+    }
+    output.extend_from_slice(&ciphertext);
+    output
+}
+
+/// Opens an envelope made by `seal` with the recipient's secret key.
+pub(crate) fn open(format: &EnvelopeFormat, secret: &StaticSecret, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let header_len = format.header_len();
+    if ciphertext.len() < header_len || envelope_version(ciphertext) != Some(format.version) {
+        return Err(SdkError::InvalidCiphertext);
+    }
+
+    let ephemeral_key = ephemeral_key(ciphertext);
+    let shared_secret = secret.diffie_hellman(&ephemeral_key);
+    if format.view_tag && ciphertext[VIEW_TAG_OFFSET] != view_tag(&shared_secret) {
+        return Err(SdkError::DecryptionFailed);
+    }
+    let cipher = derive_cipher(format.kdf_info, &shared_secret, &ephemeral_key, &PublicKey::from(secret));
+
+    cipher
+        .decrypt(&Nonce::default(), Payload { msg: &ciphertext[header_len..], aad })
+        .map_err(|_| SdkError::DecryptionFailed)
+}
+
+pub(crate) fn random_secret<R: RngCore + CryptoRng>(rng: &mut R) -> StaticSecret {
+    let mut secret_key = [0u8; 32];
+    rng.fill_bytes(&mut secret_key);
+    StaticSecret::from(secret_key)
+}
+
+/// The ephemeral key of an envelope whose header has been checked.
+fn ephemeral_key(ciphertext: &[u8]) -> PublicKey {
+    PublicKey::from(<[u8; 32]>::try_from(&ciphertext[ENVELOPE_PREFIX_LEN..ENVELOPE_PREFIX_LEN + 32]).unwrap())
+}
+
+/// One byte derived from the shared secret. A recipient recomputes it with one hash after
+/// the key exchange, and skips the 255 in 256 outputs whose tag doesn't match.
+fn view_tag(shared_secret: &SharedSecret) -> u8 {
+    Sha256::new().chain_update(VIEW_TAG_DOMAIN).chain_update(shared_secret.as_bytes()).finalize()[0]
+}
+
+fn derive_cipher(info: &[u8], shared_secret: &SharedSecret, ephemeral_key: &PublicKey, recipient_key: &PublicKey) -> ChaCha20Poly1305 {
+    let mut salt = [0u8; 64];
+    salt[..32].copy_from_slice(ephemeral_key.as_bytes());
+    salt[32..].copy_from_slice(recipient_key.as_bytes());
+
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared_secret.as_bytes())
+        .expand(info, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+/// Encrypts `note` for the owner of `recipient_key`, for `encrypted_output1/2`. The output
+/// commitment is authenticated with the ciphertext, so it can't be attached to another output.
+///
+/// The envelope carries a random detection clue, use `encrypt_note_with_clue` when the
+/// recipient shared a clue key.
+pub fn encrypt_note<R: RngCore + CryptoRng>(
+    recipient_key: &[u8; 32],
+    commitment: &[u8; 32],
+    note: &Note,
+    rng: &mut R,
+) -> Vec<u8> {
+    let clue = random_clue(rng);
+    seal_note(recipient_key, &clue, commitment, note, rng)
+}
+
+/// Encrypts `note` like `encrypt_note`, with a clue that the recipient's detection keys flag.
+pub fn encrypt_note_with_clue<R: RngCore + CryptoRng>(
+    recipient_key: &[u8; 32],
+    clue_key: &ClueKey,
+    commitment: &[u8; 32],
+    note: &Note,
+    rng: &mut R,
+) -> Vec<u8> {
+    let clue = clue_key.create_clue(rng);
+    seal_note(recipient_key, &clue, commitment, note, rng)
+}
+
+fn seal_note<R: RngCore + CryptoRng>(
+    recipient_key: &[u8; 32],
+    clue: &[u8; CLUE_LEN],
+    commitment: &[u8; 32],
+    note: &Note,
+    rng: &mut R,
+) -> Vec<u8> {
+    let plaintext = note.try_to_vec().expect("Borsh serialization of a note can't fail");
+    seal(&NOTE_ENVELOPE, recipient_key, Some(clue), commitment, &plaintext, rng)
+}
+
+/// The X25519 key pair notes are encrypted to. Its public key is the one published with
+/// `register_account` and in shielded addresses.
+pub struct EncryptionKeypair {
+    secret: StaticSecret,
+}
+
+impl EncryptionKeypair {
+    pub fn new(secret_key: [u8; 32]) -> Self {
+        Self { secret: StaticSecret::from(secret_key) }
+    }
+
+    pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        Self { secret: random_secret(rng) }
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        PublicKey::from(&self.secret).to_bytes()
+    }
+
+    /// Decrypts a note and checks it against its output commitment.
+    pub fn decrypt_note(&self, ciphertext: &[u8], commitment: &[u8; 32]) -> Result<Note> {
+        let plaintext = open(&NOTE_ENVELOPE, &self.secret, commitment, ciphertext)?;
+        let note = Note::try_from_slice(&plaintext).map_err(|_| SdkError::DecryptionFailed)?;
+
+        if note.commitment()? != *commitment {
+            return Err(SdkError::CommitmentMismatch);
+        }
+        Ok(note)
+    }
+
+    /// Whether an output may be ours, checked with the view tag of the commitment account:
+    /// one key exchange and one hash instead of a full decryption. Outputs without a view
+    /// tag are always candidates.
+    pub fn is_candidate(&self, account: &CommitmentAccount) -> bool {
+        let Some(tag) = account.view_tag else {
+            return true;
+        };
+        if account.encrypted_output.len() <= VIEW_TAG_OFFSET {
+            return false;
+        }
+
+        let shared_secret = self.secret.diffie_hellman(&ephemeral_key(&account.encrypted_output));
+        tag == view_tag(&shared_secret)
+    }
+
+    /// Finds and decrypts the notes sent to this key, ordered by tree and leaf index. Only outputs
+    /// that pass `is_candidate` are decrypted.
+    pub fn scan<'a>(&self, commitment_accounts: impl IntoIterator<Item = &'a CommitmentAccount>) -> Vec<ScannedNote> {
+        let mut notes: Vec<ScannedNote> = commitment_accounts
+            .into_iter()
+            .filter(|account| self.is_candidate(account))
+            .filter_map(|account| {
+                let note = self.decrypt_note(&account.encrypted_output, &account.commitment).ok()?;
+                Some(ScannedNote { tree_index: account.tree_index, index: account.index, commitment: account.commitment, note })
+            })
+            .collect();
+        notes.sort_by_key(|note| (note.tree_index, note.index));
+        notes
+    }
+}
+
+/// A note found by `EncryptionKeypair::scan`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScannedNote {
+    pub tree_index: u32,
+    pub index: u64,
+    pub commitment: [u8; 32],
+    pub note: Note,
+}
+
+/// The message wallets sign to derive the legacy encryption key.
+pub const LEGACY_SIGN_IN_MESSAGE: &[u8] = b"Privacy Money account sign in";
+
+const LEGACY_UPGRADE_KDF_INFO: &[u8] = b"zkcash-legacy-encryption-key-v1";
+const LEGACY_IV_LEN: usize = 16;
+const LEGACY_TAG_LEN: usize = 16;
+
+type LegacyCipher = ctr::Ctr128BE<aes::Aes128>;
+
+/// The key of the format written by the TypeScript `EncryptionService`: the first 31 bytes
+/// of the wallet's signature of `LEGACY_SIGN_IN_MESSAGE`.
+///
+/// Format: IV (16 bytes) || tag (16 bytes) || AES-128-CTR ciphertext. The AES key is
+/// `key[..16]`, and the tag is the truncated HMAC-SHA256 of `IV || ciphertext` keyed with
+/// `key[16..31]`. It can only be decrypted by the sender's own wallet.
+pub struct LegacyEncryptionKey([u8; 31]);
+
+/// A UTXO in the legacy `amount|blinding|index|mintAddress` format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LegacyUtxo {
+    pub amount: u64,
+    pub blinding: [u8; 32],
+    pub index: u64,
+    /// The mint address as the field element that is hashed into the commitment.
+    pub mint: [u8; 32],
+}
+
+impl LegacyUtxo {
+    /// The legacy format doesn't store the owner, it's the key pair derived from the wallet.
+    pub fn into_note(self, owner: [u8; 32]) -> Note {
+        Note { amount: self.amount, owner, blinding: self.blinding, mint: self.mint }
+    }
+}
+
+impl LegacyEncryptionKey {
+    pub fn new(key: [u8; 31]) -> Self {
+        Self(key)
+    }
+
+    pub fn from_wallet_signature(signature: &[u8; 64]) -> Self {
+        Self(signature[..31].try_into().unwrap())
+    }
+
+    /// The UTXO private key of the TypeScript client, SHA-256 of the legacy key.
+    pub fn spending_key(&self) -> SpendingKey {
+        SpendingKey::new(Sha256::digest(self.0).into())
+    }
+
+    /// An X25519 key derived from the legacy key, so an account from a wallet signature can
+    /// receive notes in the versioned format without a new secret.
+    pub fn encryption_keypair(&self) -> EncryptionKeypair {
+        let mut secret_key = [0u8; 32];
+        Hkdf::<Sha256>::new(None, &self.0)
+            .expand(LEGACY_UPGRADE_KDF_INFO, &mut secret_key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        EncryptionKeypair::new(secret_key)
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        if ciphertext.len() < LEGACY_IV_LEN + LEGACY_TAG_LEN {
+            return Err(SdkError::InvalidCiphertext);
+        }
+        let (iv, rest) = ciphertext.split_at(LEGACY_IV_LEN);
+        let (tag, data) = rest.split_at(LEGACY_TAG_LEN);
+
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.0[16..31]).expect("HMAC takes keys of any length");
+        mac.update(iv);
+        mac.update(data);
+        mac.verify_truncated_left(tag).map_err(|_| SdkError::DecryptionFailed)?;
+
+        let mut plaintext = data.to_vec();
+        LegacyCipher::new(self.0[..16].into(), iv.into()).apply_keystream(&mut plaintext);
+        Ok(plaintext)
+    }
+
+    pub fn decrypt_utxo(&self, ciphertext: &[u8]) -> Result<LegacyUtxo> {
+        let plaintext = String::from_utf8(self.decrypt(ciphertext)?).map_err(|_| SdkError::InvalidLegacyUtxo)?;
+        let fields: Vec<&str> = plaintext.split('|').collect();
+        let [amount, blinding, index, mint] = fields[..] else {
+            return Err(SdkError::InvalidLegacyUtxo);
+        };
+
+        Ok(LegacyUtxo {
+            amount: amount.parse().map_err(|_| SdkError::InvalidLegacyUtxo)?,
+            blinding: decimal_to_bytes(blinding)?,
+            index: index.parse().map_err(|_| SdkError::InvalidLegacyUtxo)?,
+            mint: decimal_to_bytes(mint)?,
+        })
+    }
+}
+
+fn decimal_to_bytes(decimal: &str) -> Result<[u8; 32]> {
+    let value = BigUint::parse_bytes(decimal.as_bytes(), 10).ok_or(SdkError::InvalidLegacyUtxo)?;
+    let bytes = value.to_bytes_be();
+    if bytes.len() > 32 {
+        return Err(SdkError::InvalidLegacyUtxo);
+    }
+
+    let mut output = [0u8; 32];
+    output[32 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(output)
+}
+
+/// An output decrypted by `decrypt_output`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecryptedOutput {
+    Note(Note),
+    Legacy(LegacyUtxo),
+}
+
+/// Decrypts an `encrypted_output` in either format. Versioned ciphertexts are tried first,
+/// and the legacy format only if `legacy_key` is given.
+pub fn decrypt_output(
+    keypair: &EncryptionKeypair,
+    legacy_key: Option<&LegacyEncryptionKey>,
+    ciphertext: &[u8],
+    commitment: &[u8; 32],
+) -> Result<DecryptedOutput> {
+    let error = match keypair.decrypt_note(ciphertext, commitment) {
+        Ok(note) => return Ok(DecryptedOutput::Note(note)),
+        Err(error) => error,
+    };
+
+    match legacy_key {
+        Some(legacy_key) => legacy_key.decrypt_utxo(ciphertext).map(DecryptedOutput::Legacy),
+        None => Err(error),
+    }
+}
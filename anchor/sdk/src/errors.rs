@@ -0,0 +1,55 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SdkError {
+    #[error("Ciphertext has an unknown version or is too short")]
+    InvalidCiphertext,
+    #[error("Ciphertext can't be decrypted with this key")]
+    DecryptionFailed,
+    #[error("Decrypted legacy UTXO isn't in the amount|blinding|index|mintAddress format")]
+    InvalidLegacyUtxo,
+    #[error("Decrypted note doesn't hash to the output commitment")]
+    CommitmentMismatch,
+    #[error("Detection or clue key has an invalid encoding")]
+    InvalidDetectionKey,
+    #[error("Detection precision must be between 1 and 24 bits")]
+    InvalidPrecision,
+    #[error("Snapshot can't be read or written")]
+    InvalidSnapshot,
+    #[error("Note store can't be read or written")]
+    InvalidNoteStore,
+    #[error("Amount must be greater than zero")]
+    InvalidAmount,
+    #[error("Spendable notes don't cover the amount")]
+    InsufficientFunds,
+    #[error("Inputs of a planned transaction add up to more than a u64")]
+    InputAmountOverflow,
+    #[error("Seed must be 16 to 64 bytes")]
+    InvalidSeed,
+    #[error("Account data is not a commitment account")]
+    InvalidAccountData,
+    #[error("Failed to hash the note")]
+    HashFailed,
+    #[error("Wallet has no registered shielded account")]
+    AccountNotRegistered,
+    #[error("Shielded address has an invalid encoding or checksum")]
+    InvalidAddressEncoding,
+    #[error("Shielded address has an unknown prefix")]
+    UnknownAddressPrefix,
+    #[error("Shielded address has an unsupported version")]
+    UnsupportedAddressVersion,
+    #[error("Claim note has an invalid encoding or checksum")]
+    InvalidClaimNote,
+    #[error("Claim note has an unknown prefix")]
+    UnknownClaimNotePrefix,
+    #[error("Claim note has an unsupported version")]
+    UnsupportedClaimNoteVersion,
+    #[error("Shielded pubkey must be a non-zero field element and the encryption key non-zero")]
+    InvalidAddressKeys,
+    #[error("Batch deposit must have 1 to 8 outputs")]
+    InvalidBatchSize,
+    #[error("Amounts of a batch deposit add up to more than a u64")]
+    BatchAmountOverflow,
+}
+
+pub type Result<T> = std::result::Result<T, SdkError>;
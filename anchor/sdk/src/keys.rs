@@ -0,0 +1,143 @@
+use crate::detection::{DetectionSecretKey, MAX_PRECISION};
+use crate::encryption::EncryptionKeypair;
+use crate::{Result, SdkError};
+use hmac::{Hmac, Mac};
+use light_hasher::{Hasher, Poseidon};
+use num_bigint::BigUint;
+use rand_core::{CryptoRng, RngCore};
+use sha2::Sha512;
+
+const BN254_FIELD_SIZE: &[u8] = b"21888242871839275222246405745257275088548364400416034343698204186575808495617";
+
+/// The key notes are spent with. Its public key `Poseidon(private_key)` owns the notes, as in
+/// the `Keypair` template of the circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpendingKey {
+    private_key: [u8; 32],
+}
+
+impl SpendingKey {
+    /// Reduces `private_key` (big-endian) into the field, like the TypeScript `Keypair`.
+    pub fn new(private_key: [u8; 32]) -> Self {
+        let field_size = BigUint::parse_bytes(BN254_FIELD_SIZE, 10).unwrap();
+        Self { private_key: to_bytes32(&(BigUint::from_bytes_be(&private_key) % field_size)) }
+    }
+
+    pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut private_key = [0u8; 32];
+        rng.fill_bytes(&mut private_key);
+        Self::new(private_key)
+    }
+
+    pub fn private_key(&self) -> [u8; 32] {
+        self.private_key
+    }
+
+    pub fn public_key(&self) -> Result<[u8; 32]> {
+        Poseidon::hashv(&[&self.private_key]).map_err(|_| SdkError::HashFailed)
+    }
+
+    /// `Poseidon(private_key, commitment, index)`, the `Signature` template of the circuit.
+    pub fn sign(&self, commitment: &[u8; 32], index: u64) -> Result<[u8; 32]> {
+        Poseidon::hashv(&[&self.private_key, commitment, &field_element(index)]).map_err(|_| SdkError::HashFailed)
+    }
+
+    /// The nullifier of the note with `commitment` at leaf `index`:
+    /// `Poseidon(commitment, index, sign(commitment, index))`.
+    pub fn nullifier(&self, commitment: &[u8; 32], index: u64) -> Result<[u8; 32]> {
+        let signature = self.sign(commitment, index)?;
+        Poseidon::hashv(&[commitment, &field_element(index), &signature]).map_err(|_| SdkError::HashFailed)
+    }
+}
+
+const MASTER_KEY_DOMAIN: &[u8] = b"zkcash seed";
+const HARDENED: u32 = 1 << 31;
+
+/// First level of every derivation path, so the seed can be shared with other protocols.
+pub const ZKCASH_PURPOSE: u32 = 0x7a6b;
+pub const SPENDING_KEY_INDEX: u32 = 0;
+pub const ENCRYPTION_KEY_INDEX: u32 = 1;
+pub const DETECTION_KEY_INDEX: u32 = 2;
+
+/// A node of the key tree, derived like SLIP-10 with hardened children only: a child can't be
+/// derived from public data, and leaking one child doesn't expose its siblings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtendedKey {
+    pub key: [u8; 32],
+    pub chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    /// The master key of a seed of 16 to 64 bytes, for example a BIP-39 seed.
+    pub fn from_seed(seed: &[u8]) -> Result<Self> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(SdkError::InvalidSeed);
+        }
+        Ok(Self::from_hmac(MASTER_KEY_DOMAIN, &[seed]))
+    }
+
+    /// The hardened child `index'`.
+    pub fn derive_child(&self, index: u32) -> Self {
+        Self::from_hmac(&self.chain_code, &[&[0], &self.key, &(index | HARDENED).to_be_bytes()])
+    }
+
+    pub fn derive_path(&self, path: &[u32]) -> Self {
+        path.iter().fold(self.clone(), |node, index| node.derive_child(*index))
+    }
+
+    fn from_hmac(key: &[u8], data: &[&[u8]]) -> Self {
+        let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+        for chunk in data {
+            mac.update(chunk);
+        }
+        let output = mac.finalize().into_bytes();
+        Self { key: output[..32].try_into().unwrap(), chain_code: output[32..].try_into().unwrap() }
+    }
+}
+
+/// The keys of shielded account `index` of a seed, at `m/purpose'/index'/...`.
+pub struct AccountKeys {
+    pub index: u32,
+    /// At `.../0'`.
+    pub spending_key: SpendingKey,
+    /// At `.../1'`.
+    pub encryption_key: EncryptionKeypair,
+    /// At `.../2'/i'` for every bit of precision.
+    pub detection_key: DetectionSecretKey,
+}
+
+impl AccountKeys {
+    pub fn derive(master_key: &ExtendedKey, index: u32) -> Self {
+        let account = master_key.derive_path(&[ZKCASH_PURPOSE, index]);
+        let detection = account.derive_child(DETECTION_KEY_INDEX);
+
+        Self {
+            index,
+            spending_key: SpendingKey::new(account.derive_child(SPENDING_KEY_INDEX).key),
+            encryption_key: EncryptionKeypair::new(account.derive_child(ENCRYPTION_KEY_INDEX).key),
+            detection_key: DetectionSecretKey::new(core::array::from_fn::<_, MAX_PRECISION, _>(|i| {
+                detection.derive_child(i as u32).key
+            })),
+        }
+    }
+}
+
+fn field_element(value: u64) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[24..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+/// `-value` in the field, big-endian: the public amount of a transaction that takes `value`
+/// out of its inputs.
+pub(crate) fn negated_field_element(value: u64) -> [u8; 32] {
+    let field_size = BigUint::parse_bytes(BN254_FIELD_SIZE, 10).unwrap();
+    to_bytes32(&((field_size.clone() - value) % field_size))
+}
+
+fn to_bytes32(value: &BigUint) -> [u8; 32] {
+    let bytes = value.to_bytes_be();
+    let mut output = [0u8; 32];
+    output[32 - bytes.len()..].copy_from_slice(&bytes);
+    output
+}
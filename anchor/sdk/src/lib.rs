@@ -0,0 +1,49 @@
+//! Client-side helpers for zkcash: notes, their encryption, and reading them back from the
+//! pool's commitment accounts.
+//!
+//! The `encryption` module encrypts notes to the recipient's X25519 key for
+//! `encrypted_output1/2`, and still decrypts the format of the TypeScript client.
+//!
+//! The `detection` module lets a user hand a scanning server a detection key that flags
+//! their outputs, and other outputs at a chosen false positive rate, without being able to
+//! decrypt them.
+//!
+//! The `keys` module derives the spending, encryption and detection keys of any number of
+//! shielded accounts from one seed.
+//!
+//! The `wallet` module finds the notes of a `keys::SpendingKey` in the commitment accounts,
+//! tracks which are spent through their nullifier PDAs, and keeps them in a local store.
+//! `selection` chooses the notes to spend, and makes the dummy inputs and outputs that pad
+//! a transaction to two of each. It also splits amounts into the denominations of a pool
+//! that only accepts fixed amounts.
+//!
+//! The `batch` module plans a batch deposit of many notes from one transfer, and the wallet
+//! finds its notes in the `BatchDepositEvent` of the deposit.
+//!
+//! The `claim` module encodes a note and its spending key as a link that anyone can claim
+//! into their own shielded address.
+//!
+//! The `auditor` module decrypts the ciphertexts that a pool with an auditor key stores
+//! next to every output and checks each note against its output commitment. The ciphertexts
+//! are disclosed voluntarily by senders rather than proven, so the auditor sees which outputs
+//! it can't account for but can't recover them.
+//!
+//! The `registry` module looks up the shielded keys a wallet published with
+//! `register_account`, to send notes to a plain Solana address, and `address` encodes the
+//! same keys as a checksummed shielded address.
+pub mod address;
+pub mod auditor;
+pub mod batch;
+pub mod claim;
+pub mod detection;
+pub mod encryption;
+pub mod errors;
+pub mod keys;
+pub mod note;
+pub mod registry;
+pub mod selection;
+pub mod wallet;
+
+pub use address::{Network, ShieldedAddress};
+pub use errors::{Result, SdkError};
+pub use note::Note;
@@ -0,0 +1,76 @@
+use crate::{Result, SdkError};
+use anchor_lang::prelude::borsh;
+use anchor_lang::{AccountDeserialize, AnchorDeserialize, AnchorSerialize, Discriminator};
+use light_hasher::{Hasher, Poseidon};
+use rand_core::{CryptoRng, RngCore};
+use zkcash::CommitmentAccount;
+
+/// The field element used as the mint of native SOL notes. Clients hash the decimal string
+/// `11111111111111111111111111111112` as a number, so this is that number in big endian.
+pub const SOL_MINT: [u8; 32] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 140, 61, 239, 177, 237, 185, 132,
+    254, 42, 199, 28, 113, 200,
+];
+
+/// A shielded note. Field elements are stored in big endian, as in the circuit inputs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Note {
+    pub amount: u64,
+    /// Poseidon public key of the owner.
+    pub owner: [u8; 32],
+    pub blinding: [u8; 32],
+    pub mint: [u8; 32],
+}
+
+impl Note {
+    pub fn sol(amount: u64, owner: [u8; 32], blinding: [u8; 32]) -> Self {
+        Self { amount, owner, blinding, mint: SOL_MINT }
+    }
+
+    /// A SOL note with a random 248-bit blinding, which is always a field element.
+    pub fn random_sol<R: RngCore + CryptoRng>(amount: u64, owner: [u8; 32], rng: &mut R) -> Self {
+        let mut blinding = [0u8; 32];
+        rng.fill_bytes(&mut blinding[1..]);
+        Self::sol(amount, owner, blinding)
+    }
+
+    /// The output commitment, `Poseidon(amount, owner, blinding, mint)`.
+    pub fn commitment(&self) -> Result<[u8; 32]> {
+        let mut amount = [0u8; 32];
+        amount[24..].copy_from_slice(&self.amount.to_be_bytes());
+
+        Poseidon::hashv(&[&amount, &self.owner, &self.blinding, &self.mint]).map_err(|_| SdkError::HashFailed)
+    }
+}
+
+/// A commitment account written before the auditor output, view tag and tree index were
+/// appended to it. Its output is in tree 0, with no auditor ciphertext and no view tag.
+#[derive(AnchorDeserialize)]
+struct LegacyCommitmentAccount {
+    commitment: [u8; 32],
+    encrypted_output: Vec<u8>,
+    index: u64,
+    bump: u8,
+}
+
+/// Decodes the data of a commitment account, as returned by `getProgramAccounts`. Accounts
+/// of every layout decode: the program allocated them larger than their fields, so the
+/// appended fields of older accounts usually read as zero, and the older layout is read
+/// explicitly when they don't.
+pub fn decode_commitment_account(data: &[u8]) -> Result<CommitmentAccount> {
+    if let Ok(account) = CommitmentAccount::try_deserialize(&mut &data[..]) {
+        return Ok(account);
+    }
+
+    let mut data = data.strip_prefix(CommitmentAccount::DISCRIMINATOR).ok_or(SdkError::InvalidAccountData)?;
+    let legacy = LegacyCommitmentAccount::deserialize(&mut data).map_err(|_| SdkError::InvalidAccountData)?;
+    Ok(CommitmentAccount {
+        commitment: legacy.commitment,
+        encrypted_output: legacy.encrypted_output,
+        index: legacy.index,
+        bump: legacy.bump,
+        auditor_output: Vec::new(),
+        view_tag: None,
+        tree_index: 0,
+    })
+}
@@ -0,0 +1,56 @@
+use crate::{Note, Result, SdkError};
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::AccountDeserialize;
+use rand_core::{CryptoRng, RngCore};
+use zkcash::ShieldedAccount;
+
+pub use zkcash_interface::shielded_account_address;
+
+/// Reads raw account data, for example through `getAccountInfo` of an RPC client.
+pub trait AccountSource {
+    fn get_account_data(&self, address: &Pubkey) -> Option<Vec<u8>>;
+}
+
+/// The shielded keys a wallet published in the registry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShieldedRecipient {
+    pub wallet: Pubkey,
+    /// Poseidon public key the notes are owned by.
+    pub shielded_pubkey: [u8; 32],
+    /// X25519 public key the notes are encrypted to.
+    pub encryption_key: [u8; 32],
+}
+
+impl ShieldedRecipient {
+    pub fn from_account(account: &ShieldedAccount) -> Self {
+        Self {
+            wallet: account.owner,
+            shielded_pubkey: account.shielded_pubkey,
+            encryption_key: account.encryption_key,
+        }
+    }
+
+    /// A new note of `amount` lamports owned by this recipient.
+    pub fn new_output<R: RngCore + CryptoRng>(&self, amount: u64, rng: &mut R) -> Note {
+        Note::random_sol(amount, self.shielded_pubkey, rng)
+    }
+}
+
+pub fn decode_shielded_account(data: &[u8]) -> Result<ShieldedAccount> {
+    ShieldedAccount::try_deserialize(&mut &data[..]).map_err(|_| SdkError::InvalidAccountData)
+}
+
+/// Looks up the shielded keys of `wallet`. Fails if the wallet never registered or deleted
+/// its account.
+pub fn lookup_recipient(source: &impl AccountSource, wallet: &Pubkey) -> Result<ShieldedRecipient> {
+    let data = source
+        .get_account_data(&shielded_account_address(wallet))
+        .ok_or(SdkError::AccountNotRegistered)?;
+    let account = decode_shielded_account(&data)?;
+
+    // The PDA is derived from the wallet, but check the owner in case the source is wrong
+    if account.owner != *wallet {
+        return Err(SdkError::InvalidAccountData);
+    }
+    Ok(ShieldedRecipient::from_account(&account))
+}
@@ -0,0 +1,200 @@
+use crate::encryption::{encrypt_note, EncryptionKeypair};
+use crate::wallet::WalletNote;
+use crate::{Note, Result, SdkError};
+use rand_core::{CryptoRng, RngCore};
+
+/// An input of a planned transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlannedInput {
+    /// The wallet note at this tree and leaf index.
+    Note(u32, u64),
+    /// The change output of the merge at this position of `TransferPlan::merges`, once it has
+    /// been inserted into the tree.
+    Merged(usize),
+    /// A zero-amount note, see `dummy_input`.
+    Dummy,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedTransaction {
+    pub inputs: [PlannedInput; 2],
+    /// The sum of the inputs.
+    pub input_amount: u64,
+}
+
+/// The transactions that move `amount` out of the wallet's notes. Every transaction takes
+/// exactly two inputs, so when more notes are needed they are first merged two by two, each
+/// merge sending the sum back to the wallet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferPlan {
+    pub merges: Vec<PlannedTransaction>,
+    /// The transaction that pays `amount`.
+    pub spend: PlannedTransaction,
+    /// What the spend sends back to the wallet.
+    pub change: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SelectionOptions {
+    /// Spend the smallest other note along with a note that covers the amount on its own,
+    /// instead of a dummy input. This folds small notes into the change, but links both
+    /// notes to the same transaction.
+    pub consolidate: bool,
+}
+
+/// Chooses the notes that pay `amount` of `mint` from the spendable notes.
+///
+/// Every note that is spent together is linked, so the plan uses as few notes as possible:
+/// one note if one covers the amount, the smallest one that does to minimize the change;
+/// otherwise the pair with the smallest sum that covers it; otherwise the largest notes,
+/// merged until two remain.
+pub fn plan_transfer<'a>(
+    notes: impl IntoIterator<Item = &'a WalletNote>,
+    mint: &[u8; 32],
+    amount: u64,
+    options: SelectionOptions,
+) -> Result<TransferPlan> {
+    if amount == 0 {
+        return Err(SdkError::InvalidAmount);
+    }
+    let mut notes: Vec<&WalletNote> = notes
+        .into_iter()
+        .filter(|note| !note.spent && note.note.mint == *mint && note.note.amount > 0)
+        .collect();
+    notes.sort_by_key(|note| (note.note.amount, note.tree_index, note.index));
+
+    let total: u128 = notes.iter().map(|note| note.note.amount as u128).sum();
+    if total < amount as u128 {
+        return Err(SdkError::InsufficientFunds);
+    }
+
+    if let Some(position) = notes.iter().position(|note| note.note.amount >= amount) {
+        let note = notes.remove(position);
+        let second = notes.first().filter(|_| options.consolidate);
+        let inputs = [PlannedInput::Note(note.tree_index, note.index), second.map_or(PlannedInput::Dummy, |other| PlannedInput::Note(other.tree_index, other.index))];
+        let input_amount = add_amounts(note.note.amount, second.map_or(0, |other| other.note.amount))?;
+        return Ok(plan(Vec::new(), inputs, input_amount, amount));
+    }
+
+    if let Some((first, second)) = smallest_covering_pair(&notes, amount) {
+        let inputs = [PlannedInput::Note(notes[first].tree_index, notes[first].index), PlannedInput::Note(notes[second].tree_index, notes[second].index)];
+        let input_amount = add_amounts(notes[first].note.amount, notes[second].note.amount)?;
+        return Ok(plan(Vec::new(), inputs, input_amount, amount));
+    }
+
+    // Take the largest notes until they cover the amount, and merge all but the last one
+    let mut selected = Vec::new();
+    let mut selected_amount = 0u64;
+    for note in notes.iter().rev() {
+        selected.push(*note);
+        selected_amount = add_amounts(selected_amount, note.note.amount)?;
+        if selected_amount >= amount {
+            break;
+        }
+    }
+
+    let mut merges = Vec::new();
+    let mut merged = PlannedInput::Note(selected[0].tree_index, selected[0].index);
+    let mut merged_amount = selected[0].note.amount;
+    for note in &selected[1..selected.len() - 1] {
+        merged_amount = add_amounts(merged_amount, note.note.amount)?;
+        merges.push(PlannedTransaction { inputs: [merged, PlannedInput::Note(note.tree_index, note.index)], input_amount: merged_amount });
+        merged = PlannedInput::Merged(merges.len() - 1);
+    }
+    let last = selected[selected.len() - 1];
+    let input_amount = add_amounts(merged_amount, last.note.amount)?;
+    Ok(plan(merges, [merged, PlannedInput::Note(last.tree_index, last.index)], input_amount, amount))
+}
+
+/// The inputs of a transaction are u64 amounts, so a plan can't combine notes past that.
+fn add_amounts(left: u64, right: u64) -> Result<u64> {
+    left.checked_add(right).ok_or(SdkError::InputAmountOverflow)
+}
+
+fn plan(merges: Vec<PlannedTransaction>, inputs: [PlannedInput; 2], input_amount: u64, amount: u64) -> TransferPlan {
+    TransferPlan { merges, spend: PlannedTransaction { inputs, input_amount }, change: input_amount - amount }
+}
+
+/// Two pointers over the notes sorted by amount.
+fn smallest_covering_pair(notes: &[&WalletNote], amount: u64) -> Option<(usize, usize)> {
+    let mut best: Option<(u128, usize, usize)> = None;
+    let (mut low, mut high) = (0, notes.len().checked_sub(1)?);
+    while low < high {
+        let sum = notes[low].note.amount as u128 + notes[high].note.amount as u128;
+        if sum >= amount as u128 {
+            if best.map_or(true, |(best_sum, _, _)| sum < best_sum) {
+                best = Some((sum, low, high));
+            }
+            high -= 1;
+        } else {
+            low += 1;
+        }
+    }
+    best.map(|(_, low, high)| (low, high))
+}
+
+/// An amount split into the denominations of a pool, see `split_into_denominations`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DenominationSplit {
+    /// One deposit or withdrawal per entry, largest first.
+    pub amounts: Vec<u64>,
+    /// What can't be made of the denominations. It stays in the wallet, or in the pool for a
+    /// withdrawal.
+    pub remainder: u64,
+}
+
+/// Splits `amount` into deposits or withdrawals that a pool with fixed denominations accepts.
+/// `denominations` can be the padded `denominations` of the pool config, zeros are ignored,
+/// and a pool without denominations takes the amount at once.
+///
+/// The largest denomination that fits is taken first. This uses the fewest transactions when
+/// each denomination divides the next one, like 0.1, 1 and 10 SOL, which is how pools should
+/// pick them.
+pub fn split_into_denominations(amount: u64, denominations: &[u64]) -> DenominationSplit {
+    let mut denominations: Vec<u64> = denominations.iter().copied().filter(|denomination| *denomination > 0).collect();
+    if denominations.is_empty() {
+        let amounts = if amount > 0 { vec![amount] } else { vec![] };
+        return DenominationSplit { amounts, remainder: 0 };
+    }
+    denominations.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut amounts = Vec::new();
+    let mut remainder = amount;
+    for denomination in denominations {
+        let count = remainder / denomination;
+        amounts.extend(std::iter::repeat(denomination).take(count as usize));
+        remainder %= denomination;
+    }
+    DenominationSplit { amounts, remainder }
+}
+
+/// A zero-amount input owned by `owner`. The circuit skips the Merkle proof of inputs without
+/// an amount, so it is spent at leaf index 0, and its random blinding gives it a fresh
+/// nullifier like any other input.
+pub fn dummy_input<R: RngCore + CryptoRng>(owner: [u8; 32], mint: [u8; 32], rng: &mut R) -> Note {
+    Note { amount: 0, owner, blinding: random_field_element(rng), mint }
+}
+
+/// A zero-amount output with its commitment and encrypted output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DummyOutput {
+    pub note: Note,
+    pub commitment: [u8; 32],
+    pub encrypted_output: Vec<u8>,
+}
+
+/// A zero-amount output for a random owner, encrypted to a random key. Notes have a fixed
+/// size, so its ciphertext has the length of a real one, with a random clue and view tag.
+pub fn dummy_output<R: RngCore + CryptoRng>(mint: [u8; 32], rng: &mut R) -> Result<DummyOutput> {
+    let note = Note { amount: 0, owner: random_field_element(rng), blinding: random_field_element(rng), mint };
+    let commitment = note.commitment()?;
+    let encrypted_output = encrypt_note(&EncryptionKeypair::random(rng).public_key(), &commitment, &note, rng);
+    Ok(DummyOutput { note, commitment, encrypted_output })
+}
+
+/// 248 random bits, which are always a field element.
+fn random_field_element<R: RngCore + CryptoRng>(rng: &mut R) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    rng.fill_bytes(&mut bytes[1..]);
+    bytes
+}
@@ -0,0 +1,276 @@
+use crate::batch::batch_deposit_accounts;
+use crate::detection::read_snapshot;
+use crate::encryption::{EncryptionKeypair, LegacyEncryptionKey};
+use crate::keys::{AccountKeys, ExtendedKey, SpendingKey};
+use crate::note::decode_commitment_account;
+use crate::registry::AccountSource;
+use crate::{Note, Result, SdkError};
+use anchor_lang::prelude::borsh;
+use anchor_lang::{AnchorDeserialize, AnchorSerialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use zkcash::{BatchDepositEvent, CommitmentAccount};
+use zkcash_interface::nullifier_account_addresses;
+
+pub const NOTE_STORE_VERSION: u8 = 2;
+
+/// A note of the wallet, with what is needed to spend it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WalletNote {
+    /// The tree the note is in. Leaf indices are per tree, so notes are keyed by both.
+    pub tree_index: u32,
+    pub index: u64,
+    pub commitment: [u8; 32],
+    pub note: Note,
+    pub nullifier: [u8; 32],
+    pub spent: bool,
+}
+
+/// A note saved by version 1 of the store, before pools had several trees. Its notes are
+/// all in tree 0.
+#[derive(AnchorDeserialize)]
+struct LegacyWalletNote {
+    index: u64,
+    commitment: [u8; 32],
+    note: Note,
+    nullifier: [u8; 32],
+    spent: bool,
+}
+
+impl From<LegacyWalletNote> for WalletNote {
+    fn from(note: LegacyWalletNote) -> Self {
+        WalletNote {
+            tree_index: 0,
+            index: note.index,
+            commitment: note.commitment,
+            note: note.note,
+            nullifier: note.nullifier,
+            spent: note.spent,
+        }
+    }
+}
+
+/// The notes found so far, keyed by tree and leaf index. Saved as `version || borsh(notes)`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NoteStore {
+    notes: BTreeMap<(u32, u64), WalletNote>,
+}
+
+impl NoteStore {
+    /// Loads the store at `path`, or an empty store if there is none yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(_) => return Err(SdkError::InvalidNoteStore),
+        };
+        let notes = match data.split_first() {
+            Some((&NOTE_STORE_VERSION, data)) => decode_notes::<WalletNote>(data)?,
+            Some((1, data)) => decode_notes::<LegacyWalletNote>(data)?.into_iter().map(WalletNote::from).collect(),
+            _ => return Err(SdkError::InvalidNoteStore),
+        };
+        Ok(Self { notes: notes.into_iter().map(|note| ((note.tree_index, note.index), note)).collect() })
+    }
+
+    /// Writes the store to a temporary file and moves it over `path`, so a crash never
+    /// leaves a partial store behind.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut data = vec![NOTE_STORE_VERSION];
+        self.notes.values().cloned().collect::<Vec<_>>().serialize(&mut data).map_err(|_| SdkError::InvalidNoteStore)?;
+
+        let temporary_path = path.with_extension("tmp");
+        fs::write(&temporary_path, data).map_err(|_| SdkError::InvalidNoteStore)?;
+        fs::rename(&temporary_path, path).map_err(|_| SdkError::InvalidNoteStore)
+    }
+
+    pub fn insert(&mut self, note: WalletNote) -> bool {
+        self.notes.insert((note.tree_index, note.index), note).is_none()
+    }
+
+    pub fn contains(&self, tree_index: u32, index: u64) -> bool {
+        self.notes.contains_key(&(tree_index, index))
+    }
+
+    /// All notes, spent or not, ordered by tree and leaf index.
+    pub fn notes(&self) -> impl Iterator<Item = &WalletNote> {
+        self.notes.values()
+    }
+}
+
+fn decode_notes<T: AnchorDeserialize>(mut data: &[u8]) -> Result<Vec<T>> {
+    let notes = Vec::<T>::deserialize(&mut data).map_err(|_| SdkError::InvalidNoteStore)?;
+    if !data.is_empty() {
+        return Err(SdkError::InvalidNoteStore);
+    }
+    Ok(notes)
+}
+
+/// Finds the wallet's notes in the pool's commitment accounts and tracks which are spent.
+///
+/// Outputs are trial-decrypted with the encryption key, skipping those whose view tag
+/// doesn't match, and with the legacy key if there is one. Only notes owned by the spending
+/// key and matching their commitment are kept. A note is spent once one of the nullifier
+/// PDAs of its nullifier exists.
+pub struct Wallet {
+    spending_key: SpendingKey,
+    public_key: [u8; 32],
+    encryption_key: EncryptionKeypair,
+    legacy_key: Option<LegacyEncryptionKey>,
+    store: NoteStore,
+}
+
+impl Wallet {
+    pub fn new(
+        spending_key: SpendingKey,
+        encryption_key: EncryptionKeypair,
+        legacy_key: Option<LegacyEncryptionKey>,
+        store: NoteStore,
+    ) -> Result<Self> {
+        let public_key = spending_key.public_key()?;
+        Ok(Self { spending_key, public_key, encryption_key, legacy_key, store })
+    }
+
+    /// The wallet of a shielded account derived from a seed.
+    pub fn from_account_keys(keys: AccountKeys, store: NoteStore) -> Result<Self> {
+        Self::new(keys.spending_key, keys.encryption_key, None, store)
+    }
+
+    /// The wallet of the keys the TypeScript client derives from a signature of
+    /// `LEGACY_SIGN_IN_MESSAGE`. It still finds legacy notes, and receives new notes with an
+    /// encryption key derived from the same signature.
+    pub fn from_wallet_signature(signature: &[u8; 64], store: NoteStore) -> Result<Self> {
+        let legacy_key = LegacyEncryptionKey::from_wallet_signature(signature);
+        Self::new(legacy_key.spending_key(), legacy_key.encryption_keypair(), Some(legacy_key), store)
+    }
+
+    pub fn spending_key(&self) -> &SpendingKey {
+        &self.spending_key
+    }
+
+    pub fn encryption_key(&self) -> &EncryptionKeypair {
+        &self.encryption_key
+    }
+
+    pub fn store(&self) -> &NoteStore {
+        &self.store
+    }
+
+    /// Adds the output if it is ours. Returns whether a new note was found.
+    pub fn ingest(&mut self, account: &CommitmentAccount) -> Result<bool> {
+        if self.store.contains(account.tree_index, account.index) {
+            return Ok(false);
+        }
+        let Some(note) = self.decrypt(account) else {
+            return Ok(false);
+        };
+        if note.owner != self.public_key || note.commitment()? != account.commitment {
+            return Ok(false);
+        }
+
+        let nullifier = self.spending_key.nullifier(&account.commitment, account.index)?;
+        Ok(self.store.insert(WalletNote {
+            tree_index: account.tree_index,
+            index: account.index,
+            commitment: account.commitment,
+            note,
+            nullifier,
+            spent: false,
+        }))
+    }
+
+    /// Ingests raw commitment account data, as returned by `getProgramAccounts`.
+    pub fn ingest_account_data(&mut self, data: &[u8]) -> Result<bool> {
+        self.ingest(&decode_commitment_account(data)?)
+    }
+
+    /// Ingests the outputs of a batch deposit. Returns the number of new notes.
+    pub fn ingest_batch_deposit(&mut self, event: &BatchDepositEvent) -> Result<usize> {
+        let mut found = 0;
+        for account in batch_deposit_accounts(event) {
+            found += self.ingest(&account)? as usize;
+        }
+        Ok(found)
+    }
+
+    /// Ingests a snapshot written by `detection::write_snapshot`. Returns the number of new notes.
+    pub fn ingest_snapshot(&mut self, reader: impl Read) -> Result<usize> {
+        let mut found = 0;
+        for account in read_snapshot(reader)? {
+            found += self.ingest(&account)? as usize;
+        }
+        Ok(found)
+    }
+
+    /// Marks the notes whose nullifier was recorded on chain as spent.
+    pub fn refresh_spent(&mut self, source: &impl AccountSource) {
+        for note in self.store.notes.values_mut().filter(|note| !note.spent) {
+            note.spent = nullifier_account_addresses(&note.nullifier)
+                .iter()
+                .any(|address| source.get_account_data(address).is_some());
+        }
+    }
+
+    pub fn spendable_notes(&self) -> impl Iterator<Item = &WalletNote> {
+        self.store.notes().filter(|note| !note.spent)
+    }
+
+    /// The spendable balance of every mint the wallet holds notes of.
+    pub fn balances(&self) -> BTreeMap<[u8; 32], u64> {
+        let mut balances = BTreeMap::new();
+        for note in self.spendable_notes() {
+            let balance: &mut u64 = balances.entry(note.note.mint).or_default();
+            *balance = balance.saturating_add(note.note.amount);
+        }
+        balances
+    }
+
+    fn decrypt(&self, account: &CommitmentAccount) -> Option<Note> {
+        if self.encryption_key.is_candidate(account) {
+            if let Ok(note) = self.encryption_key.decrypt_note(&account.encrypted_output, &account.commitment) {
+                return Some(note);
+            }
+        }
+        // Legacy outputs don't store the owner, the commitment check above tells if it's ours
+        let utxo = self.legacy_key.as_ref()?.decrypt_utxo(&account.encrypted_output).ok()?;
+        Some(utxo.into_note(self.public_key))
+    }
+}
+
+/// A shielded account found by `recover_accounts`.
+pub struct RecoveredAccount {
+    pub index: u32,
+    pub wallet: Wallet,
+}
+
+/// Finds the used shielded accounts of a seed. Accounts are derived in order and scanned
+/// against `commitment_accounts`, and the search stops after `gap_limit` accounts in a row
+/// without any note, like the gap limit of BIP-44 wallets.
+pub fn recover_accounts(
+    master_key: &ExtendedKey,
+    commitment_accounts: &[CommitmentAccount],
+    gap_limit: u32,
+) -> Result<Vec<RecoveredAccount>> {
+    let mut recovered = Vec::new();
+    let mut unused = 0;
+    let mut index = 0;
+    while unused < gap_limit {
+        let mut wallet = Wallet::from_account_keys(AccountKeys::derive(master_key, index), NoteStore::default())?;
+        for account in commitment_accounts {
+            wallet.ingest(account)?;
+        }
+
+        if wallet.store().notes().next().is_some() {
+            recovered.push(RecoveredAccount { index, wallet });
+            unused = 0;
+        } else {
+            unused += 1;
+        }
+        let Some(next) = index.checked_add(1) else {
+            break;
+        };
+        index = next;
+    }
+    Ok(recovered)
+}
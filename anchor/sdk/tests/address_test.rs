@@ -0,0 +1,70 @@
+use zkcash_sdk::address::{DEVNET_HRP, MAINNET_HRP};
+use zkcash_sdk::{Network, SdkError, ShieldedAddress};
+
+fn test_address(network: Network) -> ShieldedAddress {
+    let mut shielded_pubkey = [0u8; 32];
+    shielded_pubkey[1..].copy_from_slice(&[0x5a; 31]);
+    ShieldedAddress::new(network, shielded_pubkey, [0x33; 32]).unwrap()
+}
+
+#[test]
+fn test_round_trip() {
+    for network in [Network::Mainnet, Network::Devnet] {
+        let address = test_address(network);
+        let encoded = address.encode();
+        assert!(encoded.starts_with(&format!("{}1", network.hrp())));
+        assert_eq!(ShieldedAddress::decode(&encoded), Ok(address.clone()));
+        assert_eq!(encoded.parse::<ShieldedAddress>(), Ok(address));
+    }
+    assert_ne!(MAINNET_HRP, DEVNET_HRP);
+}
+
+#[test]
+fn test_decode_accepts_uppercase() {
+    let encoded = test_address(Network::Mainnet).encode().to_uppercase();
+    assert_eq!(ShieldedAddress::decode(&encoded), Ok(test_address(Network::Mainnet)));
+}
+
+#[test]
+fn test_typo_is_rejected() {
+    let encoded = test_address(Network::Mainnet).encode();
+    let mut chars: Vec<char> = encoded.chars().collect();
+    let i = chars.len() / 2;
+    chars[i] = if chars[i] == 'q' { 'p' } else { 'q' };
+    let typo: String = chars.into_iter().collect();
+
+    assert_eq!(ShieldedAddress::decode(&typo), Err(SdkError::InvalidAddressEncoding));
+}
+
+#[test]
+fn test_bech32_checksum_is_rejected() {
+    let address = test_address(Network::Mainnet);
+    let mut payload = vec![0u8];
+    payload.extend_from_slice(&address.shielded_pubkey);
+    payload.extend_from_slice(&address.encryption_key);
+    let bech32 = bech32::encode::<bech32::Bech32>(bech32::Hrp::parse(MAINNET_HRP).unwrap(), &payload).unwrap();
+
+    assert_eq!(ShieldedAddress::decode(&bech32), Err(SdkError::InvalidAddressEncoding));
+}
+
+#[test]
+fn test_unknown_prefix_and_version_are_rejected() {
+    let address = test_address(Network::Mainnet);
+    let mut payload = vec![0u8];
+    payload.extend_from_slice(&address.shielded_pubkey);
+    payload.extend_from_slice(&address.encryption_key);
+
+    let other_prefix = bech32::encode::<bech32::Bech32m>(bech32::Hrp::parse("sol").unwrap(), &payload).unwrap();
+    assert_eq!(ShieldedAddress::decode(&other_prefix), Err(SdkError::UnknownAddressPrefix));
+
+    payload[0] = 1;
+    let other_version = bech32::encode::<bech32::Bech32m>(bech32::Hrp::parse(MAINNET_HRP).unwrap(), &payload).unwrap();
+    assert_eq!(ShieldedAddress::decode(&other_version), Err(SdkError::UnsupportedAddressVersion));
+}
+
+#[test]
+fn test_keys_are_validated() {
+    assert_eq!(ShieldedAddress::new(Network::Mainnet, [0xff; 32], [0x33; 32]), Err(SdkError::InvalidAddressKeys));
+    assert_eq!(ShieldedAddress::new(Network::Mainnet, [0u8; 32], [0x33; 32]), Err(SdkError::InvalidAddressKeys));
+    assert_eq!(ShieldedAddress::new(Network::Mainnet, [1u8; 32], [0u8; 32]), Err(SdkError::InvalidAddressKeys));
+}
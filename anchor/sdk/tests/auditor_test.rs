@@ -0,0 +1,132 @@
+use rand_core::OsRng;
+use zkcash::CommitmentAccount;
+use zkcash_sdk::auditor::{encrypt_for_auditor, Auditor, AUDITOR_CIPHERTEXT_VERSION};
+use zkcash_sdk::encryption::envelope_version;
+use zkcash_sdk::note::decode_commitment_account;
+use zkcash_sdk::{Note, SdkError};
+use anchor_lang::{AccountSerialize, AnchorSerialize, Discriminator};
+
+fn test_note(amount: u64) -> Note {
+    let mut blinding = [0u8; 32];
+    blinding[31] = amount as u8;
+    Note::sol(amount, [7u8; 32], blinding)
+}
+
+fn commitment_account(note: &Note, index: u64, auditor_output: Vec<u8>) -> CommitmentAccount {
+    CommitmentAccount {
+        commitment: note.commitment().unwrap(),
+        encrypted_output: vec![1, 2, 3],
+        index,
+        bump: 255,
+        auditor_output,
+        view_tag: None,
+        tree_index: 0,
+    }
+}
+
+#[test]
+fn test_encrypt_decrypt_round_trip() {
+    let auditor = Auditor::random(&mut OsRng);
+    let note = test_note(1_000_000);
+    let commitment = note.commitment().unwrap();
+
+    let ciphertext = encrypt_for_auditor(&auditor.public_key(), &commitment, &note, &mut OsRng);
+    assert_eq!(envelope_version(&ciphertext), Some(AUDITOR_CIPHERTEXT_VERSION));
+    assert_eq!(auditor.decrypt(&ciphertext, &commitment), Ok(note));
+}
+
+#[test]
+fn test_decrypt_rejects_wrong_key_and_commitment() {
+    let auditor = Auditor::random(&mut OsRng);
+    let note = test_note(5);
+    let commitment = note.commitment().unwrap();
+    let ciphertext = encrypt_for_auditor(&auditor.public_key(), &commitment, &note, &mut OsRng);
+
+    let other_auditor = Auditor::random(&mut OsRng);
+    assert_eq!(other_auditor.decrypt(&ciphertext, &commitment), Err(SdkError::DecryptionFailed));
+
+    // The commitment is authenticated, so a ciphertext can't be moved to another output
+    let other_commitment = test_note(6).commitment().unwrap();
+    assert_eq!(auditor.decrypt(&ciphertext, &other_commitment), Err(SdkError::DecryptionFailed));
+
+    assert_eq!(auditor.decrypt(&ciphertext[..10], &commitment), Err(SdkError::InvalidCiphertext));
+}
+
+#[test]
+fn test_decrypt_rejects_note_that_does_not_match_commitment() {
+    let auditor = Auditor::random(&mut OsRng);
+    let note = test_note(5);
+    // A sender could encrypt another note under the right commitment
+    let commitment = test_note(6).commitment().unwrap();
+    let ciphertext = encrypt_for_auditor(&auditor.public_key(), &commitment, &note, &mut OsRng);
+
+    assert_eq!(auditor.decrypt(&ciphertext, &commitment), Err(SdkError::CommitmentMismatch));
+}
+
+#[test]
+fn test_audit_history() {
+    let auditor = Auditor::random(&mut OsRng);
+    let notes = [test_note(1), test_note(2), test_note(3)];
+    let accounts = [
+        commitment_account(&notes[2], 2, encrypt_for_auditor(&auditor.public_key(), &notes[2].commitment().unwrap(), &notes[2], &mut OsRng)),
+        // Made before the pool had an auditor
+        commitment_account(&notes[0], 0, Vec::new()),
+        commitment_account(&notes[1], 1, encrypt_for_auditor(&auditor.public_key(), &notes[1].commitment().unwrap(), &notes[1], &mut OsRng)),
+    ];
+
+    let entries = auditor.audit(&accounts);
+    let indices: Vec<u64> = entries.iter().map(|entry| entry.index).collect();
+    assert_eq!(indices, vec![0, 1, 2]);
+    assert_eq!(entries[0].note, None);
+    assert_eq!(entries[1].note, Some(Ok(notes[1].clone())));
+    assert_eq!(entries[2].note, Some(Ok(notes[2].clone())));
+    assert!(!entries[0].is_verified());
+    assert!(entries[1].is_verified());
+}
+
+#[test]
+fn test_audit_flags_outputs_hidden_from_the_auditor() {
+    let auditor = Auditor::random(&mut OsRng);
+    let notes = [test_note(1), test_note(2)];
+    // The program takes any non-empty ciphertext, including one for another note
+    let decoy = encrypt_for_auditor(&auditor.public_key(), &notes[1].commitment().unwrap(), &notes[0], &mut OsRng);
+    let accounts = [commitment_account(&notes[1], 0, decoy), commitment_account(&notes[0], 1, vec![1u8; 80])];
+
+    let entries = auditor.audit(&accounts);
+    assert_eq!(entries[0].note, Some(Err(SdkError::CommitmentMismatch)));
+    assert!(entries.iter().all(|entry| !entry.is_verified()));
+}
+
+#[test]
+fn test_decode_commitment_account() {
+    let note = test_note(1);
+    let account = commitment_account(&note, 4, vec![9; 10]);
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    assert_eq!(&data[..8], CommitmentAccount::DISCRIMINATOR);
+
+    let decoded = decode_commitment_account(&data).unwrap();
+    assert_eq!(decoded.index, 4);
+    assert_eq!(decoded.auditor_output, vec![9; 10]);
+
+    assert!(decode_commitment_account(&data[8..]).is_err());
+}
+
+#[test]
+fn test_decode_legacy_commitment_account() {
+    let note = test_note(1);
+    let mut data = CommitmentAccount::DISCRIMINATOR.to_vec();
+    (note.commitment().unwrap(), vec![1u8, 2, 3], 4u64, 255u8).serialize(&mut data).unwrap();
+    let serialized = |account: CommitmentAccount| {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data
+    };
+    let expected = serialized(commitment_account(&note, 4, Vec::new()));
+
+    assert_eq!(serialized(decode_commitment_account(&data).unwrap()), expected);
+
+    // The space the program allocated for the old struct, past its serialized fields
+    data.resize(8 + 72 + 3, 0);
+    assert_eq!(serialized(decode_commitment_account(&data).unwrap()), expected);
+}
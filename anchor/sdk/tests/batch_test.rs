@@ -0,0 +1,89 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::{AnchorSerialize, Discriminator};
+use rand_core::OsRng;
+use zkcash::batch_deposit::{PendingBatch, BATCH_DEPOSIT_OUTPUTS, MAX_BATCH_CIPHERTEXT_LEN};
+use zkcash::BatchDepositEvent;
+use zkcash_sdk::auditor::Auditor;
+use zkcash_sdk::batch::{batch_deposit_plan, decode_batch_deposit_event};
+use zkcash_sdk::encryption::EncryptionKeypair;
+use zkcash_sdk::keys::SpendingKey;
+use zkcash_sdk::wallet::{NoteStore, Wallet};
+use zkcash_sdk::{Network, SdkError, ShieldedAddress};
+
+fn test_recipient() -> (Wallet, ShieldedAddress) {
+    let spending_key = SpendingKey::random(&mut OsRng);
+    let encryption_key = EncryptionKeypair::random(&mut OsRng);
+    let address = ShieldedAddress::new(Network::Devnet, spending_key.public_key().unwrap(), encryption_key.public_key()).unwrap();
+    (Wallet::new(spending_key, encryption_key, None, NoteStore::default()).unwrap(), address)
+}
+
+#[test]
+fn test_batch_deposit_plan() {
+    let (_, alice) = test_recipient();
+    let (_, bob) = test_recipient();
+    let plan = batch_deposit_plan(&[(alice.clone(), 1_000), (bob, 2_500)], &[0u8; 32], &mut OsRng).unwrap();
+
+    assert_eq!(plan.amount, 3_500);
+    assert_eq!(plan.notes.iter().map(|note| note.amount).collect::<Vec<_>>(), vec![1_000, 2_500]);
+    assert_eq!(plan.notes[0].owner, alice.shielded_pubkey);
+
+    let commitments: Vec<[u8; 32]> = plan.outputs.iter().map(|output| output.commitment).collect();
+    assert_eq!(Some(plan.outputs_hash), PendingBatch::outputs_hash(&commitments));
+    for (output, note) in plan.outputs.iter().zip(&plan.notes) {
+        assert_eq!(output.commitment, note.commitment().unwrap());
+        assert!(output.auditor_output.is_empty(), "The pool has no auditor");
+        assert!(output.encrypted_output.len() <= MAX_BATCH_CIPHERTEXT_LEN);
+        assert!(PendingBatch::is_valid_output(output));
+    }
+}
+
+#[test]
+fn test_batch_deposit_plan_encrypts_outputs_to_auditor() {
+    let (_, alice) = test_recipient();
+    let auditor = Auditor::random(&mut OsRng);
+    let plan = batch_deposit_plan(&[(alice.clone(), 1_000), (alice, 2_000)], &auditor.public_key(), &mut OsRng).unwrap();
+
+    for (output, note) in plan.outputs.iter().zip(&plan.notes) {
+        assert!(output.auditor_output.len() <= MAX_BATCH_CIPHERTEXT_LEN);
+        assert_eq!(auditor.decrypt(&output.auditor_output, &output.commitment).as_ref(), Ok(note));
+    }
+}
+
+#[test]
+fn test_batch_deposit_plan_rejects_invalid_batches() {
+    let (_, alice) = test_recipient();
+
+    assert_eq!(batch_deposit_plan(&[], &[0u8; 32], &mut OsRng).err(), Some(SdkError::InvalidBatchSize));
+    let too_many = vec![(alice.clone(), 1); BATCH_DEPOSIT_OUTPUTS + 1];
+    assert_eq!(batch_deposit_plan(&too_many, &[0u8; 32], &mut OsRng).err(), Some(SdkError::InvalidBatchSize));
+    assert_eq!(
+        batch_deposit_plan(&[(alice.clone(), 0)], &[0u8; 32], &mut OsRng).err(),
+        Some(SdkError::InvalidAmount)
+    );
+    assert_eq!(
+        batch_deposit_plan(&[(alice.clone(), u64::MAX), (alice, 1)], &[0u8; 32], &mut OsRng).err(),
+        Some(SdkError::BatchAmountOverflow)
+    );
+}
+
+#[test]
+fn test_wallet_ingests_batch_deposit() {
+    let (mut alice_wallet, alice) = test_recipient();
+    let (mut bob_wallet, bob) = test_recipient();
+    let plan = batch_deposit_plan(&[(alice.clone(), 1_000), (bob, 2_000), (alice, 3_000)], &[0u8; 32], &mut OsRng).unwrap();
+
+    let event = BatchDepositEvent { tree_account: Pubkey::new_unique(), tree_index: 2, first_leaf_index: 40, outputs: plan.outputs };
+    let mut data = BatchDepositEvent::DISCRIMINATOR.to_vec();
+    event.serialize(&mut data).unwrap();
+    let event = decode_batch_deposit_event(&data).unwrap();
+
+    assert_eq!(alice_wallet.ingest_batch_deposit(&event), Ok(2));
+    let notes: Vec<_> = alice_wallet.store().notes().map(|note| (note.tree_index, note.index, note.note.amount)).collect();
+    assert_eq!(notes, vec![(2, 40, 1_000), (2, 42, 3_000)]);
+    assert_eq!(alice_wallet.ingest_batch_deposit(&event), Ok(0), "Notes are only added once");
+
+    assert_eq!(bob_wallet.ingest_batch_deposit(&event), Ok(1));
+    assert_eq!(bob_wallet.store().notes().next().unwrap().index, 41);
+
+    assert_eq!(decode_batch_deposit_event(&data[8..]).err(), Some(SdkError::InvalidAccountData));
+}
@@ -0,0 +1,120 @@
+use anchor_lang::prelude::Pubkey;
+use bech32::{Bech32m, Hrp};
+use rand_core::OsRng;
+use std::collections::HashMap;
+use zkcash::utils::{check_public_amount, has_auditor_outputs};
+use zkcash_interface::nullifier_account_addresses;
+use zkcash_sdk::auditor::Auditor;
+use zkcash_sdk::claim::{claim_transaction, ClaimNote};
+use zkcash_sdk::encryption::EncryptionKeypair;
+use zkcash_sdk::keys::SpendingKey;
+use zkcash_sdk::note::SOL_MINT;
+use zkcash_sdk::registry::AccountSource;
+use zkcash_sdk::{Network, SdkError, ShieldedAddress};
+
+struct TestSource(HashMap<Pubkey, Vec<u8>>);
+
+impl AccountSource for TestSource {
+    fn get_account_data(&self, address: &Pubkey) -> Option<Vec<u8>> {
+        self.0.get(address).cloned()
+    }
+}
+
+fn test_claim_note(network: Network) -> ClaimNote {
+    let (spending_key, note) = ClaimNote::new_note(2_000_000, SOL_MINT, &mut OsRng).unwrap();
+    ClaimNote { network, pool: Pubkey::new_unique(), tree_index: 3, spending_key, note, leaf_index: 41 }
+}
+
+#[test]
+fn test_encode_round_trip() {
+    for network in [Network::Mainnet, Network::Devnet] {
+        let claim_note = test_claim_note(network);
+        assert_eq!(ClaimNote::decode(&claim_note.encode()), Ok(claim_note.clone()));
+
+        let url = claim_note.to_url("https://pay.example/claim");
+        assert!(url.starts_with("https://pay.example/claim#zkcnote"));
+        assert_eq!(ClaimNote::from_url(&url), Ok(claim_note));
+    }
+}
+
+#[test]
+fn test_decode_rejects_corrupted_notes() {
+    let encoded = test_claim_note(Network::Mainnet).encode();
+    let mut typo = encoded.clone().into_bytes();
+    let last = typo.len() - 1;
+    typo[last] = if typo[last] == b'q' { b'p' } else { b'q' };
+    assert_eq!(ClaimNote::decode(std::str::from_utf8(&typo).unwrap()), Err(SdkError::InvalidClaimNote));
+
+    let address = ShieldedAddress::new(Network::Mainnet, [1u8; 32], [2u8; 32]).unwrap().encode();
+    assert_eq!(ClaimNote::decode(&address), Err(SdkError::UnknownClaimNotePrefix));
+
+    let hrp = Hrp::parse("zkcnote").unwrap();
+    let mut payload = vec![0u8; 141 + 8];
+    payload[0] = 1;
+    let next_version = bech32::encode::<Bech32m>(hrp, &payload).unwrap();
+    assert_eq!(ClaimNote::decode(&next_version), Err(SdkError::UnsupportedClaimNoteVersion));
+}
+
+#[test]
+fn test_claim_transaction_sweeps_to_claimant() {
+    let claim_note = test_claim_note(Network::Devnet);
+    let claimant_key = SpendingKey::random(&mut OsRng);
+    let claimant_encryption = EncryptionKeypair::random(&mut OsRng);
+    let claimant = ShieldedAddress::new(Network::Devnet, claimant_key.public_key().unwrap(), claimant_encryption.public_key()).unwrap();
+
+    let transaction = claim_transaction(&claim_note, &claimant, Pubkey::new_unique(), 5_000, &[0u8; 32], &mut OsRng).unwrap();
+    assert_eq!(transaction.inputs[0].note, claim_note.note);
+    assert_eq!(transaction.inputs[0].nullifier, claim_note.nullifier().unwrap());
+    assert_eq!(transaction.inputs[1].note.amount, 0);
+    assert_eq!(transaction.ext_data.ext_amount, 0);
+    assert_eq!(transaction.ext_data.fee, 5_000);
+    assert!(transaction.ext_data.auditor_outputs.is_none(), "The pool has no auditor");
+    assert!(check_public_amount(transaction.ext_data.ext_amount, transaction.ext_data.fee, transaction.public_amount));
+
+    // The claimant finds the swept note in one of the two outputs
+    let encrypted_outputs = [&transaction.ext_data.encrypted_output1, &transaction.ext_data.encrypted_output2];
+    let claimed: Vec<_> = (0..2)
+        .filter_map(|i| claimant_encryption.decrypt_note(encrypted_outputs[i], &transaction.output_commitments[i]).ok())
+        .collect();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].amount, 1_995_000);
+    assert_eq!(claimed[0].owner, claimant.shielded_pubkey);
+    let total: u64 = transaction.outputs.iter().map(|note| note.amount).sum();
+    assert_eq!(total, 1_995_000);
+
+    assert_eq!(
+        claim_transaction(&claim_note, &claimant, Pubkey::new_unique(), 2_000_001, &[0u8; 32], &mut OsRng).err(),
+        Some(SdkError::InsufficientFunds)
+    );
+}
+
+#[test]
+fn test_claim_transaction_encrypts_outputs_to_auditor() {
+    let claim_note = test_claim_note(Network::Devnet);
+    let claimant = ShieldedAddress::new(
+        Network::Devnet,
+        SpendingKey::random(&mut OsRng).public_key().unwrap(),
+        EncryptionKeypair::random(&mut OsRng).public_key(),
+    )
+    .unwrap();
+    let auditor = Auditor::random(&mut OsRng);
+
+    let transaction = claim_transaction(&claim_note, &claimant, Pubkey::new_unique(), 5_000, &auditor.public_key(), &mut OsRng).unwrap();
+    assert!(has_auditor_outputs(auditor.public_key(), &transaction.ext_data));
+
+    // The auditor recovers both outputs, in the order of the outputs
+    let auditor_outputs = transaction.ext_data.auditor_outputs.unwrap();
+    let ciphertexts = [&auditor_outputs.encrypted_output1, &auditor_outputs.encrypted_output2];
+    for ((ciphertext, commitment), output) in ciphertexts.into_iter().zip(&transaction.output_commitments).zip(&transaction.outputs) {
+        assert_eq!(auditor.decrypt(ciphertext, commitment).as_ref(), Ok(output));
+    }
+}
+
+#[test]
+fn test_is_claimed() {
+    let claim_note = test_claim_note(Network::Mainnet);
+    assert!(!claim_note.is_claimed(&TestSource(HashMap::new())).unwrap());
+
+    let address = nullifier_account_addresses(&claim_note.nullifier().unwrap())[0];
+    assert!(claim_note.is_claimed(&TestSource(HashMap::from([(address, vec![1])]))).unwrap());
+}
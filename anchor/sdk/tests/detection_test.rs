@@ -0,0 +1,112 @@
+use anchor_lang::AccountSerialize;
+use rand_core::OsRng;
+use zkcash::utils::view_tag;
+use zkcash::CommitmentAccount;
+use zkcash_sdk::detection::{
+    random_clue, read_snapshot, write_snapshot, ClueKey, DetectionKey, DetectionSecretKey, DetectionService,
+    MAX_PRECISION,
+};
+use zkcash_sdk::encryption::{encrypt_note, encrypt_note_with_clue, EncryptionKeypair};
+use zkcash_sdk::{Note, SdkError};
+
+fn commitment_account(index: u64, encrypted_output: Vec<u8>) -> CommitmentAccount {
+    CommitmentAccount {
+        commitment: [index as u8; 32],
+        view_tag: view_tag(&encrypted_output),
+        encrypted_output,
+        index,
+        bump: 255,
+        auditor_output: Vec::new(),
+        tree_index: 0,
+    }
+}
+
+#[test]
+fn test_detect_own_clues_at_every_precision() {
+    let secret_key = DetectionSecretKey::random(&mut OsRng);
+    let clue_key = secret_key.clue_key();
+
+    for _ in 0..8 {
+        let clue = clue_key.create_clue(&mut OsRng);
+        for precision in 1..=MAX_PRECISION {
+            assert!(secret_key.detection_key(precision).unwrap().detect(&clue));
+        }
+    }
+}
+
+#[test]
+fn test_false_positive_rate() {
+    let detection_key = DetectionSecretKey::random(&mut OsRng).detection_key(2).unwrap();
+    let other_clue_key = DetectionSecretKey::random(&mut OsRng).clue_key();
+
+    // Expect a quarter of the clues for other keys, and of random clues, to be flagged
+    let flagged = (0..200).filter(|_| detection_key.detect(&other_clue_key.create_clue(&mut OsRng))).count();
+    assert!((25..75).contains(&flagged), "flagged {flagged} of 200");
+    let flagged = (0..200).filter(|_| detection_key.detect(&random_clue(&mut OsRng))).count();
+    assert!((25..75).contains(&flagged), "flagged {flagged} of 200");
+
+    let precise_key = DetectionSecretKey::random(&mut OsRng).detection_key(MAX_PRECISION).unwrap();
+    assert!((0..50).all(|_| !precise_key.detect(&other_clue_key.create_clue(&mut OsRng))));
+}
+
+#[test]
+fn test_detect_rejects_malformed_clues() {
+    let secret_key = DetectionSecretKey::random(&mut OsRng);
+    let detection_key = secret_key.detection_key(MAX_PRECISION).unwrap();
+    let clue = secret_key.clue_key().create_clue(&mut OsRng);
+
+    assert!(!detection_key.detect(&clue[..clue.len() - 1]));
+    let mut tampered = clue;
+    tampered[40] ^= 1;
+    assert!(!detection_key.detect(&tampered), "The scalar binds the clue bits and the Q point");
+}
+
+#[test]
+fn test_key_encoding() {
+    let secret_key = DetectionSecretKey::random(&mut OsRng);
+    let clue_key = secret_key.clue_key();
+    assert_eq!(ClueKey::from_bytes(&clue_key.to_bytes()), Ok(clue_key));
+    assert_eq!(ClueKey::from_bytes(&[0xffu8; MAX_PRECISION * 32]), Err(SdkError::InvalidDetectionKey));
+
+    let detection_key = secret_key.detection_key(10).unwrap();
+    assert_eq!(DetectionKey::from_bytes(&detection_key.to_bytes()), Ok(detection_key));
+    assert_eq!(DetectionKey::from_bytes(&[]), Err(SdkError::InvalidDetectionKey));
+
+    assert!(matches!(secret_key.detection_key(0), Err(SdkError::InvalidPrecision)));
+    assert!(matches!(secret_key.detection_key(MAX_PRECISION + 1), Err(SdkError::InvalidPrecision)));
+}
+
+#[test]
+fn test_detection_service_flags_outputs_in_snapshot() {
+    let keypair = EncryptionKeypair::random(&mut OsRng);
+    let secret_key = DetectionSecretKey::random(&mut OsRng);
+    let clue_key = secret_key.clue_key();
+    let note = Note::random_sol(5, [7u8; 32], &mut OsRng);
+    let commitment = note.commitment().unwrap();
+
+    let mut account_data = Vec::new();
+    for index in 0..32u64 {
+        let encrypted_output = if index % 10 == 3 {
+            encrypt_note_with_clue(&keypair.public_key(), &clue_key, &commitment, &note, &mut OsRng)
+        } else {
+            encrypt_note(&keypair.public_key(), &commitment, &note, &mut OsRng)
+        };
+        let mut data = Vec::new();
+        commitment_account(index, encrypted_output).try_serialize(&mut data).unwrap();
+        account_data.push(data);
+    }
+    // Outputs in the legacy format carry no clue
+    let mut data = Vec::new();
+    commitment_account(32, vec![0u8; 120]).try_serialize(&mut data).unwrap();
+    account_data.push(data);
+
+    let mut snapshot = Vec::new();
+    write_snapshot(&mut snapshot, account_data.iter().map(Vec::as_slice)).unwrap();
+    assert_eq!(read_snapshot(&snapshot[..]).unwrap().len(), 33);
+    assert!(matches!(read_snapshot(&snapshot[..snapshot.len() - 1]), Err(SdkError::InvalidSnapshot)));
+    assert!(matches!(read_snapshot(&snapshot[..2]), Err(SdkError::InvalidSnapshot)));
+
+    let service = DetectionService::from_snapshot(&snapshot[..]).unwrap();
+    let flagged = service.flagged_indices(&secret_key.detection_key(MAX_PRECISION).unwrap());
+    assert_eq!(flagged, vec![(0, 3), (0, 13), (0, 23), (0, 32)]);
+}
@@ -0,0 +1,188 @@
+use rand_core::OsRng;
+use zkcash::CommitmentAccount;
+use zkcash::utils::view_tag;
+use zkcash_sdk::encryption::{
+    decrypt_output, encrypt_note, DecryptedOutput, EncryptionKeypair, LegacyEncryptionKey, LegacyUtxo,
+    envelope_version, ScannedNote, ENVELOPE_MAGIC, NOTE_CIPHERTEXT_VERSION,
+};
+use zkcash_sdk::note::SOL_MINT;
+use zkcash_sdk::{Note, SdkError};
+
+// Encrypted by the TypeScript `EncryptionService` with the key bytes 1..=31 and the IV
+// 0xa0..=0xaf, from the UTXO string "1000000|123456789|5|11111111111111111111111111111112".
+const LEGACY_CIPHERTEXT: &str = "a0a1a2a3a4a5a6a7a8a9aaabacadaeafaae726e333a58cf818d442afa8b6c306775be3997a2fca1faa01c3251fa5742e8d19599811b72b69dd34f4248f11c9f1586dd1c5eed22e196d16d42cdd6c87441a2535bd";
+// The same UTXO with the IV 0x01a1..=0xaf, starting with the version byte of the note envelope
+const LEGACY_CIPHERTEXT_VERSION_IV: &str = "01a1a2a3a4a5a6a7a8a9aaabacadaeaf036fa9f84a5b7c7ea81a66d9912a993585b6aef91c90baa498ff7994e22d37cc02d4c0aab4adbdebbb8f726c1b3bb4f4284c86589dac6877a585a5f35ef67903a249d7f6";
+
+fn legacy_key() -> LegacyEncryptionKey {
+    LegacyEncryptionKey::new(core::array::from_fn(|i| i as u8 + 1))
+}
+
+// Built the way `transact` builds it
+fn commitment_account(commitment: [u8; 32], index: u64, encrypted_output: Vec<u8>) -> CommitmentAccount {
+    CommitmentAccount {
+        commitment,
+        view_tag: view_tag(&encrypted_output),
+        encrypted_output,
+        index,
+        bump: 255,
+        auditor_output: Vec::new(),
+        tree_index: 0,
+    }
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+}
+
+fn legacy_ciphertext() -> Vec<u8> {
+    from_hex(LEGACY_CIPHERTEXT)
+}
+
+#[test]
+fn test_encrypt_decrypt_note_round_trip() {
+    let keypair = EncryptionKeypair::random(&mut OsRng);
+    let note = Note::random_sol(1_000_000, [7u8; 32], &mut OsRng);
+    let commitment = note.commitment().unwrap();
+
+    let ciphertext = encrypt_note(&keypair.public_key(), &commitment, &note, &mut OsRng);
+    assert_eq!(envelope_version(&ciphertext), Some(NOTE_CIPHERTEXT_VERSION));
+    assert_eq!(keypair.decrypt_note(&ciphertext, &commitment), Ok(note.clone()));
+
+    let other_keypair = EncryptionKeypair::random(&mut OsRng);
+    assert_eq!(other_keypair.decrypt_note(&ciphertext, &commitment), Err(SdkError::DecryptionFailed));
+
+    let mut tampered = ciphertext.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert_eq!(keypair.decrypt_note(&tampered, &commitment), Err(SdkError::DecryptionFailed));
+
+    let mut unknown_version = ciphertext;
+    unknown_version[ENVELOPE_MAGIC.len()] = NOTE_CIPHERTEXT_VERSION + 1;
+    assert_eq!(keypair.decrypt_note(&unknown_version, &commitment), Err(SdkError::InvalidCiphertext));
+}
+
+#[test]
+fn test_decrypt_legacy_utxo() {
+    let mut blinding = [0u8; 32];
+    blinding[28..].copy_from_slice(&123_456_789u32.to_be_bytes());
+
+    let utxo = legacy_key().decrypt_utxo(&legacy_ciphertext()).unwrap();
+    assert_eq!(utxo, LegacyUtxo { amount: 1_000_000, blinding, index: 5, mint: SOL_MINT });
+    assert_eq!(utxo.into_note([7u8; 32]), Note::sol(1_000_000, [7u8; 32], blinding));
+}
+
+#[test]
+fn test_decrypt_legacy_rejects_tampering() {
+    let mut ciphertext = legacy_ciphertext();
+    ciphertext[40] ^= 1;
+    assert_eq!(legacy_key().decrypt(&ciphertext), Err(SdkError::DecryptionFailed));
+
+    let wrong_key = LegacyEncryptionKey::from_wallet_signature(&[1u8; 64]);
+    assert_eq!(wrong_key.decrypt(&legacy_ciphertext()), Err(SdkError::DecryptionFailed));
+
+    assert_eq!(legacy_key().decrypt(&legacy_ciphertext()[..20]), Err(SdkError::InvalidCiphertext));
+}
+
+#[test]
+fn test_legacy_iv_starting_with_version_byte() {
+    let ciphertext = from_hex(LEGACY_CIPHERTEXT_VERSION_IV);
+    assert_eq!(ciphertext[0], NOTE_CIPHERTEXT_VERSION);
+    assert_eq!(envelope_version(&ciphertext), None, "Only the magic marks an envelope");
+
+    // No view tag, so every wallet still tries it as a legacy output
+    let account = commitment_account([1u8; 32], 0, ciphertext.clone());
+    assert_eq!(account.view_tag, None);
+    assert!(EncryptionKeypair::random(&mut OsRng).is_candidate(&account));
+
+    let keypair = EncryptionKeypair::random(&mut OsRng);
+    assert_eq!(
+        decrypt_output(&keypair, Some(&legacy_key()), &ciphertext, &[1u8; 32]),
+        Ok(DecryptedOutput::Legacy(legacy_key().decrypt_utxo(&legacy_ciphertext()).unwrap()))
+    );
+}
+
+#[test]
+fn test_decrypt_output_falls_back_to_legacy() {
+    let keypair = EncryptionKeypair::random(&mut OsRng);
+    let note = Note::random_sol(5, [7u8; 32], &mut OsRng);
+    let commitment = note.commitment().unwrap();
+    let ciphertext = encrypt_note(&keypair.public_key(), &commitment, &note, &mut OsRng);
+
+    assert_eq!(decrypt_output(&keypair, Some(&legacy_key()), &ciphertext, &commitment), Ok(DecryptedOutput::Note(note)));
+    assert!(matches!(
+        decrypt_output(&keypair, Some(&legacy_key()), &legacy_ciphertext(), &commitment),
+        Ok(DecryptedOutput::Legacy(_))
+    ));
+    assert_eq!(decrypt_output(&keypair, None, &legacy_ciphertext(), &commitment), Err(SdkError::InvalidCiphertext));
+}
+
+/// Fills every buffer with the same byte, to pin the ephemeral key of a test vector.
+struct FixedRng(u8);
+
+impl rand_core::RngCore for FixedRng {
+    fn next_u32(&mut self) -> u32 {
+        u32::from_ne_bytes([self.0; 4])
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        u64::from_ne_bytes([self.0; 8])
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dest.fill(self.0);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl rand_core::CryptoRng for FixedRng {}
+
+// Test vector for other implementations of the versioned format: recipient secret key 0x11..,
+// ephemeral secret key 0x22.., and the note below. The lines are the header (magic, version,
+// ephemeral key and view tag), the random clue, and the ciphertext.
+#[test]
+fn test_note_ciphertext_vector() {
+    let keypair = EncryptionKeypair::new([0x11; 32]);
+    let note = Note::sol(1_000_000, [7u8; 32], [9u8; 32]);
+    let commitment = note.commitment().unwrap();
+
+    let ciphertext = encrypt_note(&keypair.public_key(), &commitment, &note, &mut FixedRng(0x22));
+    let hex: String = ciphertext.iter().map(|byte| format!("{byte:02x}")).collect();
+    assert_eq!(hex, concat!(
+        "7a6b63617368010faa684ed28867b97f4a6a2dee5df8ce974e76b7018e3f22a1c4cf2678570f2069",
+        "8e874067457fe4db229f5a96b9883e56149f23b7f55a0f97b18b4fbd53050c7f4ff3999faf677e77c82531330b687fef6219764760fe961be669f576d02ad005222222",
+        "0a9ac43990d805b7ec984345fe4e79220d8bf3b46f5c03549e176e02af4b02776636bfef6889d68ee5d7364550249b90581276b97119d3e3e7c639d15f308b0e35b200074b4079e27ae4d9af69b34fda82ca2ceb81b9b8f1fa1e9a1c24c76dfbba16b64f73dc4a343b2f31bd41173e456549b67a867f1f51",
+    ));
+    assert_eq!(keypair.decrypt_note(&ciphertext, &commitment), Ok(note));
+}
+
+#[test]
+fn test_scan_with_view_tags() {
+    let keypair = EncryptionKeypair::random(&mut OsRng);
+    let other_keypair = EncryptionKeypair::random(&mut OsRng);
+
+    let mut accounts = Vec::new();
+    let mut ours = Vec::new();
+    for index in 0..64u64 {
+        let note = Note::random_sol(index, [7u8; 32], &mut OsRng);
+        let commitment = note.commitment().unwrap();
+        let recipient = if index % 8 == 3 { &keypair } else { &other_keypair };
+        let ciphertext = encrypt_note(&recipient.public_key(), &commitment, &note, &mut OsRng);
+        if index % 8 == 3 {
+            ours.push(ScannedNote { tree_index: 0, index, commitment, note });
+        }
+        accounts.push(commitment_account(commitment, index, ciphertext));
+    }
+    // Legacy outputs have no view tag and are always candidates
+    accounts.push(commitment_account([1u8; 32], 64, legacy_ciphertext()));
+
+    let candidates = accounts.iter().filter(|account| keypair.is_candidate(account)).count();
+    assert!(candidates > ours.len(), "Our notes and the legacy output are candidates");
+    assert!(candidates < accounts.len() / 2, "Most outputs should be dropped by their view tag");
+
+    accounts.reverse();
+    assert_eq!(keypair.scan(&accounts), ours);
+}
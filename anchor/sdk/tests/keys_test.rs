@@ -0,0 +1,86 @@
+use rand_core::OsRng;
+use zkcash::utils::view_tag;
+use zkcash::CommitmentAccount;
+use zkcash_sdk::encryption::encrypt_note;
+use zkcash_sdk::keys::{AccountKeys, ExtendedKey, SpendingKey, SPENDING_KEY_INDEX, ZKCASH_PURPOSE};
+use zkcash_sdk::wallet::{recover_accounts, NoteStore, Wallet};
+use zkcash_sdk::{Note, SdkError};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn master_key() -> ExtendedKey {
+    ExtendedKey::from_seed(&core::array::from_fn::<u8, 32, _>(|i| i as u8)).unwrap()
+}
+
+fn output_to(keys: &AccountKeys, amount: u64, index: u64) -> CommitmentAccount {
+    let note = Note::random_sol(amount, keys.spending_key.public_key().unwrap(), &mut OsRng);
+    let commitment = note.commitment().unwrap();
+    let encrypted_output = encrypt_note(&keys.encryption_key.public_key(), &commitment, &note, &mut OsRng);
+    CommitmentAccount {
+        commitment,
+        view_tag: view_tag(&encrypted_output),
+        encrypted_output,
+        index,
+        bump: 255,
+        auditor_output: Vec::new(),
+        tree_index: 0,
+    }
+}
+
+// Computed with HMAC-SHA512 from Python's standard library
+#[test]
+fn test_derivation_vector() {
+    let master_key = master_key();
+    assert_eq!(hex(&master_key.key), "73a4034346c2cf0dfae522d091c6b1ba703b636e9a1a725ac3033ccc0507ce4c");
+    assert_eq!(hex(&master_key.chain_code), "37b131ee802b2702ead50cc69ca65c1f69d4c71eb3504dd4945aa8a2ac01a770");
+
+    let spending_node = master_key.derive_path(&[ZKCASH_PURPOSE, 0, SPENDING_KEY_INDEX]);
+    assert_eq!(hex(&spending_node.key), "a31442460a73ff9c7d2d3bc52b07ed007e903d5d2d023af1493ae5eb5d8c46e8");
+    assert_eq!(AccountKeys::derive(&master_key, 0).spending_key, SpendingKey::new(spending_node.key));
+
+    assert!(matches!(ExtendedKey::from_seed(&[0u8; 15]), Err(SdkError::InvalidSeed)));
+    assert!(matches!(ExtendedKey::from_seed(&[0u8; 65]), Err(SdkError::InvalidSeed)));
+}
+
+#[test]
+fn test_accounts_have_independent_keys() {
+    let master_key = master_key();
+    let first = AccountKeys::derive(&master_key, 0);
+    let second = AccountKeys::derive(&master_key, 1);
+
+    assert_ne!(first.spending_key, second.spending_key);
+    assert_ne!(first.encryption_key.public_key(), second.encryption_key.public_key());
+    assert_ne!(first.detection_key.clue_key(), second.detection_key.clue_key());
+    assert_eq!(AccountKeys::derive(&master_key, 1).spending_key, second.spending_key, "Derivation is deterministic");
+}
+
+#[test]
+fn test_recover_accounts_with_gap_limit() {
+    let master_key = master_key();
+    let accounts = [
+        output_to(&AccountKeys::derive(&master_key, 0), 100, 0),
+        output_to(&AccountKeys::derive(&master_key, 2), 200, 1),
+        output_to(&AccountKeys::derive(&master_key, 2), 300, 2),
+        // Beyond a gap of two unused accounts
+        output_to(&AccountKeys::derive(&master_key, 5), 400, 3),
+    ];
+
+    let recovered = recover_accounts(&master_key, &accounts, 2).unwrap();
+    let found: Vec<(u32, usize)> = recovered.iter().map(|account| (account.index, account.wallet.store().notes().count())).collect();
+    assert_eq!(found, vec![(0, 1), (2, 2)]);
+
+    let recovered = recover_accounts(&master_key, &accounts, 3).unwrap();
+    assert_eq!(recovered.last().unwrap().index, 5);
+}
+
+#[test]
+fn test_wallet_from_signature_matches_typescript_keys() {
+    let signature = [5u8; 64];
+    let wallet = Wallet::from_wallet_signature(&signature, NoteStore::default()).unwrap();
+
+    // The TypeScript client hashes the first 31 bytes of the signature with SHA-256
+    let legacy_private_key = "1aeb4802bb81e004e56732669542c641074b6001f08c36635ae8f2529abbda23";
+    assert_eq!(hex(&wallet.spending_key().private_key()), legacy_private_key);
+}
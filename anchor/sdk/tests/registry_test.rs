@@ -0,0 +1,50 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::AccountSerialize;
+use rand_core::OsRng;
+use std::collections::HashMap;
+use zkcash::ShieldedAccount;
+use zkcash_sdk::registry::{lookup_recipient, shielded_account_address, AccountSource};
+use zkcash_sdk::SdkError;
+
+struct TestSource(HashMap<Pubkey, Vec<u8>>);
+
+impl AccountSource for TestSource {
+    fn get_account_data(&self, address: &Pubkey) -> Option<Vec<u8>> {
+        self.0.get(address).cloned()
+    }
+}
+
+fn registered_account(owner: Pubkey) -> Vec<u8> {
+    let mut shielded_pubkey = [0u8; 32];
+    shielded_pubkey[31] = 42;
+    let account = ShieldedAccount { owner, shielded_pubkey, encryption_key: [7u8; 32], bump: 255 };
+
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn test_lookup_recipient() {
+    let wallet = Pubkey::new_unique();
+    let source = TestSource(HashMap::from([(shielded_account_address(&wallet), registered_account(wallet))]));
+
+    let recipient = lookup_recipient(&source, &wallet).unwrap();
+    assert_eq!(recipient.wallet, wallet);
+    assert_eq!(recipient.encryption_key, [7u8; 32]);
+
+    let note = recipient.new_output(1_000, &mut OsRng);
+    assert_eq!(note.owner, recipient.shielded_pubkey);
+    assert_eq!(note.amount, 1_000);
+    assert_eq!(note.blinding[0], 0, "Blinding must be a field element");
+
+    assert_eq!(lookup_recipient(&source, &Pubkey::new_unique()), Err(SdkError::AccountNotRegistered));
+}
+
+#[test]
+fn test_lookup_recipient_rejects_account_of_another_wallet() {
+    let wallet = Pubkey::new_unique();
+    let source = TestSource(HashMap::from([(shielded_account_address(&wallet), registered_account(Pubkey::new_unique()))]));
+
+    assert_eq!(lookup_recipient(&source, &wallet), Err(SdkError::InvalidAccountData));
+}
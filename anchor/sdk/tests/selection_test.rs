@@ -0,0 +1,123 @@
+use rand_core::OsRng;
+use zkcash_sdk::encryption::{encrypt_note, EncryptionKeypair};
+use zkcash_sdk::note::SOL_MINT;
+use zkcash_sdk::selection::{
+    dummy_input, dummy_output, plan_transfer, split_into_denominations, DenominationSplit, PlannedInput, PlannedTransaction, SelectionOptions, TransferPlan,
+};
+use zkcash_sdk::wallet::WalletNote;
+use zkcash_sdk::{Note, SdkError};
+
+fn wallet_notes(amounts: &[u64]) -> Vec<WalletNote> {
+    amounts
+        .iter()
+        .enumerate()
+        .map(|(index, amount)| WalletNote {
+            tree_index: 0,
+            index: index as u64,
+            commitment: [index as u8; 32],
+            note: Note::sol(*amount, [7u8; 32], [index as u8; 32]),
+            nullifier: [index as u8 + 100; 32],
+            spent: false,
+        })
+        .collect()
+}
+
+fn single_spend(inputs: [PlannedInput; 2], input_amount: u64, change: u64) -> TransferPlan {
+    TransferPlan { merges: vec![], spend: PlannedTransaction { inputs, input_amount }, change }
+}
+
+#[test]
+fn test_single_note_with_least_change() {
+    let notes = wallet_notes(&[50, 300, 120, 900]);
+    let plan = plan_transfer(&notes, &SOL_MINT, 100, SelectionOptions::default()).unwrap();
+    assert_eq!(plan, single_spend([PlannedInput::Note(0, 2), PlannedInput::Dummy], 120, 20));
+
+    let plan = plan_transfer(&notes, &SOL_MINT, 100, SelectionOptions { consolidate: true }).unwrap();
+    assert_eq!(plan, single_spend([PlannedInput::Note(0, 2), PlannedInput::Note(0, 0)], 170, 70), "Folds the smallest note into the change");
+}
+
+#[test]
+fn test_pair_with_smallest_sum() {
+    let notes = wallet_notes(&[10, 40, 70, 95, 60]);
+    let plan = plan_transfer(&notes, &SOL_MINT, 130, SelectionOptions::default()).unwrap();
+    assert_eq!(plan, single_spend([PlannedInput::Note(0, 4), PlannedInput::Note(0, 2)], 130, 0));
+}
+
+#[test]
+fn test_merges_when_two_notes_are_not_enough() {
+    let notes = wallet_notes(&[10, 20, 30, 40, 5]);
+    let plan = plan_transfer(&notes, &SOL_MINT, 95, SelectionOptions::default()).unwrap();
+
+    assert_eq!(plan.merges, vec![
+        PlannedTransaction { inputs: [PlannedInput::Note(0, 3), PlannedInput::Note(0, 2)], input_amount: 70 },
+        PlannedTransaction { inputs: [PlannedInput::Merged(0), PlannedInput::Note(0, 1)], input_amount: 90 },
+    ]);
+    assert_eq!(plan.spend, PlannedTransaction { inputs: [PlannedInput::Merged(1), PlannedInput::Note(0, 0)], input_amount: 100 });
+    assert_eq!(plan.change, 5);
+}
+
+#[test]
+fn test_skips_spent_notes_and_other_mints() {
+    let mut notes = wallet_notes(&[500, 100, 200]);
+    notes[0].spent = true;
+    notes[2].note.mint = [9u8; 32];
+
+    assert_eq!(plan_transfer(&notes, &SOL_MINT, 150, SelectionOptions::default()), Err(SdkError::InsufficientFunds));
+    assert_eq!(plan_transfer(&notes, &SOL_MINT, 0, SelectionOptions::default()), Err(SdkError::InvalidAmount));
+    assert!(plan_transfer(&notes, &[9u8; 32], 150, SelectionOptions::default()).is_ok());
+}
+
+#[test]
+fn test_inputs_past_u64_are_rejected() {
+    // A note that covers the amount, with the smallest note folded in
+    let notes = wallet_notes(&[u64::MAX, 1]);
+    assert!(plan_transfer(&notes, &SOL_MINT, u64::MAX, SelectionOptions::default()).is_ok());
+    assert_eq!(plan_transfer(&notes, &SOL_MINT, u64::MAX, SelectionOptions { consolidate: true }), Err(SdkError::InputAmountOverflow));
+
+    // A pair that covers the amount
+    let notes = wallet_notes(&[u64::MAX - 1, u64::MAX - 1]);
+    assert_eq!(plan_transfer(&notes, &SOL_MINT, u64::MAX, SelectionOptions::default()), Err(SdkError::InputAmountOverflow));
+
+    // Notes that need merging
+    let notes = wallet_notes(&[u64::MAX / 2 - 1; 3]);
+    assert_eq!(plan_transfer(&notes, &SOL_MINT, u64::MAX, SelectionOptions::default()), Err(SdkError::InputAmountOverflow));
+}
+
+#[test]
+fn test_dummies_look_like_real_notes() {
+    let input = dummy_input([7u8; 32], SOL_MINT, &mut OsRng);
+    assert_eq!(input.amount, 0);
+    assert_ne!(input.blinding, dummy_input([7u8; 32], SOL_MINT, &mut OsRng).blinding);
+
+    let dummy = dummy_output(SOL_MINT, &mut OsRng).unwrap();
+    assert_eq!(dummy.note.amount, 0);
+    assert_eq!(dummy.commitment, dummy.note.commitment().unwrap());
+
+    let real = Note::random_sol(1_000, [7u8; 32], &mut OsRng);
+    let real_output = encrypt_note(&EncryptionKeypair::random(&mut OsRng).public_key(), &real.commitment().unwrap(), &real, &mut OsRng);
+    assert_eq!(dummy.encrypted_output.len(), real_output.len());
+    assert_eq!(dummy.encrypted_output[0], real_output[0]);
+}
+
+#[test]
+fn test_split_into_denominations() {
+    let denominations = [100_000_000, 1_000_000_000, 10_000_000_000, 0, 0, 0, 0, 0];
+    assert_eq!(
+        split_into_denominations(12_345_000_000, &denominations),
+        DenominationSplit {
+            amounts: vec![10_000_000_000, 1_000_000_000, 1_000_000_000, 100_000_000, 100_000_000, 100_000_000],
+            remainder: 45_000_000,
+        }
+    );
+    assert_eq!(
+        split_into_denominations(50_000_000, &denominations),
+        DenominationSplit { amounts: vec![], remainder: 50_000_000 }
+    );
+
+    // Pools without denominations take any amount at once
+    assert_eq!(
+        split_into_denominations(1_234, &[0u64; 8]),
+        DenominationSplit { amounts: vec![1_234], remainder: 0 }
+    );
+    assert_eq!(split_into_denominations(0, &[]), DenominationSplit::default());
+}
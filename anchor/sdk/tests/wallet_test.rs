@@ -0,0 +1,185 @@
+use anchor_lang::prelude::Pubkey;
+use light_hasher::{Hasher, Poseidon};
+use rand_core::OsRng;
+use std::collections::HashMap;
+use zkcash::utils::view_tag;
+use anchor_lang::AnchorSerialize;
+use zkcash::CommitmentAccount;
+use zkcash_interface::nullifier_account_addresses;
+use zkcash_sdk::encryption::{encrypt_note, EncryptionKeypair, LegacyEncryptionKey};
+use zkcash_sdk::keys::SpendingKey;
+use zkcash_sdk::note::SOL_MINT;
+use zkcash_sdk::registry::AccountSource;
+use zkcash_sdk::wallet::{NoteStore, Wallet};
+use zkcash_sdk::{Note, SdkError};
+
+// The legacy UTXO "1000000|123456789|5|11111111111111111111111111111112", see encryption_test
+const LEGACY_CIPHERTEXT: &str = "a0a1a2a3a4a5a6a7a8a9aaabacadaeafaae726e333a58cf818d442afa8b6c306775be3997a2fca1faa01c3251fa5742e8d19599811b72b69dd34f4248f11c9f1586dd1c5eed22e196d16d42cdd6c87441a2535bd";
+
+struct TestSource(HashMap<Pubkey, Vec<u8>>);
+
+impl AccountSource for TestSource {
+    fn get_account_data(&self, address: &Pubkey) -> Option<Vec<u8>> {
+        self.0.get(address).cloned()
+    }
+}
+
+fn commitment_account(commitment: [u8; 32], index: u64, encrypted_output: Vec<u8>) -> CommitmentAccount {
+    CommitmentAccount {
+        commitment,
+        view_tag: view_tag(&encrypted_output),
+        encrypted_output,
+        index,
+        bump: 255,
+        auditor_output: Vec::new(),
+        tree_index: 0,
+    }
+}
+
+fn output_to(encryption_key: &EncryptionKeypair, note: &Note, index: u64) -> CommitmentAccount {
+    let commitment = note.commitment().unwrap();
+    commitment_account(commitment, index, encrypt_note(&encryption_key.public_key(), &commitment, note, &mut OsRng))
+}
+
+fn output_to_tree(encryption_key: &EncryptionKeypair, note: &Note, tree_index: u32, index: u64) -> CommitmentAccount {
+    CommitmentAccount { tree_index, ..output_to(encryption_key, note, index) }
+}
+
+fn legacy_ciphertext() -> Vec<u8> {
+    (0..LEGACY_CIPHERTEXT.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&LEGACY_CIPHERTEXT[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn test_wallet() -> (Wallet, SpendingKey, EncryptionKeypair) {
+    let spending_key = SpendingKey::random(&mut OsRng);
+    let encryption_key = EncryptionKeypair::new([3u8; 32]);
+    let legacy_key = LegacyEncryptionKey::new(core::array::from_fn(|i| i as u8 + 1));
+    let wallet = Wallet::new(spending_key.clone(), EncryptionKeypair::new([3u8; 32]), Some(legacy_key), NoteStore::default()).unwrap();
+    (wallet, spending_key, encryption_key)
+}
+
+#[test]
+fn test_spending_key_matches_circuit() {
+    let spending_key = SpendingKey::new([0xff; 32]);
+    let private_key = spending_key.private_key();
+    assert!(private_key < [0x30; 32], "Private key is reduced into the field");
+    assert_eq!(spending_key.public_key().unwrap(), Poseidon::hashv(&[&private_key]).unwrap());
+
+    let commitment = [9u8; 32];
+    let mut index = [0u8; 32];
+    index[31] = 5;
+    let signature = Poseidon::hashv(&[&private_key, &commitment, &index]).unwrap();
+    assert_eq!(spending_key.sign(&commitment, 5).unwrap(), signature);
+    assert_eq!(
+        spending_key.nullifier(&commitment, 5).unwrap(),
+        Poseidon::hashv(&[&commitment, &index, &signature]).unwrap()
+    );
+}
+
+#[test]
+fn test_ingest_finds_own_notes() {
+    let (mut wallet, spending_key, encryption_key) = test_wallet();
+    let public_key = spending_key.public_key().unwrap();
+    let other_key = EncryptionKeypair::random(&mut OsRng);
+
+    let ours = Note::random_sol(1_000, public_key, &mut OsRng);
+    assert!(wallet.ingest(&output_to(&encryption_key, &ours, 0)).unwrap());
+    assert!(!wallet.ingest(&output_to(&encryption_key, &ours, 0)).unwrap(), "Known leaves are skipped");
+    assert!(!wallet.ingest(&output_to(&other_key, &ours, 1)).unwrap());
+    // Encrypted to us, but owned by another spending key
+    let not_spendable = Note::random_sol(1_000, [7u8; 32], &mut OsRng);
+    assert!(!wallet.ingest(&output_to(&encryption_key, &not_spendable, 2)).unwrap());
+
+    // A legacy output: the commitment tells that it's owned by our spending key
+    let legacy_note = LegacyEncryptionKey::new(core::array::from_fn(|i| i as u8 + 1))
+        .decrypt_utxo(&legacy_ciphertext())
+        .unwrap()
+        .into_note(public_key);
+    assert!(wallet.ingest(&commitment_account(legacy_note.commitment().unwrap(), 5, legacy_ciphertext())).unwrap());
+    assert!(!wallet.ingest(&commitment_account([1u8; 32], 6, legacy_ciphertext())).unwrap());
+
+    let notes: Vec<_> = wallet.store().notes().collect();
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0].note, ours);
+    assert_eq!(notes[0].nullifier, spending_key.nullifier(&ours.commitment().unwrap(), 0).unwrap());
+    assert_eq!(notes[1].note, legacy_note);
+    assert_eq!(wallet.balances().get(&SOL_MINT), Some(&1_001_000));
+}
+
+#[test]
+fn test_ingest_notes_of_several_trees() {
+    let (mut wallet, spending_key, encryption_key) = test_wallet();
+    let public_key = spending_key.public_key().unwrap();
+    let first = Note::random_sol(100, public_key, &mut OsRng);
+    let second = Note::random_sol(200, public_key, &mut OsRng);
+
+    // Each shard numbers its leaves from 0, so both notes are at leaf 4
+    assert!(wallet.ingest(&output_to_tree(&encryption_key, &first, 0, 4)).unwrap());
+    assert!(wallet.ingest(&output_to_tree(&encryption_key, &second, 1, 4)).unwrap());
+    assert!(!wallet.ingest(&output_to_tree(&encryption_key, &second, 1, 4)).unwrap(), "Known leaves are skipped");
+
+    let positions: Vec<(u32, u64)> = wallet.store().notes().map(|note| (note.tree_index, note.index)).collect();
+    assert_eq!(positions, vec![(0, 4), (1, 4)]);
+    assert_eq!(wallet.balances().get(&SOL_MINT), Some(&300));
+
+    let path = std::env::temp_dir().join(format!("zkcash-notes-{}.bin", Pubkey::new_unique()));
+    wallet.store().save(&path).unwrap();
+    assert_eq!(&NoteStore::load(&path).unwrap(), wallet.store());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_refresh_spent() {
+    let (mut wallet, spending_key, encryption_key) = test_wallet();
+    let public_key = spending_key.public_key().unwrap();
+    let spent = Note::random_sol(400, public_key, &mut OsRng);
+    let unspent = Note::random_sol(600, public_key, &mut OsRng);
+    wallet.ingest(&output_to(&encryption_key, &spent, 0)).unwrap();
+    wallet.ingest(&output_to(&encryption_key, &unspent, 1)).unwrap();
+
+    // Spent as the second input of a transaction
+    let nullifier = spending_key.nullifier(&spent.commitment().unwrap(), 0).unwrap();
+    let source = TestSource(HashMap::from([(nullifier_account_addresses(&nullifier)[1], vec![0u8; 9])]));
+    wallet.refresh_spent(&source);
+
+    let spendable: Vec<u64> = wallet.spendable_notes().map(|note| note.index).collect();
+    assert_eq!(spendable, vec![1]);
+    assert_eq!(wallet.balances().get(&SOL_MINT), Some(&600));
+}
+
+#[test]
+fn test_note_store_persistence() {
+    let (mut wallet, spending_key, encryption_key) = test_wallet();
+    let note = Note::random_sol(5, spending_key.public_key().unwrap(), &mut OsRng);
+    wallet.ingest(&output_to(&encryption_key, &note, 3)).unwrap();
+
+    let path = std::env::temp_dir().join(format!("zkcash-notes-{}.bin", Pubkey::new_unique()));
+    assert_eq!(NoteStore::load(&path), Ok(NoteStore::default()), "Missing store is empty");
+    wallet.store().save(&path).unwrap();
+    let loaded = NoteStore::load(&path).unwrap();
+    assert_eq!(&loaded, wallet.store());
+
+    std::fs::write(&path, [3u8, 0, 0, 0, 0]).unwrap();
+    assert_eq!(NoteStore::load(&path), Err(SdkError::InvalidNoteStore), "Unknown store version");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_note_store_loads_version_1() {
+    let (mut wallet, spending_key, encryption_key) = test_wallet();
+    let note = Note::random_sol(5, spending_key.public_key().unwrap(), &mut OsRng);
+    wallet.ingest(&output_to(&encryption_key, &note, 3)).unwrap();
+    let stored = wallet.store().notes().next().unwrap();
+
+    // Version 1 stored the notes without their tree, they were all in tree 0
+    let mut data = vec![1u8];
+    1u32.serialize(&mut data).unwrap();
+    (stored.index, stored.commitment, stored.note.clone(), stored.nullifier, stored.spent).serialize(&mut data).unwrap();
+
+    let path = std::env::temp_dir().join(format!("zkcash-notes-{}.bin", Pubkey::new_unique()));
+    std::fs::write(&path, data).unwrap();
+    assert_eq!(&NoteStore::load(&path).unwrap(), wallet.store());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -30,7 +30,7 @@ export function mockEncrypt(value: Utxo): string {
 
 /**
  * Calculates the hash of ext data using Borsh serialization
- * @param extData External data object containing recipient, amount, and encrypted outputs
+ * @param extData External data object containing recipient, amount, encrypted outputs, an optional withdraw call, optional auditor outputs and optional payouts
  * @returns The hash as a Uint8Array (32 bytes)
  */
 export function getExtDataHash(extData: {
@@ -39,6 +39,20 @@ export function getExtDataHash(extData: {
   encryptedOutput1: string | Uint8Array;
   encryptedOutput2: string | Uint8Array;
   fee: string | number | BN;
+  call?: {
+    programId: string | PublicKey;
+    accounts: { pubkey: string | PublicKey; isWritable: boolean }[];
+    data: Uint8Array;
+  } | null;
+  auditorOutputs?: { encryptedOutput1: Uint8Array; encryptedOutput2: Uint8Array } | null;
+  payouts?: { recipient: string | PublicKey; amount: string | number | BN }[] | null;
+  association?: {
+    associationSet: string | PublicKey;
+    root: Uint8Array;
+    proofA: Uint8Array;
+    proofB: Uint8Array;
+    proofC: Uint8Array;
+  } | null;
 }): Uint8Array {
   // Convert all inputs to their appropriate types
   const recipient = extData.recipient instanceof PublicKey 
@@ -61,6 +75,55 @@ export function getExtDataHash(extData: {
       encryptedOutput1: { array: { type: 'u8' } },
       encryptedOutput2: { array: { type: 'u8' } },
       fee: 'u64',
+      call: {
+        option: {
+          struct: {
+            programId: { array: { type: 'u8', len: 32 } },
+            accounts: {
+              array: {
+                type: {
+                  struct: {
+                    pubkey: { array: { type: 'u8', len: 32 } },
+                    isWritable: 'bool',
+                  }
+                }
+              }
+            },
+            data: { array: { type: 'u8' } },
+          }
+        }
+      },
+      auditorOutputs: {
+        option: {
+          struct: {
+            encryptedOutput1: { array: { type: 'u8' } },
+            encryptedOutput2: { array: { type: 'u8' } },
+          }
+        }
+      },
+      payouts: {
+        option: {
+          array: {
+            type: {
+              struct: {
+                recipient: { array: { type: 'u8', len: 32 } },
+                amount: 'u64',
+              }
+            }
+          }
+        }
+      },
+      association: {
+        option: {
+          struct: {
+            associationSet: { array: { type: 'u8', len: 32 } },
+            root: { array: { type: 'u8', len: 32 } },
+            proofA: { array: { type: 'u8', len: 64 } },
+            proofB: { array: { type: 'u8', len: 128 } },
+            proofC: { array: { type: 'u8', len: 64 } },
+          }
+        }
+      },
     }
   };
 
@@ -70,6 +133,29 @@ export function getExtDataHash(extData: {
     encryptedOutput1: encryptedOutput1,
     encryptedOutput2: encryptedOutput2,
     fee: fee,  // BN instance - Borsh handles it correctly with u64 type
+    call: extData.call ? {
+      programId: new PublicKey(extData.call.programId).toBytes(),
+      accounts: extData.call.accounts.map((account) => ({
+        pubkey: new PublicKey(account.pubkey).toBytes(),
+        isWritable: account.isWritable,
+      })),
+      data: Buffer.from(extData.call.data),
+    } : null,  // Borsh writes a single 0 byte for None
+    auditorOutputs: extData.auditorOutputs ? {
+      encryptedOutput1: Buffer.from(extData.auditorOutputs.encryptedOutput1),
+      encryptedOutput2: Buffer.from(extData.auditorOutputs.encryptedOutput2),
+    } : null,
+    payouts: extData.payouts ? extData.payouts.map((payout) => ({
+      recipient: new PublicKey(payout.recipient).toBytes(),
+      amount: new BN(payout.amount.toString()),
+    })) : null,
+    association: extData.association ? {
+      associationSet: new PublicKey(extData.association.associationSet).toBytes(),
+      root: Buffer.from(extData.association.root),
+      proofA: Buffer.from(extData.association.proofA),
+      proofB: Buffer.from(extData.association.proofB),
+      proofC: Buffer.from(extData.association.proofC),
+    } : null,
   };
   
   // Serialize with Borsh
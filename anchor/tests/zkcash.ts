@@ -40,7 +40,19 @@ function findNullifierPDAs(program: anchor.Program<any>, proof: any) {
     program.programId
   );
   
-  return { nullifier0PDA, nullifier1PDA };
+  // A nullifier is spent if it was recorded as either input, so the PDAs of the other slot
+  // are checked too
+  const [nullifier2PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("nullifier0"), Buffer.from(proof.inputNullifiers[1])],
+    program.programId
+  );
+
+  const [nullifier3PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("nullifier1"), Buffer.from(proof.inputNullifiers[0])],
+    program.programId
+  );
+
+  return { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA };
 }
 
 // Find commitment PDAs for the given proof
@@ -55,7 +67,19 @@ function findCommitmentPDAs(program: anchor.Program<any>, proof: any) {
     program.programId
   );
   
-  return { commitment0PDA, commitment1PDA };
+  // A commitment exists if it was recorded as either output, so the PDAs of the other slot
+  // are checked too
+  const [commitment2PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("commitment1"), Buffer.from(proof.outputCommitments[0])],
+    program.programId
+  );
+
+  const [commitment3PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("commitment0"), Buffer.from(proof.outputCommitments[1])],
+    program.programId
+  );
+
+  return { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA };
 }
 
 describe("zkcash", () => {
@@ -332,10 +356,10 @@ describe("zkcash", () => {
     };
 
     // Derive nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
 
     // Derive commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     // Get balances before transaction
     const treeTokenAccountBalanceBefore = await provider.connection.getBalance(treeTokenAccountPDA);
@@ -354,8 +378,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: nullifier0PDA,
         nullifier1: nullifier1PDA,
+        nullifier2: nullifier2PDA,
+        nullifier3: nullifier3PDA,
         commitment0: commitment0PDA,
         commitment1: commitment1PDA,
+        commitment2: commitment2PDA,
+        commitment3: commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -562,8 +590,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: withdrawNullifiers.nullifier0PDA,
         nullifier1: withdrawNullifiers.nullifier1PDA,
+        nullifier2: withdrawNullifiers.nullifier2PDA,
+        nullifier3: withdrawNullifiers.nullifier3PDA,
         commitment0: withdrawCommitments.commitment0PDA,
         commitment1: withdrawCommitments.commitment1PDA,
+        commitment2: withdrawCommitments.commitment2PDA,
+        commitment3: withdrawCommitments.commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -779,10 +811,10 @@ describe("zkcash", () => {
     };
 
     // Derive nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
 
     // Derive commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     // Get balances before transaction
     const treeTokenAccountBalanceBefore = await provider.connection.getBalance(treeTokenAccountPDA);
@@ -801,8 +833,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: nullifier0PDA,
         nullifier1: nullifier1PDA,
+        nullifier2: nullifier2PDA,
+        nullifier3: nullifier3PDA,
         commitment0: commitment0PDA,
         commitment1: commitment1PDA,
+        commitment2: commitment2PDA,
+        commitment3: commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -1009,8 +1045,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: withdrawNullifiers.nullifier0PDA,
         nullifier1: withdrawNullifiers.nullifier1PDA,
+        nullifier2: withdrawNullifiers.nullifier2PDA,
+        nullifier3: withdrawNullifiers.nullifier3PDA,
         commitment0: withdrawCommitments.commitment0PDA,
         commitment1: withdrawCommitments.commitment1PDA,
+        commitment2: withdrawCommitments.commitment2PDA,
+        commitment3: withdrawCommitments.commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -1226,10 +1266,10 @@ describe("zkcash", () => {
     };
 
     // Derive nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
 
     // Derive commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     // Get balances before transaction
     const treeTokenAccountBalanceBefore = await provider.connection.getBalance(treeTokenAccountPDA);
@@ -1248,8 +1288,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: nullifier0PDA,
         nullifier1: nullifier1PDA,
+        nullifier2: nullifier2PDA,
+        nullifier3: nullifier3PDA,
         commitment0: commitment0PDA,
         commitment1: commitment1PDA,
+        commitment2: commitment2PDA,
+        commitment3: commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -1456,8 +1500,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: withdrawNullifiers.nullifier0PDA,
         nullifier1: withdrawNullifiers.nullifier1PDA,
+        nullifier2: withdrawNullifiers.nullifier2PDA,
+        nullifier3: withdrawNullifiers.nullifier3PDA,
         commitment0: withdrawCommitments.commitment0PDA,
         commitment1: withdrawCommitments.commitment1PDA,
+        commitment2: withdrawCommitments.commitment2PDA,
+        commitment3: withdrawCommitments.commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -1676,10 +1724,10 @@ describe("zkcash", () => {
     };
 
     // Derive nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
 
     // Derive commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     // Get balances before transaction
     const treeTokenAccountBalanceBefore = await provider.connection.getBalance(treeTokenAccountPDA);
@@ -1698,8 +1746,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: nullifier0PDA,
         nullifier1: nullifier1PDA,
+        nullifier2: nullifier2PDA,
+        nullifier3: nullifier3PDA,
         commitment0: commitment0PDA,
         commitment1: commitment1PDA,
+        commitment2: commitment2PDA,
+        commitment3: commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -1906,8 +1958,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: withdrawNullifiers.nullifier0PDA,
         nullifier1: withdrawNullifiers.nullifier1PDA,
+        nullifier2: withdrawNullifiers.nullifier2PDA,
+        nullifier3: withdrawNullifiers.nullifier3PDA,
         commitment0: withdrawCommitments.commitment0PDA,
         commitment1: withdrawCommitments.commitment1PDA,
+        commitment2: withdrawCommitments.commitment2PDA,
+        commitment3: withdrawCommitments.commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -2126,10 +2182,10 @@ describe("zkcash", () => {
     };
 
     // Derive nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
 
     // Derive commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     // Get balances before transaction
     const treeTokenAccountBalanceBefore = await provider.connection.getBalance(treeTokenAccountPDA);
@@ -2148,8 +2204,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: nullifier0PDA,
         nullifier1: nullifier1PDA,
+        nullifier2: nullifier2PDA,
+        nullifier3: nullifier3PDA,
         commitment0: commitment0PDA,
         commitment1: commitment1PDA,
+        commitment2: commitment2PDA,
+        commitment3: commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -2356,8 +2416,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: withdrawNullifiers.nullifier0PDA,
         nullifier1: withdrawNullifiers.nullifier1PDA,
+        nullifier2: withdrawNullifiers.nullifier2PDA,
+        nullifier3: withdrawNullifiers.nullifier3PDA,
         commitment0: withdrawCommitments.commitment0PDA,
         commitment1: withdrawCommitments.commitment1PDA,
+        commitment2: withdrawCommitments.commitment2PDA,
+        commitment3: withdrawCommitments.commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -2576,10 +2640,10 @@ describe("zkcash", () => {
     };
 
     // Derive nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
 
     // Derive commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     // Get balances before transaction
     const treeTokenAccountBalanceBefore = await provider.connection.getBalance(treeTokenAccountPDA);
@@ -2598,8 +2662,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: nullifier0PDA,
         nullifier1: nullifier1PDA,
+        nullifier2: nullifier2PDA,
+        nullifier3: nullifier3PDA,
         commitment0: commitment0PDA,
         commitment1: commitment1PDA,
+        commitment2: commitment2PDA,
+        commitment3: commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -2806,8 +2874,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: withdrawNullifiers.nullifier0PDA,
         nullifier1: withdrawNullifiers.nullifier1PDA,
+        nullifier2: withdrawNullifiers.nullifier2PDA,
+        nullifier3: withdrawNullifiers.nullifier3PDA,
         commitment0: withdrawCommitments.commitment0PDA,
         commitment1: withdrawCommitments.commitment1PDA,
+        commitment2: withdrawCommitments.commitment2PDA,
+        commitment3: withdrawCommitments.commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -3042,10 +3114,10 @@ describe("zkcash", () => {
     };
     
     // Derive nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
     
     // Derive commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
     
     // Set compute budget for the transaction
     const modifyComputeUnits = anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ 
@@ -3060,8 +3132,12 @@ describe("zkcash", () => {
           treeAccount: treeAccountPDA,
           nullifier0: nullifier0PDA,
           nullifier1: nullifier1PDA,
+          nullifier2: nullifier2PDA,
+          nullifier3: nullifier3PDA,
           commitment0: commitment0PDA,
           commitment1: commitment1PDA,
+          commitment2: commitment2PDA,
+          commitment3: commitment3PDA,
           recipient: recipient.publicKey,
           feeRecipientAccount: feeRecipient.publicKey,
           treeTokenAccount: treeTokenAccountPDA,
@@ -3232,10 +3308,10 @@ describe("zkcash", () => {
     };
 
     // Derive nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
     
     // Derive commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     const modifyComputeUnits = anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ 
       units: 1_000_000 
@@ -3248,8 +3324,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: nullifier0PDA,
         nullifier1: nullifier1PDA,
+        nullifier2: nullifier2PDA,
+        nullifier3: nullifier3PDA,
         commitment0: commitment0PDA,
         commitment1: commitment1PDA,
+        commitment2: commitment2PDA,
+        commitment3: commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -3412,8 +3492,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: withdrawNullifiers.nullifier0PDA,
         nullifier1: withdrawNullifiers.nullifier1PDA,
+        nullifier2: withdrawNullifiers.nullifier2PDA,
+        nullifier3: withdrawNullifiers.nullifier3PDA,
         commitment0: withdrawCommitments.commitment0PDA,
         commitment1: withdrawCommitments.commitment1PDA,
+        commitment2: withdrawCommitments.commitment2PDA,
+        commitment3: withdrawCommitments.commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -3473,8 +3557,12 @@ describe("zkcash", () => {
           treeAccount: treeAccountPDA,
           nullifier0: withdrawNullifiers.nullifier0PDA,
           nullifier1: withdrawNullifiers.nullifier1PDA,
+          nullifier2: withdrawNullifiers.nullifier2PDA,
+          nullifier3: withdrawNullifiers.nullifier3PDA,
           commitment0: secondWithdrawCommitments.commitment0PDA,
           commitment1: secondWithdrawCommitments.commitment1PDA,
+          commitment2: secondWithdrawCommitments.commitment2PDA,
+          commitment3: secondWithdrawCommitments.commitment3PDA,
           recipient: recipient.publicKey,
           feeRecipientAccount: feeRecipient.publicKey,
           treeTokenAccount: treeTokenAccountPDA,
@@ -3500,7 +3588,7 @@ describe("zkcash", () => {
       // Sign the transaction
       failingTransactionV0.sign([randomUser]);
 
-      // We expect this to fail before this point due to nullifier PDA creation constraint
+      // We expect this to fail with NullifierAlreadySpent during preflight
       // But we're simulating the transaction exactly the same way to ensure consistency
       const failingTxSig = await provider.connection.sendTransaction(failingTransactionV0, {
         skipPreflight: false,
@@ -3510,16 +3598,9 @@ describe("zkcash", () => {
       // If we get here, the transaction succeeded which is unexpected
       expect.fail("Transaction should have failed due to nullifier reuse but succeeded");
     } catch (error) {
-      // For versioned transactions, check for specific error patterns
+      // Reused nullifiers are detected before account creation
       const errorString = error.toString();
-      expect(
-        errorString.includes("ConstraintSeeds") || 
-        errorString.includes("0x7d6") || // ConstraintSeeds code
-        errorString.includes("constraint was violated") ||
-        errorString.includes("account already exists") ||
-        errorString.includes("failed") ||
-        errorString.includes("Error")
-      ).to.be.true;
+      expect(errorString).to.include("0x177f"); // NullifierAlreadySpent
     }
   });
 
@@ -3564,10 +3645,10 @@ describe("zkcash", () => {
     };
 
     // Get nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proof);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proof);
     
     // Get commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proof);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proof);
 
     try {
       // Create the compute units instruction
@@ -3582,8 +3663,12 @@ describe("zkcash", () => {
           treeAccount: treeAccountPDA,
           nullifier0: nullifier0PDA,
           nullifier1: nullifier1PDA,
+          nullifier2: nullifier2PDA,
+          nullifier3: nullifier3PDA,
           commitment0: commitment0PDA,
           commitment1: commitment1PDA,
+          commitment2: commitment2PDA,
+          commitment3: commitment3PDA,
           recipient: recipient.publicKey,
           feeRecipientAccount: feeRecipient.publicKey,
           treeTokenAccount: treeTokenAccountPDA,
@@ -3659,10 +3744,10 @@ describe("zkcash", () => {
     };
 
     // Get nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proof);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proof);
     
     // Get commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proof);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proof);
 
     try {
       // Create the compute units instruction
@@ -3677,8 +3762,12 @@ describe("zkcash", () => {
           treeAccount: treeAccountPDA,
           nullifier0: nullifier0PDA,
           nullifier1: nullifier1PDA,
+          nullifier2: nullifier2PDA,
+          nullifier3: nullifier3PDA,
           commitment0: commitment0PDA,
           commitment1: commitment1PDA,
+          commitment2: commitment2PDA,
+          commitment3: commitment3PDA,
           recipient: recipient.publicKey,
           feeRecipientAccount: feeRecipient.publicKey,
           treeTokenAccount: treeTokenAccountPDA,
@@ -3758,10 +3847,10 @@ describe("zkcash", () => {
     };
 
     // Get nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proof);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proof);
     
     // Get commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proof);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proof);
 
     try {
       // Create the compute units instruction
@@ -3776,8 +3865,12 @@ describe("zkcash", () => {
           treeAccount: treeAccountPDA,
           nullifier0: nullifier0PDA,
           nullifier1: nullifier1PDA,
+          nullifier2: nullifier2PDA,
+          nullifier3: nullifier3PDA,
           commitment0: commitment0PDA,
           commitment1: commitment1PDA,
+          commitment2: commitment2PDA,
+          commitment3: commitment3PDA,
           recipient: recipient.publicKey,
           feeRecipientAccount: feeRecipient.publicKey,
           treeTokenAccount: treeTokenAccountPDA,
@@ -3938,10 +4031,10 @@ describe("zkcash", () => {
     };
 
     // Derive nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
     
     // Derive commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     try {
       // Create the compute units instruction
@@ -3956,8 +4049,12 @@ describe("zkcash", () => {
           treeAccount: treeAccountPDA,
           nullifier0: nullifier0PDA,
           nullifier1: nullifier1PDA,
+          nullifier2: nullifier2PDA,
+          nullifier3: nullifier3PDA,
           commitment0: commitment0PDA,
           commitment1: commitment1PDA,
+          commitment2: commitment2PDA,
+          commitment3: commitment3PDA,
           recipient: recipient.publicKey,
           feeRecipientAccount: feeRecipient.publicKey,
           treeTokenAccount: treeTokenAccountPDA,
@@ -4044,10 +4141,10 @@ describe("zkcash", () => {
     };
 
     // Find nullifier PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, validProof);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, validProof);
     
     // Find commitment PDAs
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, validProof);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, validProof);
     
     try {
       // Create the compute units instruction
@@ -4063,8 +4160,12 @@ describe("zkcash", () => {
           treeAccount: treeAccountPDA,
           nullifier0: nullifier0PDA,
           nullifier1: nullifier1PDA,
+          nullifier2: nullifier2PDA,
+          nullifier3: nullifier3PDA,
           commitment0: commitment0PDA,
           commitment1: commitment1PDA,
+          commitment2: commitment2PDA,
+          commitment3: commitment3PDA,
           recipient: recipient.publicKey,
           feeRecipientAccount: feeRecipient.publicKey,
           treeTokenAccount: treeTokenAccountPDA,
@@ -4292,8 +4393,8 @@ describe("zkcash", () => {
     };
 
     // Derive PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     try {
       // Execute the transaction - should fail
@@ -4307,8 +4408,12 @@ describe("zkcash", () => {
           treeAccount: treeAccountPDA,
           nullifier0: nullifier0PDA,
           nullifier1: nullifier1PDA,
+          nullifier2: nullifier2PDA,
+          nullifier3: nullifier3PDA,
           commitment0: commitment0PDA,
           commitment1: commitment1PDA,
+          commitment2: commitment2PDA,
+          commitment3: commitment3PDA,
           recipient: recipient.publicKey,
           feeRecipientAccount: feeRecipient.publicKey,
           treeTokenAccount: treeTokenAccountPDA,
@@ -4488,8 +4593,8 @@ describe("zkcash", () => {
     };
 
     // Derive PDAs
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     // Execute the transaction - should now succeed
     const modifyComputeUnits = anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ 
@@ -4502,8 +4607,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: nullifier0PDA,
         nullifier1: nullifier1PDA,
+        nullifier2: nullifier2PDA,
+        nullifier3: nullifier3PDA,
         commitment0: commitment0PDA,
         commitment1: commitment1PDA,
+        commitment2: commitment2PDA,
+        commitment3: commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -4617,8 +4726,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: depositNullifiers.nullifier0PDA,
         nullifier1: depositNullifiers.nullifier1PDA,
+        nullifier2: depositNullifiers.nullifier2PDA,
+        nullifier3: depositNullifiers.nullifier3PDA,
         commitment0: depositCommitments.commitment0PDA,
         commitment1: depositCommitments.commitment1PDA,
+        commitment2: depositCommitments.commitment2PDA,
+        commitment3: depositCommitments.commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -4727,8 +4840,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: withdrawNullifiers.nullifier0PDA,
         nullifier1: withdrawNullifiers.nullifier1PDA,
+        nullifier2: withdrawNullifiers.nullifier2PDA,
+        nullifier3: withdrawNullifiers.nullifier3PDA,
         commitment0: withdrawCommitments.commitment0PDA,
         commitment1: withdrawCommitments.commitment1PDA,
+        commitment2: withdrawCommitments.commitment2PDA,
+        commitment3: withdrawCommitments.commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -4835,8 +4952,8 @@ describe("zkcash", () => {
     };
 
     // Derive nullifier and commitment PDAs for deposit
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(program, proofToSubmit);
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(program, proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(program, proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(program, proofToSubmit);
 
     // Execute the deposit transaction
     const modifyComputeUnits = anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ 
@@ -4849,8 +4966,12 @@ describe("zkcash", () => {
         treeAccount: treeAccountPDA,
         nullifier0: nullifier0PDA,
         nullifier1: nullifier1PDA,
+        nullifier2: nullifier2PDA,
+        nullifier3: nullifier3PDA,
         commitment0: commitment0PDA,
         commitment1: commitment1PDA,
+        commitment2: commitment2PDA,
+        commitment3: commitment3PDA,
         recipient: recipient.publicKey,
         feeRecipientAccount: feeRecipient.publicKey,
         treeTokenAccount: treeTokenAccountPDA,
@@ -4987,8 +5108,12 @@ describe("zkcash", () => {
           treeAccount: treeAccountPDA,
           nullifier0: withdrawNullifiers.nullifier0PDA,
           nullifier1: withdrawNullifiers.nullifier1PDA,
+          nullifier2: withdrawNullifiers.nullifier2PDA,
+          nullifier3: withdrawNullifiers.nullifier3PDA,
           commitment0: withdrawCommitments.commitment0PDA,
           commitment1: withdrawCommitments.commitment1PDA,
+          commitment2: withdrawCommitments.commitment2PDA,
+          commitment3: withdrawCommitments.commitment3PDA,
           recipient: recipient.publicKey,
           feeRecipientAccount: feeRecipient.publicKey,
           treeTokenAccount: treeTokenAccountPDA,
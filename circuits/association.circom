@@ -0,0 +1,74 @@
+pragma circom 2.0.0;
+
+include "../scripts/node_modules/circomlib/circuits/poseidon.circom";
+include "./merkleProof.circom";
+include "./keypair.circom";
+
+/*
+Proves that every input of a transaction is in an association set, a Merkle tree of
+approved commitments published by a provider. The nullifiers are the public inputs of the
+transaction proof too, and a nullifier opens to a single commitment, so the proof is about
+the inputs that transaction spends without revealing them.
+
+commitment = hash(amount, pubKey, blinding, mintAddress)
+nullifier = hash(commitment, merklePath, sign(privKey, commitment, merklePath))
+*/
+template AssociationMembership(levels, nIns) {
+    signal input associationRoot;
+    signal input inputNullifier[nIns];
+
+    signal input inAmount[nIns];
+    signal input inPrivateKey[nIns];
+    signal input inBlinding[nIns];
+    signal input inMintAddress[nIns];
+    // the position of the input in the pool's tree, part of its nullifier
+    signal input inPathIndices[nIns];
+    // the position of the input in the association set
+    signal input associationPathIndices[nIns];
+    signal input associationPathElements[nIns][levels];
+
+    component inKeypair[nIns];
+    component inSignature[nIns];
+    component inCommitmentHasher[nIns];
+    component inNullifierHasher[nIns];
+    component associationTree[nIns];
+    component associationCheckRoot[nIns];
+
+    for (var tx = 0; tx < nIns; tx++) {
+        inKeypair[tx] = Keypair();
+        inKeypair[tx].privateKey <== inPrivateKey[tx];
+
+        inCommitmentHasher[tx] = Poseidon(4);
+        inCommitmentHasher[tx].inputs[0] <== inAmount[tx];
+        inCommitmentHasher[tx].inputs[1] <== inKeypair[tx].publicKey;
+        inCommitmentHasher[tx].inputs[2] <== inBlinding[tx];
+        inCommitmentHasher[tx].inputs[3] <== inMintAddress[tx];
+
+        inSignature[tx] = Signature();
+        inSignature[tx].privateKey <== inPrivateKey[tx];
+        inSignature[tx].commitment <== inCommitmentHasher[tx].out;
+        inSignature[tx].merklePath <== inPathIndices[tx];
+
+        inNullifierHasher[tx] = Poseidon(3);
+        inNullifierHasher[tx].inputs[0] <== inCommitmentHasher[tx].out;
+        inNullifierHasher[tx].inputs[1] <== inPathIndices[tx];
+        inNullifierHasher[tx].inputs[2] <== inSignature[tx].out;
+        inNullifierHasher[tx].out === inputNullifier[tx];
+
+        associationTree[tx] = MerkleProof(levels);
+        associationTree[tx].leaf <== inCommitmentHasher[tx].out;
+        associationTree[tx].pathIndices <== associationPathIndices[tx];
+        for (var i = 0; i < levels; i++) {
+            associationTree[tx].pathElements[i] <== associationPathElements[tx][i];
+        }
+
+        // zero amount inputs are padding, like in the transaction circuit
+        associationCheckRoot[tx] = ForceEqualIfEnabled();
+        associationCheckRoot[tx].in[0] <== associationRoot;
+        associationCheckRoot[tx].in[1] <== associationTree[tx].root;
+        associationCheckRoot[tx].enabled <== inAmount[tx];
+    }
+}
+
+// The public inputs are in the order `verify_association_proof` passes them
+component main {public [associationRoot, inputNullifier]} = AssociationMembership(26, 2);
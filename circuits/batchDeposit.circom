@@ -0,0 +1,69 @@
+pragma circom 2.0.0;
+
+include "../scripts/node_modules/circomlib/circuits/poseidon.circom";
+include "../scripts/node_modules/circomlib/circuits/bitify.circom";
+include "../scripts/node_modules/circomlib/circuits/comparators.circom";
+
+/*
+Proves that the outputs of a batch deposit add up to the deposited amount, without revealing
+their amounts or owners. The outputs are hashed into one public input, so the verifying key
+stays the same size however many outputs a batch has.
+
+commitment = hash(amount, pubKey, blinding, mintAddress)
+outputsHash = hash(outputCommitment[0], ..., outputCommitment[nOuts - 1])
+
+A zero commitment is padding, for batches with fewer than nOuts outputs. Its amount must be
+zero, and it doesn't have to open to a note.
+*/
+template BatchDeposit(nOuts) {
+    signal input publicAmount;
+    signal input outputsHash;
+
+    signal input outputCommitment[nOuts];
+    signal input outAmount[nOuts];
+    signal input outPubkey[nOuts];
+    signal input outBlinding[nOuts];
+    signal input outMintAddress[nOuts];
+
+    component outCommitmentHasher[nOuts];
+    component outAmountCheck[nOuts];
+    component outIsPadding[nOuts];
+    component outCheckCommitment[nOuts];
+    var sumOuts = 0;
+
+    for (var tx = 0; tx < nOuts; tx++) {
+        outCommitmentHasher[tx] = Poseidon(4);
+        outCommitmentHasher[tx].inputs[0] <== outAmount[tx];
+        outCommitmentHasher[tx].inputs[1] <== outPubkey[tx];
+        outCommitmentHasher[tx].inputs[2] <== outBlinding[tx];
+        outCommitmentHasher[tx].inputs[3] <== outMintAddress[tx];
+
+        // check the commitment only if it isn't padding
+        outIsPadding[tx] = IsZero();
+        outIsPadding[tx].in <== outputCommitment[tx];
+        outCheckCommitment[tx] = ForceEqualIfEnabled();
+        outCheckCommitment[tx].in[0] <== outputCommitment[tx];
+        outCheckCommitment[tx].in[1] <== outCommitmentHasher[tx].out;
+        outCheckCommitment[tx].enabled <== 1 - outIsPadding[tx].out;
+        outIsPadding[tx].out * outAmount[tx] === 0;
+
+        // Check that amount fits into 248 bits to prevent overflow
+        outAmountCheck[tx] = Num2Bits(248);
+        outAmountCheck[tx].in <== outAmount[tx];
+
+        sumOuts += outAmount[tx];
+    }
+
+    component outputsHasher = Poseidon(nOuts);
+    for (var tx = 0; tx < nOuts; tx++) {
+        outputsHasher.inputs[tx] <== outputCommitment[tx];
+    }
+    outputsHasher.out === outputsHash;
+
+    // verify amount invariant
+    publicAmount === sumOuts;
+}
+
+// nOuts is BATCH_DEPOSIT_OUTPUTS, and the public inputs are in the order
+// `PendingBatch::public_inputs` passes them
+component main {public [publicAmount, outputsHash]} = BatchDeposit(8);
@@ -25,10 +25,15 @@ export async function handleWithdraw(ctx: Context) {
     const requiredFields = [
       'serializedProof', 
       'treeAccount', 
+      'poolConfig',
       'nullifier0PDA',
       'nullifier1PDA',
+      'nullifier2PDA',
+      'nullifier3PDA',
       'commitment0PDA',
       'commitment1PDA',
+      'commitment2PDA',
+      'commitment3PDA',
       'treeTokenAccount',
       'recipient',
       'feeRecipientAccount',
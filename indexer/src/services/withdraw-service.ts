@@ -20,14 +20,28 @@ const TRANSACT_IX_DISCRIMINATOR = Buffer.from([217, 149, 130, 143, 221, 52, 252,
 export interface WithdrawParams {
   serializedProof: string;
   treeAccount: string;
+  // Optional accounts of `transact`, null or missing when not used
+  rootTreeAccount?: string | null;
+  commitmentQueue?: string | null;
+  // Required for every tree but tree 0
+  treeSet?: string | null;
+  poolConfig: string;
   nullifier0PDA: string;
   nullifier1PDA: string;
+  nullifier2PDA: string;
+  nullifier3PDA: string;
   commitment0PDA: string;
   commitment1PDA: string;
+  commitment2PDA: string;
+  commitment3PDA: string;
   treeTokenAccount: string;
   recipient: string;
   feeRecipientAccount: string;
   deployer: string;
+  // The association set named in the ext data and the pool's association verifying key,
+  // only with an association proof
+  associationSet?: string | null;
+  associationVerifyingKey?: string | null;
   extAmount: number;
   encryptedOutput1: string; // Base64 encoded
   encryptedOutput2: string; // Base64 encoded
@@ -125,14 +139,25 @@ function createWithdrawInstruction(
 ): TransactionInstruction {
   // Convert string addresses to PublicKeys
   const treeAccount = new PublicKey(params.treeAccount);
+  // Anchor reads the program id as None for an optional account
+  const rootTreeAccount = params.rootTreeAccount ? new PublicKey(params.rootTreeAccount) : PROGRAM_ID;
+  const commitmentQueue = params.commitmentQueue ? new PublicKey(params.commitmentQueue) : PROGRAM_ID;
+  const treeSet = params.treeSet ? new PublicKey(params.treeSet) : PROGRAM_ID;
+  const poolConfig = new PublicKey(params.poolConfig);
   const nullifier0PDA = new PublicKey(params.nullifier0PDA);
   const nullifier1PDA = new PublicKey(params.nullifier1PDA);
+  const nullifier2PDA = new PublicKey(params.nullifier2PDA);
+  const nullifier3PDA = new PublicKey(params.nullifier3PDA);
   const commitment0PDA = new PublicKey(params.commitment0PDA);
   const commitment1PDA = new PublicKey(params.commitment1PDA);
+  const commitment2PDA = new PublicKey(params.commitment2PDA);
+  const commitment3PDA = new PublicKey(params.commitment3PDA);
   const treeTokenAccount = new PublicKey(params.treeTokenAccount);
   const recipient = new PublicKey(params.recipient);
   const feeRecipientAccount = new PublicKey(params.feeRecipientAccount);
   const deployer = new PublicKey(params.deployer);
+  const associationSet = params.associationSet ? new PublicKey(params.associationSet) : PROGRAM_ID;
+  const associationVerifyingKey = params.associationVerifyingKey ? new PublicKey(params.associationVerifyingKey) : PROGRAM_ID;
 
   // The serializedProofData should already contain the full instruction data
   // (discriminator + proof + extData) as created by the client
@@ -143,10 +168,19 @@ function createWithdrawInstruction(
   return new TransactionInstruction({
     keys: [
       { pubkey: treeAccount, isSigner: false, isWritable: true },
+      { pubkey: rootTreeAccount, isSigner: false, isWritable: false },
+      { pubkey: commitmentQueue, isSigner: false, isWritable: !!params.commitmentQueue },
+      { pubkey: treeSet, isSigner: false, isWritable: false },
+      { pubkey: poolConfig, isSigner: false, isWritable: false },
       { pubkey: nullifier0PDA, isSigner: false, isWritable: true },
       { pubkey: nullifier1PDA, isSigner: false, isWritable: true },
+      // the nullifiers' PDAs in the other input slot, only read
+      { pubkey: nullifier2PDA, isSigner: false, isWritable: false },
+      { pubkey: nullifier3PDA, isSigner: false, isWritable: false },
       { pubkey: commitment0PDA, isSigner: false, isWritable: true },
       { pubkey: commitment1PDA, isSigner: false, isWritable: true },
+      { pubkey: commitment2PDA, isSigner: false, isWritable: false },
+      { pubkey: commitment3PDA, isSigner: false, isWritable: false },
       { pubkey: treeTokenAccount, isSigner: false, isWritable: true },
       // recipient
       { pubkey: recipient, isSigner: false, isWritable: true },
@@ -157,6 +191,8 @@ function createWithdrawInstruction(
       // signer (relayer instead of user)
       { pubkey: loadRelayerKeypair().publicKey, isSigner: true, isWritable: true },
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: associationSet, isSigner: false, isWritable: false },
+      { pubkey: associationVerifyingKey, isSigner: false, isWritable: false },
     ],
     programId: PROGRAM_ID,
     data: instructionData,
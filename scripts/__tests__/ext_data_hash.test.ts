@@ -0,0 +1,45 @@
+import { PublicKey } from '@solana/web3.js';
+import { getExtDataHash } from '../utils/utils';
+import 'jest';
+
+// Also pinned by test_ext_data_hash_fixture in anchor/programs/zkcash/tests/unit/utils_test.rs
+const EXT_DATA_HASH_FIXTURE = '25f90ce92ce208d3aa36095cc5e01e70c87c13156e959c7617a981f3285a59fb';
+
+const key = (byte: number) => new PublicKey(Buffer.alloc(32, byte));
+
+describe('getExtDataHash', () => {
+  it('matches the hash of the program for every ext data field', () => {
+    const hash = getExtDataHash({
+      recipient: key(1),
+      extAmount: -600,
+      encryptedOutput1: new Uint8Array([2, 3]),
+      encryptedOutput2: new Uint8Array([4]),
+      fee: 25,
+      call: {
+        programId: key(5),
+        accounts: [
+          { pubkey: key(6), isWritable: true },
+          { pubkey: key(7), isWritable: false },
+        ],
+        data: new Uint8Array([8, 9]),
+      },
+      auditorOutputs: {
+        encryptedOutput1: new Uint8Array([10]),
+        encryptedOutput2: new Uint8Array([11, 12]),
+      },
+      payouts: [
+        { recipient: key(13), amount: 200 },
+        { recipient: key(14), amount: 400 },
+      ],
+      association: {
+        associationSet: key(15),
+        root: new Uint8Array(32).fill(16),
+        proofA: new Uint8Array(64).fill(17),
+        proofB: new Uint8Array(128).fill(18),
+        proofC: new Uint8Array(64).fill(19),
+      },
+    });
+
+    expect(Buffer.from(hash).toString('hex')).toBe(EXT_DATA_HASH_FIXTURE);
+  });
+});
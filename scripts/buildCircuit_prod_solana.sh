@@ -35,54 +35,64 @@ fi
 
 # Check if circuit number is provided
 if [ -z "$1" ]; then
-    echo "Usage: $0 <circuit_number>"
+    echo "Usage: $0 <circuit_number | circuit_name>"
     echo "Example: $0 2 (for transaction2.circom) "
+    echo "Example: $0 association (for association.circom) "
     exit 1
 fi
 
+# A number picks a transaction circuit, anything else names the circuit file
+if [[ "$1" =~ ^[0-9]+$ ]]; then
+    CIRCUIT="transaction$1"
+    VERIFYING_KEY="verifyingkey$1"
+else
+    CIRCUIT="$1"
+    VERIFYING_KEY="verifyingkey_$1"
+fi
+
 # Ensure the circuit file exists
-if [ ! -f "$CIRCUIT_DIR/transaction$1.circom" ]; then
-    echo "Error: Circuit file $CIRCUIT_DIR/transaction$1.circom not found"
+if [ ! -f "$CIRCUIT_DIR/$CIRCUIT.circom" ]; then
+    echo "Error: Circuit file $CIRCUIT_DIR/$CIRCUIT.circom not found"
     echo "Please ensure your circuit files are in the $CIRCUIT_DIR directory"
     exit 1
 fi
 
 echo "===== Circuit Builder for Solana ====="
-echo "Building circuit: transaction$1.circom"
+echo "Building circuit: $CIRCUIT.circom"
 echo "==============================================="
 
 # Compile the circuit
-echo "[1/3] Compiling circuit transaction$1.circom..."
+echo "[1/3] Compiling circuit $CIRCUIT.circom..."
 # Fix the circom command with correct paths
-circom --r1cs --wasm --sym "$CIRCUIT_DIR/transaction$1.circom" -o "$ARTIFACTS_DIR" -l "../scripts/node_modules/circomlib/circuits"
+circom --r1cs --wasm --sym "$CIRCUIT_DIR/$CIRCUIT.circom" -o "$ARTIFACTS_DIR" -l "../scripts/node_modules/circomlib/circuits"
 
 # Add after the circom command in buildCircuit_prod_solana.sh
-cp "$ARTIFACTS_DIR/transaction$1_js/transaction$1.wasm" "$ARTIFACTS_DIR/"
+cp "$ARTIFACTS_DIR/${CIRCUIT}_js/$CIRCUIT.wasm" "$ARTIFACTS_DIR/"
 
 echo "✅ Circuit compilation complete"
 
 # Setup the circuit
 echo "[2/3] Setting up the circuit..."
-npx snarkjs groth16 setup "$ARTIFACTS_DIR/transaction$1.r1cs" "$ARTIFACTS_DIR/$PTAU_FILE" "$ARTIFACTS_DIR/transaction$1_0.zkey"
+npx snarkjs groth16 setup "$ARTIFACTS_DIR/$CIRCUIT.r1cs" "$ARTIFACTS_DIR/$PTAU_FILE" "$ARTIFACTS_DIR/${CIRCUIT}_0.zkey"
 echo "✅ Circuit setup complete"
 
 # Contribute to the ceremony
 echo "[3/3] Contributing to the ceremony..."
-echo "Ceremony Contribution" | npx snarkjs zkey contribute "$ARTIFACTS_DIR/transaction$1_0.zkey" "$ARTIFACTS_DIR/transaction$1.zkey"
+echo "Ceremony Contribution" | npx snarkjs zkey contribute "$ARTIFACTS_DIR/${CIRCUIT}_0.zkey" "$ARTIFACTS_DIR/$CIRCUIT.zkey"
 echo "✅ Contribution complete"
 
 # Convert zkey to json
-npx snarkjs zkey export verificationkey "$ARTIFACTS_DIR/transaction$1.zkey" "$ARTIFACTS_DIR/verifyingkey$1.json"
+npx snarkjs zkey export verificationkey "$ARTIFACTS_DIR/$CIRCUIT.zkey" "$ARTIFACTS_DIR/$VERIFYING_KEY.json"
 echo "✅ Zkey export complete"
 
 # Print circuit info
 echo "📊 Circuit information:"
-npx snarkjs info -r "$ARTIFACTS_DIR/transaction$1.r1cs"
+npx snarkjs info -r "$ARTIFACTS_DIR/$CIRCUIT.r1cs"
 
 echo "🎉 Solana Circuit Build Completed Successfully! 🎉"
 echo "Artifacts are available in $ARTIFACTS_DIR/"
-echo "- R1CS: $ARTIFACTS_DIR/transaction$1.r1cs"
-echo "- WASM: $ARTIFACTS_DIR/transaction$1.wasm"
-echo "- SYM: $ARTIFACTS_DIR/transaction$1.sym"
-echo "- ZKEY: $ARTIFACTS_DIR/transaction$1.zkey"
-echo "- VERIFICATION KEY: $ARTIFACTS_DIR/transaction$1.json"
+echo "- R1CS: $ARTIFACTS_DIR/$CIRCUIT.r1cs"
+echo "- WASM: $ARTIFACTS_DIR/$CIRCUIT.wasm"
+echo "- SYM: $ARTIFACTS_DIR/$CIRCUIT.sym"
+echo "- ZKEY: $ARTIFACTS_DIR/$CIRCUIT.zkey"
+echo "- VERIFICATION KEY: $ARTIFACTS_DIR/$VERIFYING_KEY.json"
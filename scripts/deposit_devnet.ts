@@ -78,7 +78,18 @@ function findNullifierPDAs(proof: any) {
     PROGRAM_ID
   );
   
-  return { nullifier0PDA, nullifier1PDA };
+  // The same nullifiers in the other input slot, so a note can't be spent twice through both slots
+  const [nullifier2PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("nullifier0"), Buffer.from(proof.inputNullifiers[1])],
+    PROGRAM_ID
+  );
+
+  const [nullifier3PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("nullifier1"), Buffer.from(proof.inputNullifiers[0])],
+    PROGRAM_ID
+  );
+  
+  return { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA };
 }
 
 // Find commitment PDAs for the given proof
@@ -93,7 +104,19 @@ function findCommitmentPDAs(proof: any) {
     PROGRAM_ID
   );
   
-  return { commitment0PDA, commitment1PDA };
+  // A commitment exists if it was recorded as either output, so the PDAs of the other slot
+  // are checked too
+  const [commitment2PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("commitment1"), Buffer.from(proof.outputCommitments[0])],
+    PROGRAM_ID
+  );
+
+  const [commitment3PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("commitment0"), Buffer.from(proof.outputCommitments[1])],
+    PROGRAM_ID
+  );
+
+  return { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA };
 }
 
 // Function to get tree state
@@ -162,12 +185,18 @@ async function main() {
       PROGRAM_ID
     );
 
+    const [poolConfig] = PublicKey.findProgramAddressSync(
+      [Buffer.from('pool_config'), deployer.toBuffer()],
+      PROGRAM_ID
+    );
+
     // Fee recipient is now a specific account for receiving fees
     const feeRecipientAccount = FEE_RECIPIENT_ACCOUNT;
 
     console.log('Using PDAs:');
     console.log(`Tree Account: ${treeAccount.toString()}`);
     console.log(`Tree Token Account: ${treeTokenAccount.toString()}`);
+    console.log(`Pool Config: ${poolConfig.toString()}`);
     console.log(`Fee Recipient Account (regular account): ${feeRecipientAccount.toString()}`);
 
     // Create the merkle tree with the pre-initialized poseidon hash
@@ -445,8 +474,8 @@ async function main() {
     };
 
     // Find PDAs for nullifiers and commitments
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(proofToSubmit);
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(proofToSubmit);
 
     console.log('Submitting deposit transaction...');
     
@@ -542,6 +571,22 @@ async function main() {
       extDataBuf.writeBigUInt64LE(BigInt(extData.fee.toString()), extOffset);
       extOffset += 8;
       
+      // call (Option<WithdrawCall>) - None
+      extDataBuf.writeUInt8(0, extOffset);
+      extOffset += 1;
+      
+      // auditor_outputs (Option<AuditorOutputs>) - None
+      extDataBuf.writeUInt8(0, extOffset);
+      extOffset += 1;
+      
+      // payouts (Option<Vec<Payout>>) - None
+      extDataBuf.writeUInt8(0, extOffset);
+      extOffset += 1;
+      
+      // association (Option<AssociationProof>) - None
+      extDataBuf.writeUInt8(0, extOffset);
+      extOffset += 1;
+      
       // Combine instruction discriminator with proof and extData
       const instructionData = Buffer.concat([
         TRANSACT_IX_DISCRIMINATOR,
@@ -560,20 +605,34 @@ async function main() {
     const instruction = new TransactionInstruction({
       keys: [
         { pubkey: treeAccount, isSigner: false, isWritable: true },
+        // root tree account: none, the root is one of the tree's (Anchor reads the program id as None)
+        { pubkey: PROGRAM_ID, isSigner: false, isWritable: false },
+        // commitment queue: none, the tree inserts directly
+        { pubkey: PROGRAM_ID, isSigner: false, isWritable: false },
+        // tree set: none, tree 0 takes outputs without it
+        { pubkey: PROGRAM_ID, isSigner: false, isWritable: false },
+        { pubkey: poolConfig, isSigner: false, isWritable: false },
         { pubkey: nullifier0PDA, isSigner: false, isWritable: true },
         { pubkey: nullifier1PDA, isSigner: false, isWritable: true },
+        { pubkey: nullifier2PDA, isSigner: false, isWritable: false },
+        { pubkey: nullifier3PDA, isSigner: false, isWritable: false },
         { pubkey: commitment0PDA, isSigner: false, isWritable: true },
         { pubkey: commitment1PDA, isSigner: false, isWritable: true },
+        { pubkey: commitment2PDA, isSigner: false, isWritable: false },
+        { pubkey: commitment3PDA, isSigner: false, isWritable: false },
         { pubkey: treeTokenAccount, isSigner: false, isWritable: true },
         // recipient
         { pubkey: user.publicKey, isSigner: false, isWritable: true },
         // fee recipient
         { pubkey: feeRecipientAccount, isSigner: false, isWritable: true },
-        // fee recipient
+        // authority
         { pubkey: deployer, isSigner: false, isWritable: false },
         // signer
         { pubkey: user.publicKey, isSigner: true, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        // association set and verifying key: none, the deposit has no association proof
+        { pubkey: PROGRAM_ID, isSigner: false, isWritable: false },
+        { pubkey: PROGRAM_ID, isSigner: false, isWritable: false },
       ],
       programId: PROGRAM_ID,
       data: serializedProof,
@@ -22,7 +22,8 @@ interface VerificationKey {
 }
 
 async function main(): Promise<void> {
-  const inputPath = '../artifacts/circuits/verifyingkey2.json';
+  // The transaction circuit by default, or e.g. ../artifacts/circuits/verifyingkey_association.json
+  const inputPath = process.argv[2] ?? '../artifacts/circuits/verifyingkey2.json';
 
   // Save original console.log
   const originalConsoleLog = console.log;
@@ -67,9 +67,15 @@ async function initialize() {
       PROGRAM_ID
     );
 
+    const [poolConfig] = PublicKey.findProgramAddressSync(
+      [Buffer.from('pool_config'), payer.publicKey.toBuffer()],
+      PROGRAM_ID
+    );
+
     console.log('Generated PDAs:');
     console.log(`Tree Account: ${treeAccount.toString()}`);
     console.log(`Tree Token Account: ${treeTokenAccount.toString()}`);
+    console.log(`Pool Config: ${poolConfig.toString()}`);
 
     // Create instruction data - just the discriminator for initialize
     const data = INITIALIZE_IX_DISCRIMINATOR;
@@ -80,6 +86,7 @@ async function initialize() {
       keys: [
         { pubkey: treeAccount, isSigner: false, isWritable: true },
         { pubkey: treeTokenAccount, isSigner: false, isWritable: true },
+        { pubkey: poolConfig, isSigner: false, isWritable: true },
         { pubkey: payer.publicKey, isSigner: true, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
       ],
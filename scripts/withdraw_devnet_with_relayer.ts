@@ -82,7 +82,18 @@ function findNullifierPDAs(proof: any) {
     PROGRAM_ID
   );
   
-  return { nullifier0PDA, nullifier1PDA };
+  // The same nullifiers in the other input slot, so a note can't be spent twice through both slots
+  const [nullifier2PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("nullifier0"), Buffer.from(proof.inputNullifiers[1])],
+    PROGRAM_ID
+  );
+
+  const [nullifier3PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("nullifier1"), Buffer.from(proof.inputNullifiers[0])],
+    PROGRAM_ID
+  );
+  
+  return { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA };
 }
 
 // Find commitment PDAs for the given proof
@@ -97,7 +108,19 @@ function findCommitmentPDAs(proof: any) {
     PROGRAM_ID
   );
   
-  return { commitment0PDA, commitment1PDA };
+  // A commitment exists if it was recorded as either output, so the PDAs of the other slot
+  // are checked too
+  const [commitment2PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("commitment1"), Buffer.from(proof.outputCommitments[0])],
+    PROGRAM_ID
+  );
+
+  const [commitment3PDA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("commitment0"), Buffer.from(proof.outputCommitments[1])],
+    PROGRAM_ID
+  );
+
+  return { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA };
 }
 
 // Function to serialize proof and extData (same as original withdraw script)
@@ -187,6 +210,22 @@ function serializeProofAndExtData(proof: any, extData: any) {
   extDataBuf.writeBigUInt64LE(BigInt(extData.fee.toString()), extOffset);
   extOffset += 8;
   
+  // call (Option<WithdrawCall>) - None
+  extDataBuf.writeUInt8(0, extOffset);
+  extOffset += 1;
+  
+  // auditor_outputs (Option<AuditorOutputs>) - None
+  extDataBuf.writeUInt8(0, extOffset);
+  extOffset += 1;
+  
+  // payouts (Option<Vec<Payout>>) - None
+  extDataBuf.writeUInt8(0, extOffset);
+  extOffset += 1;
+  
+  // association (Option<AssociationProof>) - None
+  extDataBuf.writeUInt8(0, extOffset);
+  extOffset += 1;
+  
   // Combine instruction discriminator with proof and extData
   const instructionData = Buffer.concat([
     TRANSACT_IX_DISCRIMINATOR,
@@ -256,12 +295,18 @@ async function main() {
       PROGRAM_ID
     );
 
+    const [poolConfig] = PublicKey.findProgramAddressSync(
+      [Buffer.from('pool_config'), deployer.toBuffer()],
+      PROGRAM_ID
+    );
+
     // Fee recipient is now a specific account for receiving fees
     const feeRecipientAccount = FEE_RECIPIENT_ACCOUNT;
 
     console.log('Using PDAs:');
     console.log(`Tree Account: ${treeAccount.toString()}`);
     console.log(`Tree Token Account: ${treeTokenAccount.toString()}`);
+    console.log(`Pool Config: ${poolConfig.toString()}`);
     console.log(`Fee Recipient Account (regular account): ${feeRecipientAccount.toString()}`);
 
     // Get all relevant balances before transaction
@@ -489,8 +534,8 @@ async function main() {
     };
 
     // Find PDAs for nullifiers and commitments
-    const { nullifier0PDA, nullifier1PDA } = findNullifierPDAs(proofToSubmit);
-    const { commitment0PDA, commitment1PDA } = findCommitmentPDAs(proofToSubmit);
+    const { nullifier0PDA, nullifier1PDA, nullifier2PDA, nullifier3PDA } = findNullifierPDAs(proofToSubmit);
+    const { commitment0PDA, commitment1PDA, commitment2PDA, commitment3PDA } = findCommitmentPDAs(proofToSubmit);
 
     // Serialize the proof and extData
     const serializedProof = serializeProofAndExtData(proofToSubmit, extData);
@@ -500,10 +545,19 @@ async function main() {
     const withdrawParams = {
       serializedProof: serializedProof.toString('base64'),
       treeAccount: treeAccount.toString(),
+      // The relayer passes the program id for the optional accounts that are not given
+      rootTreeAccount: null,
+      commitmentQueue: null,
+      treeSet: null, // tree 0 takes outputs without the tree set
+      poolConfig: poolConfig.toString(),
       nullifier0PDA: nullifier0PDA.toString(),
       nullifier1PDA: nullifier1PDA.toString(),
+      nullifier2PDA: nullifier2PDA.toString(),
+      nullifier3PDA: nullifier3PDA.toString(),
       commitment0PDA: commitment0PDA.toString(),
       commitment1PDA: commitment1PDA.toString(),
+      commitment2PDA: commitment2PDA.toString(),
+      commitment3PDA: commitment3PDA.toString(),
       treeTokenAccount: treeTokenAccount.toString(),
       recipient: RECIPIENT_ADDRESS.toString(),
       feeRecipientAccount: feeRecipientAccount.toString(),